@@ -0,0 +1,1386 @@
+//! The engine's own player.
+//!
+//! [`ChessAI`] searches a position with negamax alpha-beta and iterative deepening: each
+//! iteration searches one ply deeper than the last, ordering the previous principal variation
+//! first, so the search can be stopped at any time with the best move found so far.
+//!
+//! On top of the plain search it uses
+//! - aspiration windows, searching each iteration with a narrow window around the previous
+//!   score and widening it only when the score falls outside,
+//! - principal variation search, proving every move after the first worse with a null window
+//!   and searching it fully only when that fails,
+//! - a quiescence search of captures and promotions at the horizon, so positions are only
+//!   evaluated once they are quiet,
+//! - check extensions, searching one ply deeper when the side to move is in check,
+//! - mate distance scores, so a faster mate is preferred and a slower loss is chosen when
+//!   mated,
+//! - move ordering by the transposition table's move, then captures that don't lose material
+//!   by [`Board::see`], then killer moves, countermoves and the history of quiet moves,
+//! - late move reductions, searching quiet moves ordered late one ply shallower unless they
+//!   turn out better than expected,
+//! - null move pruning, letting the opponent move twice and cutting the node off when that
+//!   still fails to bring the score below beta. Positions with only pawns left are skipped, as
+//!   there passing is often the best move and zugzwang makes the test unsound.
+//!
+//! Positions are scored by an [`Evaluator`], by default the [`StandardEvaluator`], what
+//! the search learns about each position is kept in a [`TranspositionTable`], and searches on
+//! the clock are timed by a [`TimeManager`]. With an [`OpeningBook`] set, moves the book knows
+//! are played without searching. With [`Tablebases`] set, root moves that would throw away the
+//! tablebase result are left out, and positions inside the tables end the search with their
+//! result as a score.
+//!
+//! A search can also rank several root moves, see [`ChessAI::set_multi_pv`]: each iteration
+//! first searches every move for the best one, then the others for the second best, and so on,
+//! so each of the lines gets an exact score rather than a bound.
+//!
+//! With more than one thread the search is a Lazy SMP search: helper threads run the same
+//! iterative deepening as the main thread, sharing nothing but the transposition table and the
+//! node count. Threads reaching positions at different times fill the table for each other,
+//! and helpers starting on odd depths spread the work further. Only the main thread times the
+//! search and reports, and the helpers stop when it is done. A single thread searches
+//! deterministically, the same position and limits visiting the same nodes every time.
+pub mod book;
+pub mod eval;
+pub mod skill;
+pub mod syzygy;
+pub mod time_manager;
+pub mod tt;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::board::pieces::{Color, Piece};
+use crate::board::see::piece_value;
+use crate::board::zobrist::ZobristKey;
+use crate::board::{Board, Square};
+use crate::chess_moves::packed_move::PackedMove;
+use crate::chess_moves::{ChessMove, MoveList, MoveUndo};
+use crate::ai::book::OpeningBook;
+use crate::ai::eval::{Evaluator, StandardEvaluator};
+use crate::ai::skill::{NoisyEvaluator, SkillLevel, SKILL_LINES};
+use crate::ai::syzygy::{Tablebases, Wdl};
+use crate::ai::time_manager::TimeManager;
+use crate::ai::tt::{Bound, TranspositionTable, DEFAULT_HASH_MB};
+use crate::engine::{Score, SearchInfo, SearchLimits, SearchResult, SearchSignals};
+use crate::rules::draw::{self, PositionHistory};
+
+/// Score of being mated on the board. Mate in n plies scores `MATE_SCORE - n`.
+pub const MATE_SCORE: i32 = 100_000;
+/// Scores beyond this are mates.
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+/// Deepest a search line may go, extensions and quiescence included.
+pub const MAX_PLY: usize = 128;
+const MAX_DEPTH: u8 = 64;
+/// Score of a tablebase win reached after n plies is `TB_WIN_SCORE - n`, below any mate.
+pub const TB_WIN_SCORE: i32 = 20_000;
+/// Half width of the first aspiration window, in centipawns.
+const ASPIRATION_WINDOW: i32 = 25;
+/// Iterations from this depth on start with an aspiration window.
+const ASPIRATION_DEPTH: u8 = 4;
+/// Nodes between checks of the stop conditions.
+const CHECK_INTERVAL: u64 = 1024;
+/// Longest sleep while pausing before a weakened AI answers, so a stop is noticed quickly.
+const PAUSE_STEP: Duration = Duration::from_millis(5);
+pub const MAX_THREADS: usize = 256;
+/// Most lines a search may report, see [`ChessAI::set_multi_pv`].
+pub const MAX_MULTI_PV: usize = 256;
+/// Shallowest remaining depth at which null moves are tried.
+const NULL_MOVE_DEPTH: u8 = 3;
+/// Plies a null move's search is reduced by, on top of the move itself.
+const NULL_MOVE_REDUCTION: u8 = 2;
+/// Shallowest remaining depth at which late moves are reduced.
+const LMR_DEPTH: u8 = 3;
+/// Moves searched at full depth before later ones are reduced.
+const LMR_MOVES: usize = 3;
+/// History scores stay within plus or minus this.
+const MAX_HISTORY: i32 = 16_384;
+
+// Move ordering classes, above and below any history score
+const GOOD_CAPTURE: i32 = 1 << 28;
+const KILLER: i32 = 1 << 26;
+const BAD_CAPTURE: i32 = -(1 << 28);
+
+/// A computer player.
+///
+/// Clones share the evaluator and the transposition table, so cloning one is cheap and the
+/// clone searches with everything the original has learnt.
+#[derive(Debug, Clone)]
+pub struct ChessAI {
+    /// Limits for the moves it chooses in a game, see [`ChessAI::limits`].
+    limits: SearchLimits,
+    evaluator: Arc<dyn Evaluator>,
+    table: Arc<TranspositionTable>,
+    /// Threads searching, the main one included.
+    threads: usize,
+    /// Root moves searched and reported with their own principal variation.
+    multi_pv: usize,
+    /// Consulted before searching when choosing a move.
+    book: Option<Arc<OpeningBook>>,
+    /// Probed at the root and inside the search.
+    tablebases: Option<Arc<Tablebases>>,
+    skill: SkillLevel,
+    /// Seeds the evaluation noise of a weakened AI, fixed so its evaluations stay consistent
+    /// from one search to the next.
+    noise_seed: u64,
+    /// State of the generator choosing among moves and think times.
+    random: Arc<AtomicU64>,
+}
+
+/// The result of a search.
+#[derive(Debug, Clone)]
+pub struct SearchOutcome {
+    /// `None` only when the position has no legal move.
+    pub best_move: Option<ChessMove>,
+    pub score: Score,
+    /// The principal variation, starting with `best_move`.
+    pub pv: Vec<ChessMove>,
+    /// Depth of the last completed iteration.
+    pub depth: u8,
+    pub nodes: u64,
+    /// The best root moves' lines, best first, as many as the AI's MultiPV setting asks for.
+    pub lines: Vec<AnalysisLine>,
+}
+
+/// One of the best root moves found by a search, with the line the search expects to follow.
+#[derive(Debug, Clone)]
+pub struct AnalysisLine {
+    pub score: Score,
+    /// The principal variation, starting with the root move.
+    pub pv: Vec<ChessMove>,
+    pub depth: u8,
+}
+
+impl Default for ChessAI {
+    /// An AI thinking a second per move.
+    fn default() -> ChessAI {
+        ChessAI::new(SearchLimits {
+            move_time: Some(Duration::from_secs(1)),
+            ..SearchLimits::default()
+        })
+    }
+}
+
+impl ChessAI {
+    pub fn new(limits: SearchLimits) -> ChessAI {
+        ChessAI {
+            limits,
+            evaluator: Arc::new(StandardEvaluator::default()),
+            table: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            threads: 1,
+            multi_pv: 1,
+            book: None,
+            tablebases: None,
+            skill: SkillLevel::MAX,
+            noise_seed: 0,
+            random: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    /// Replaces the transposition table with an empty one of `megabytes`.
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.table = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    /// Sets the number of threads searching, between 1 and [`MAX_THREADS`].
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.clamp(1, MAX_THREADS);
+    }
+
+    /// Sets how many of the best root moves a search scores exactly and reports, each with its
+    /// own principal variation. More lines make each iteration slower, so a search with the
+    /// same limits ends shallower.
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.clamp(1, MAX_MULTI_PV);
+    }
+
+    /// Analyses `board` at full strength, ranking its best `lines` moves.
+    ///
+    /// # Returns
+    /// Up to `lines` lines, best first, fewer if the position has fewer legal moves.
+    pub fn analyse(
+        &self,
+        board: &Board,
+        history: &PositionHistory,
+        limits: &SearchLimits,
+        lines: usize,
+    ) -> Vec<AnalysisLine> {
+        let mut ai = self.clone();
+        ai.set_multi_pv(lines);
+        ai.skill = SkillLevel::MAX;
+        ai.search(board, history, limits, &SearchSignals::default(), &mut |_| {}).lines
+    }
+
+    /// Sets the opening book to play from, `None` to always search.
+    pub fn set_book(&mut self, book: Option<OpeningBook>) {
+        self.book = book.map(Arc::new);
+    }
+
+    /// Sets the endgame tablebases to probe, `None` to search every position.
+    pub fn set_tablebases(&mut self, tablebases: Option<Tablebases>) {
+        self.tablebases = tablebases.map(Arc::new);
+    }
+
+    /// Weakens the AI to `skill`, [`SkillLevel::MAX`] for full strength.
+    pub fn set_skill(&mut self, skill: SkillLevel) {
+        self.skill = skill;
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        self.noise_seed = skill::mix(seed);
+        self.random = Arc::new(AtomicU64::new(seed));
+    }
+
+    pub fn skill(&self) -> SkillLevel {
+        self.skill
+    }
+
+    fn next_random(&self) -> u64 {
+        skill::mix(self.random.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Waits until the skill level's think time has passed since `start`, but no longer than
+    /// `max_wait` or until `signals` stops the search.
+    pub fn pause(&self, start: Instant, max_wait: Duration, signals: &SearchSignals) {
+        let think_time = self.skill.think_time(self.next_random()).min(max_wait);
+        while start.elapsed() < think_time && !signals.stop.load(Ordering::Relaxed) {
+            thread::sleep(think_time.saturating_sub(start.elapsed()).min(PAUSE_STEP));
+        }
+    }
+
+    /// A move from the opening book for `board`, if the AI has a book that knows the position.
+    pub fn book_move(&self, board: &Board) -> Option<ChessMove> {
+        self.book.as_ref()?.probe(board)
+    }
+
+    /// Forgets every position searched, e.g. for a new game.
+    pub fn clear_hash(&self) {
+        self.table.clear();
+    }
+
+    /// Chooses a move for the side to move, from the book if it knows the position and by
+    /// searching within `limits` otherwise.
+    ///
+    /// # Returns
+    /// The move, and the reply the search expects to it, to think about on the opponent's time.
+    pub fn choose_move_within(&self, board: &Board, history: &PositionHistory, limits: &SearchLimits) -> SearchResult {
+        if let Some(book_move) = self.book_move(board) {
+            return SearchResult {
+                best_move: Some(book_move),
+                ponder_move: None,
+            };
+        }
+        let start = Instant::now();
+        let signals = SearchSignals::default();
+        let outcome = self.search(board, history, limits, &signals, &mut |_| {});
+        // Pausing shouldn't take longer than thinking would have
+        let max_wait = limits.time_budget(board.active_player).map_or(Duration::MAX, |budget| budget.soft);
+        self.pause(start, max_wait, &signals);
+        SearchResult {
+            best_move: outcome.best_move,
+            ponder_move: outcome.pv.get(1).copied(),
+        }
+    }
+
+    /// The limits moves are chosen within, unless the game is on the clock.
+    pub fn limits(&self) -> &SearchLimits {
+        &self.limits
+    }
+
+    /// Searches `board` until a limit is reached or `signals` stops it. Below full strength
+    /// the search is weakened by the AI's skill level, see [`skill`].
+    ///
+    /// # Arguments
+    /// * `board` - The position to search.
+    /// * `history` - The positions of the game leading to `board`, to recognise repetitions.
+    /// * `limits` - When to stop.
+    /// * `signals` - Stops the search, or holds the clock while pondering.
+    /// * `report` - Receives the progress after each completed iteration.
+    pub fn search(
+        &self,
+        board: &Board,
+        history: &PositionHistory,
+        limits: &SearchLimits,
+        signals: &SearchSignals,
+        report: &mut dyn FnMut(SearchInfo),
+    ) -> SearchOutcome {
+        let start = Instant::now();
+        let budget = limits.time_budget(board.active_player);
+        self.table.new_search();
+        let shared = SharedSearch::default();
+        let noisy_evaluator = NoisyEvaluator::new(self.evaluator.as_ref(), self.skill.eval_noise(), self.noise_seed);
+        let evaluator: &dyn Evaluator =
+            if self.skill.is_full_strength() { self.evaluator.as_ref() } else { &noisy_evaluator };
+        let node_limit = limits.nodes.unwrap_or(u64::MAX).min(self.skill.node_limit().unwrap_or(u64::MAX));
+        let mut searcher = Searcher {
+            board: board.clone(),
+            history: history.clone(),
+            evaluator,
+            table: &self.table,
+            signals,
+            shared: &shared,
+            node_limit,
+            hard_limit: budget.map(|budget| budget.hard),
+            clock_start: (!signals.pondering.load(Ordering::SeqCst)).then_some(start),
+            nodes: 0,
+            stopped: false,
+            previous_pv: Vec::new(),
+            line: Vec::new(),
+            killers: [[None; 2]; MAX_PLY],
+            history_scores: [[0; 64]; 12],
+            countermoves: [[None; 64]; 12],
+            tablebases: self.tablebases.as_deref(),
+            tb_pieces: self.tablebases.as_ref().map_or(0, |tablebases| tablebases.max_pieces()),
+        };
+
+        let mut root_moves = MoveList::new();
+        ChessMove::generate_legal_moves(board, &mut root_moves);
+        searcher.order_moves(root_moves.as_mut_slice(), 0, None);
+        let mut root_moves: Vec<ChessMove> = root_moves.iter().copied().collect();
+        self.filter_root_moves(&mut searcher, board, history, &mut root_moves);
+        let time_manager =
+            budget.map(|budget| TimeManager::new(budget, root_moves.len(), easy_recapture(board, &root_moves)));
+        let mut outcome = SearchOutcome {
+            best_move: root_moves.first().copied(),
+            score: Score::Centipawns(0),
+            pv: root_moves.first().copied().into_iter().collect(),
+            depth: 0,
+            nodes: 0,
+            lines: Vec::new(),
+        };
+        if root_moves.is_empty() {
+            let score = if board.is_in_check(board.active_player) { Score::Mate(0) } else { Score::Centipawns(0) };
+            outcome.score = score;
+            return outcome;
+        }
+
+        let skill_depth = self.skill.depth_limit().unwrap_or(MAX_DEPTH);
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(skill_depth).min(MAX_DEPTH);
+        // A weakened AI needs a few moves to choose from, but reports only those asked for
+        let skill_lines = if self.skill.is_full_strength() { 1 } else { SKILL_LINES };
+        let lines = self.multi_pv.max(skill_lines).min(root_moves.len());
+        let reported = self.multi_pv.min(lines);
+        let mut ranked = Vec::new();
+        thread::scope(|scope| {
+            for thread in 1..self.threads {
+                // Helpers don't keep time, the main thread stops them
+                let mut helper = searcher.clone();
+                helper.hard_limit = None;
+                let root_moves = root_moves.clone();
+                scope.spawn(move || helper.help(root_moves, max_depth, thread));
+            }
+            let search = RootSearch { lines, reported, max_depth, time_manager };
+            self.iterate(&mut searcher, &mut root_moves, search, &mut outcome, &mut ranked, report);
+            shared.done.store(true, Ordering::Relaxed);
+        });
+        outcome.nodes = shared.nodes.load(Ordering::Relaxed);
+
+        // A weakened AI doesn't always play the best move it found
+        if ranked.len() > 1 {
+            let scores: Vec<i32> = ranked.iter().map(|&(score, _)| score).collect();
+            let (score, pv) = ranked.swap_remove(self.skill.pick(&scores, self.next_random()));
+            outcome.best_move = pv.first().copied();
+            outcome.score = to_score(score);
+            outcome.pv = pv;
+        }
+        outcome
+    }
+
+    /// Keeps only the root moves the tablebases rank best, if they hold the position.
+    ///
+    /// With the DTZ tables the moves kept are sure to win, or to hold the draw, so probing
+    /// further inside the search would add nothing. With WDL tables alone the search still
+    /// needs them to make progress in a won position.
+    fn filter_root_moves(
+        &self,
+        searcher: &mut Searcher,
+        board: &Board,
+        history: &PositionHistory,
+        root_moves: &mut Vec<ChessMove>,
+    ) {
+        let Some(tablebases) = &self.tablebases else {
+            return;
+        };
+        let repeated = history.repetitions(board) > 1;
+        let Some(ranked) = tablebases.rank_root_moves(board, repeated) else {
+            return;
+        };
+        let Some(best_rank) = ranked.iter().map(|root_move| root_move.rank).max() else {
+            return;
+        };
+        root_moves.retain(|chess_move| {
+            ranked
+                .iter()
+                .any(|root_move| root_move.chess_move.pack() == chess_move.pack() && root_move.rank == best_rank)
+        });
+        if ranked[0].dtz.is_some() || best_rank <= 0 {
+            searcher.tb_pieces = 0;
+        }
+    }
+
+    /// Runs the main thread's iterative deepening, filling in `outcome` and reporting each
+    /// completed iteration.
+    ///
+    /// Each iteration searches `search.lines` root moves with an exact score: the best move
+    /// first, then the best of the others, and so on, leaving the last completed iteration's
+    /// scores and principal variations in `ranked`, best first.
+    fn iterate(
+        &self,
+        searcher: &mut Searcher,
+        root_moves: &mut [ChessMove],
+        mut search: RootSearch,
+        outcome: &mut SearchOutcome,
+        ranked: &mut Vec<(i32, Vec<ChessMove>)>,
+        report: &mut dyn FnMut(SearchInfo),
+    ) {
+        let start = Instant::now();
+        'deepening: for depth in 1..=search.max_depth {
+            let mut iteration = Vec::with_capacity(search.lines);
+            for line in 0..search.lines {
+                let (previous_score, previous_pv) = ranked.get(line).cloned().unwrap_or_default();
+                searcher.previous_pv = previous_pv;
+                let Some(result) = searcher.aspiration_search(&mut root_moves[line..], depth, previous_score) else {
+                    break 'deepening;
+                };
+                iteration.push(result);
+            }
+            // A later line may have found a better move than an earlier one
+            iteration.sort_by_key(|&(score, _)| -score);
+            for (root_move, (_, pv)) in root_moves.iter_mut().zip(&iteration) {
+                *root_move = pv[0];
+            }
+            *ranked = iteration;
+
+            let (score, pv) = ranked[0].clone();
+            let nodes = searcher.total_nodes();
+            let lines: Vec<AnalysisLine> = ranked[..search.reported]
+                .iter()
+                .map(|(score, pv)| AnalysisLine { score: to_score(*score), pv: pv.clone(), depth })
+                .collect();
+            let hashfull = self.table.hashfull();
+            for (index, line) in lines.iter().enumerate() {
+                report(SearchInfo {
+                    depth,
+                    multi_pv: index + 1,
+                    score: line.score,
+                    nodes,
+                    elapsed: start.elapsed(),
+                    hashfull,
+                    pv: line.pv.clone(),
+                });
+            }
+            *outcome = SearchOutcome {
+                best_move: pv.first().copied(),
+                score: to_score(score),
+                pv: pv.clone(),
+                depth,
+                nodes,
+                lines,
+            };
+            let best_move = pv[0].pack();
+            searcher.previous_pv = pv;
+            // A mate inside the full width search can't be bettered by searching deeper
+            if MATE_SCORE - score.abs() <= depth as i32 {
+                break;
+            }
+            if let Some(time_manager) = &mut search.time_manager {
+                // While pondering the clock hasn't started yet
+                let elapsed = searcher.clock_start.map_or(Duration::ZERO, |clock_start| clock_start.elapsed());
+                if !time_manager.iteration_done(best_move, score, elapsed) {
+                    break;
+                }
+            }
+        }
+        searcher.flush_nodes();
+    }
+}
+
+/// How the main thread's iterative deepening runs.
+struct RootSearch {
+    /// Root moves to find an exact score for, best first.
+    lines: usize,
+    /// Lines reported and kept in the outcome, the first ones.
+    reported: usize,
+    max_depth: u8,
+    time_manager: Option<TimeManager>,
+}
+
+/// State the threads of one search share.
+#[derive(Debug, Default)]
+struct SharedSearch {
+    /// Set by the main thread when it is done, to stop the helpers.
+    done: AtomicBool,
+    /// Nodes searched by all threads, added in batches of [`CHECK_INTERVAL`].
+    nodes: AtomicU64,
+}
+
+/// The state of one search thread.
+#[derive(Clone)]
+struct Searcher<'a> {
+    board: Board,
+    /// Positions of the game and of the line being searched, to recognise repetitions.
+    history: PositionHistory,
+    evaluator: &'a dyn Evaluator,
+    table: &'a TranspositionTable,
+    signals: &'a SearchSignals,
+    shared: &'a SharedSearch,
+    /// Nodes searched by all threads after which the search stops.
+    node_limit: u64,
+    hard_limit: Option<Duration>,
+    /// When the search started on the clock, `None` while pondering.
+    clock_start: Option<Instant>,
+    nodes: u64,
+    stopped: bool,
+    /// The principal variation of the last iteration, searched first in the next.
+    previous_pv: Vec<ChessMove>,
+    /// The moves leading from the root to the board, `None` for null moves.
+    line: Vec<Option<ChessMove>>,
+    /// The last two quiet moves to cause a cutoff at each ply, likely to refute their siblings.
+    killers: [[Option<PackedMove>; 2]; MAX_PLY],
+    /// How well quiet moves did by piece and target square, rewarded for cutoffs and punished
+    /// for being searched before the move that caused one.
+    history_scores: [[i32; 64]; 12],
+    /// The quiet move that last refuted each piece moving to each square.
+    countermoves: [[Option<PackedMove>; 64]; 12],
+    tablebases: Option<&'a Tablebases>,
+    /// Positions with at most this many pieces are probed in the tablebases.
+    tb_pieces: usize,
+}
+
+impl Searcher<'_> {
+    fn should_stop(&mut self) -> bool {
+        if self.stopped
+            || self.signals.stop.load(Ordering::Relaxed)
+            || self.shared.done.load(Ordering::Relaxed)
+            || self.shared.nodes.load(Ordering::Relaxed) >= self.node_limit
+        {
+            self.stopped = true;
+        } else if !self.signals.pondering.load(Ordering::Relaxed) {
+            // The clock starts when pondering ends
+            let clock_start = *self.clock_start.get_or_insert_with(Instant::now);
+            self.stopped = self.hard_limit.is_some_and(|hard_limit| clock_start.elapsed() >= hard_limit);
+        }
+        self.stopped
+    }
+
+    /// Counts a node, checking the stop conditions every [`CHECK_INTERVAL`] nodes.
+    ///
+    /// # Returns
+    /// `true` if the search must stop.
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            return false;
+        }
+        self.shared.nodes.fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+        self.should_stop()
+    }
+
+    /// Adds the nodes not yet counted in the shared count, once the thread is done.
+    fn flush_nodes(&mut self) {
+        self.shared.nodes.fetch_add(self.nodes % CHECK_INTERVAL, Ordering::Relaxed);
+        self.nodes -= self.nodes % CHECK_INTERVAL;
+    }
+
+    /// Nodes searched by all threads so far.
+    fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.nodes % CHECK_INTERVAL
+    }
+
+    /// Runs a helper thread's iterative deepening until it reaches `max_depth` or the main
+    /// thread is done. Helpers with odd numbers start one ply deeper, so the threads don't all
+    /// search the same depth at the same time. Their results only reach the main thread
+    /// through the transposition table.
+    fn help(&mut self, mut root_moves: Vec<ChessMove>, max_depth: u8, thread: usize) {
+        let mut previous_score = 0;
+        for depth in (1 + thread as u8 % 2)..=max_depth {
+            let Some((score, pv)) = self.aspiration_search(&mut root_moves, depth, previous_score) else {
+                break;
+            };
+            previous_score = score;
+            self.previous_pv = pv;
+        }
+        self.flush_nodes();
+    }
+
+    fn make_move(&mut self, chess_move: ChessMove) -> MoveUndo {
+        let undo = chess_move.make_reversible_move(&mut self.board);
+        self.history.push(&self.board);
+        self.line.push(Some(chess_move));
+        undo
+    }
+
+    fn undo_move(&mut self, chess_move: ChessMove, undo: MoveUndo) {
+        self.line.pop();
+        self.history.pop();
+        let _ = chess_move.undo_move(&mut self.board, undo);
+    }
+
+    /// Passes the turn to the opponent, returning the en passant square to restore.
+    ///
+    /// The position is left out of the history, a line through a null move can't repeat.
+    fn make_null_move(&mut self) -> Option<Square> {
+        let en_passant_square = self.board.en_passant_square;
+        self.board.set_en_passant_square(None);
+        self.board.set_active_player(self.board.active_player.toggle_color());
+        self.line.push(None);
+        en_passant_square
+    }
+
+    fn undo_null_move(&mut self, en_passant_square: Option<Square>) {
+        self.line.pop();
+        self.board.set_active_player(self.board.active_player.toggle_color());
+        self.board.set_en_passant_square(en_passant_square);
+    }
+
+    /// Searches one iteration, first within a window around `previous_score`, widening the
+    /// window on the side the score fell outside until it lands inside.
+    ///
+    /// # Returns
+    /// The score and principal variation, `None` if the search was stopped first.
+    fn aspiration_search(
+        &mut self,
+        root_moves: &mut [ChessMove],
+        depth: u8,
+        previous_score: i32,
+    ) -> Option<(i32, Vec<ChessMove>)> {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = if depth >= ASPIRATION_DEPTH && previous_score.abs() < MATE_THRESHOLD {
+            (previous_score - delta, previous_score + delta)
+        } else {
+            (-MATE_SCORE, MATE_SCORE)
+        };
+        loop {
+            let (score, pv) = self.search_root(root_moves, depth, alpha, beta)?;
+            if score <= alpha && alpha > -MATE_SCORE {
+                alpha = (score - delta).max(-MATE_SCORE);
+            } else if score >= beta && beta < MATE_SCORE {
+                beta = (score + delta).min(MATE_SCORE);
+            } else {
+                return Some((score, pv));
+            }
+            delta *= 2;
+        }
+    }
+
+    /// Searches every root move, moving the best one to the front for the next iteration.
+    fn search_root(
+        &mut self,
+        root_moves: &mut [ChessMove],
+        depth: u8,
+        mut alpha: i32,
+        beta: i32,
+    ) -> Option<(i32, Vec<ChessMove>)> {
+        let mut best: Option<(usize, i32, Vec<ChessMove>)> = None;
+        for (index, &chess_move) in root_moves.iter().enumerate() {
+            let mut line = Vec::new();
+            let undo = self.make_move(chess_move);
+            let score = self.principal_variation_search(depth, index == 0, 0, alpha, beta, &mut line);
+            self.undo_move(chess_move, undo);
+            // A stopped iteration can't be trusted
+            let score = score?;
+            if best.is_none() || score > alpha {
+                line.insert(0, chess_move);
+                best = Some((index, score, line));
+                alpha = alpha.max(score);
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+        let (index, score, pv) = best?;
+        root_moves[..=index].rotate_right(1);
+        Some((score, pv))
+    }
+
+    /// Searches the move just made on the board, from the mover's view. The first move of a
+    /// node is searched with the full window, the others with a null window first, `reduction`
+    /// plies shallower until they beat alpha.
+    fn principal_variation_search(
+        &mut self,
+        depth: u8,
+        first: bool,
+        reduction: u8,
+        alpha: i32,
+        beta: i32,
+        pv: &mut Vec<ChessMove>,
+    ) -> Option<i32> {
+        let depth = depth - 1;
+        let ply = self.line.len();
+        if first {
+            return self.negamax(depth, ply, -beta, -alpha, pv).map(|score| -score);
+        }
+        let mut score = -self.negamax(depth.saturating_sub(reduction), ply, -alpha - 1, -alpha, pv)?;
+        if reduction > 0 && score > alpha {
+            pv.clear();
+            score = -self.negamax(depth, ply, -alpha - 1, -alpha, pv)?;
+        }
+        if score > alpha && score < beta {
+            pv.clear();
+            return self.negamax(depth, ply, -beta, -alpha, pv).map(|score| -score);
+        }
+        Some(score)
+    }
+
+    /// Scores the board from the side to move's view, `None` if the search was stopped.
+    fn negamax(&mut self, depth: u8, ply: usize, mut alpha: i32, mut beta: i32, pv: &mut Vec<ChessMove>) -> Option<i32> {
+        if self.visit() {
+            return None;
+        }
+        if self.is_draw() {
+            return Some(0);
+        }
+        if ply >= MAX_PLY {
+            return Some(self.evaluator.evaluate(&self.board));
+        }
+
+        // No line from here can be better than mating on the next move, or worse than being
+        // mated on this one
+        alpha = alpha.max(ply as i32 - MATE_SCORE);
+        beta = beta.min(MATE_SCORE - ply as i32 - 1);
+        if alpha >= beta {
+            return Some(alpha);
+        }
+
+        let in_check = self.board.is_in_check(self.board.active_player);
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        // Reuse a result at least as deep as this search, outside the principal variation so
+        // it is still searched out in full
+        let is_pv = beta - alpha > 1;
+        let key = self.board.zobrist_key();
+        let entry = self.table.probe(key, ply);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth && !is_pv) {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if usable {
+                return Some(entry.score);
+            }
+        }
+
+        if let Some(score) = self.probe_tablebases(key, depth, ply, alpha, beta) {
+            return Some(score);
+        }
+
+        if !is_pv && !in_check && depth >= NULL_MOVE_DEPTH && self.may_pass() {
+            if let Some(score) = self.null_move_search(depth, ply, beta) {
+                return Some(score);
+            }
+            if self.stopped {
+                return None;
+            }
+        }
+
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(&self.board, &mut moves);
+        if moves.is_empty() {
+            return Some(if in_check { ply as i32 - MATE_SCORE } else { 0 });
+        }
+        self.order_moves(moves.as_mut_slice(), ply, entry.and_then(|entry| entry.best_move));
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        for (index, &chess_move) in moves.iter().enumerate() {
+            let mut line = Vec::new();
+            let undo = self.make_move(chess_move);
+            let reduction = if depth >= LMR_DEPTH
+                && index >= LMR_MOVES
+                && !in_check
+                && is_quiet(chess_move)
+                && !self.board.is_in_check(self.board.active_player)
+            {
+                // Outside the principal variation the latest moves are reduced further
+                if !is_pv && index >= 2 * LMR_MOVES { 2 } else { 1 }
+            } else {
+                0
+            };
+            let score = self.principal_variation_search(depth, index == 0, reduction, alpha, beta, &mut line);
+            self.undo_move(chess_move, undo);
+            let score = score?;
+            if score > alpha {
+                alpha = score;
+                best_move = Some(chess_move.pack());
+                pv.clear();
+                pv.push(chess_move);
+                pv.append(&mut line);
+                if alpha >= beta {
+                    if is_quiet(chess_move) {
+                        self.reward_quiet_move(chess_move, &moves.as_slice()[..index], depth, ply);
+                    }
+                    break;
+                }
+            }
+        }
+
+        let bound = if alpha >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table.store(key, ply, depth, bound, alpha, best_move);
+        Some(alpha)
+    }
+
+    /// Probes the tablebases right after a capture or pawn move, where the fifty move rule
+    /// can't have spoilt the result, and stores what they say in the transposition table.
+    ///
+    /// # Returns
+    /// The score if it settles the node: a draw, or a win or loss beyond the window.
+    fn probe_tablebases(&mut self, key: ZobristKey, depth: u8, ply: usize, alpha: i32, beta: i32) -> Option<i32> {
+        let tablebases = self.tablebases?;
+        if ply == 0
+            || self.board.half_move_clock != 0
+            || self.board.castling_rights != 0
+            || syzygy::piece_count(&self.board) > self.tb_pieces
+        {
+            return None;
+        }
+        let (score, bound) = match tablebases.probe_wdl(&self.board)? {
+            Wdl::Win => (TB_WIN_SCORE - ply as i32, Bound::Lower),
+            Wdl::Loss => (ply as i32 - TB_WIN_SCORE, Bound::Upper),
+            // Results the fifty move rule turns into draws score a little either side of one
+            wdl => (2 * wdl as i32, Bound::Exact),
+        };
+        let cutoff = match bound {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha,
+        };
+        if !cutoff {
+            return None;
+        }
+        self.table.store(key, ply, (depth + 6).min(MAX_DEPTH), bound, score, None);
+        Some(score)
+    }
+
+    /// Searches captures and promotions until the position is quiet, so the evaluation isn't
+    /// taken in the middle of an exchange. The side to move may stand pat on the evaluation
+    /// instead of capturing, unless it is in check, where every evasion is searched.
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> Option<i32> {
+        if self.visit() {
+            return None;
+        }
+        if ply >= MAX_PLY {
+            return Some(self.evaluator.evaluate(&self.board));
+        }
+
+        let in_check = self.board.is_in_check(self.board.active_player);
+        let mut moves = MoveList::new();
+        if in_check {
+            ChessMove::generate_legal_moves(&self.board, &mut moves);
+            if moves.is_empty() {
+                return Some(ply as i32 - MATE_SCORE);
+            }
+        } else {
+            let stand_pat = self.evaluator.evaluate(&self.board);
+            if stand_pat >= beta {
+                return Some(stand_pat);
+            }
+            alpha = alpha.max(stand_pat);
+            ChessMove::generate_captures(&self.board, &mut moves);
+            let mut quiets = MoveList::new();
+            ChessMove::generate_quiets(&self.board, &mut quiets);
+            for &chess_move in quiets.iter() {
+                if chess_move.get_meta_data().is_promotion() && chess_move.get_piece().is_queen() {
+                    moves.push(chess_move);
+                }
+            }
+        }
+        self.order_moves(moves.as_mut_slice(), ply, None);
+
+        for &chess_move in moves.iter() {
+            // Captures that lose material can't raise a score the side to move could stand on
+            if !in_check && self.board.see(chess_move) < 0 {
+                continue;
+            }
+            let undo = self.make_move(chess_move);
+            let score = self.quiescence(ply + 1, -beta, -alpha).map(|score| -score);
+            self.undo_move(chess_move, undo);
+            let score = score?;
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        Some(alpha)
+    }
+
+    /// Draws the search can score without looking further: repetitions, the fifty move rule
+    /// and positions neither side can win.
+    ///
+    /// Any repetition counts, as a line that repeats once can repeat again.
+    fn is_draw(&self) -> bool {
+        self.board.half_move_clock >= 100
+            || self.history.repetitions(&self.board) > 1
+            || draw::has_insufficient_material(&self.board)
+    }
+
+    /// Whether the side to move may pass for null move pruning: not right after the opponent
+    /// passed, and only with a piece besides pawns, as pawn endings are full of zugzwang.
+    fn may_pass(&self) -> bool {
+        let color = self.board.active_player;
+        let pawns_and_king = self.board.get_bitboard(color.get_pawn()) | self.board.get_bitboard(color.get_king());
+        !matches!(self.line.last(), Some(None)) && self.board.get_color_bitboard(color) & !pawns_and_king != 0
+    }
+
+    /// Passes the turn and searches the opponent's reply at reduced depth.
+    ///
+    /// # Returns
+    /// `beta` if the side to move stays at or above beta even after passing, so the node can
+    /// be cut off, `None` otherwise or when the evaluation isn't above beta to begin with.
+    fn null_move_search(&mut self, depth: u8, ply: usize, beta: i32) -> Option<i32> {
+        if self.evaluator.evaluate(&self.board) < beta {
+            return None;
+        }
+        let reduction = NULL_MOVE_REDUCTION + depth / 6;
+        let en_passant_square = self.make_null_move();
+        let score = self.negamax(depth.saturating_sub(reduction + 1), ply + 1, -beta, -beta + 1, &mut Vec::new());
+        self.undo_null_move(en_passant_square);
+        // A mate found after passing proves nothing, only that the node fails high
+        score.filter(|&score| -score >= beta).map(|_| beta)
+    }
+
+    /// Learns from the quiet `chess_move` causing a cutoff, after the quiet moves in `tried`
+    /// failed to.
+    fn reward_quiet_move(&mut self, chess_move: ChessMove, tried: &[ChessMove], depth: u8, ply: usize) {
+        let packed = Some(chess_move.pack());
+        let killers = &mut self.killers[ply];
+        if killers[0] != packed {
+            killers[1] = killers[0];
+            killers[0] = packed;
+        }
+        if let Some(previous) = self.previous_move() {
+            self.countermoves[previous.0][previous.1] = packed;
+        }
+
+        let bonus = depth as i32 * depth as i32;
+        for &quiet in tried.iter().filter(|&&quiet| is_quiet(quiet)) {
+            self.update_history(quiet, -bonus);
+        }
+        self.update_history(chess_move, bonus);
+    }
+
+    /// Moves a history score towards the bound in the direction of `bonus`, by less the
+    /// closer it already is.
+    fn update_history(&mut self, chess_move: ChessMove, bonus: i32) {
+        let Some(piece) = self.board.get_piece_at(chess_move.get_origin()) else {
+            return;
+        };
+        let score = &mut self.history_scores[piece.to_index()][chess_move.get_target() as usize];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+
+    /// The piece index and target square of the move that led to the board.
+    fn previous_move(&self) -> Option<(usize, usize)> {
+        let target = self.line.last().copied().flatten()?.get_target();
+        let piece = self.board.get_piece_at(target)?;
+        Some((piece.to_index(), target as usize))
+    }
+
+    /// Orders the transposition table's move first and the previous principal variation's
+    /// move second. Captures and promotions that don't lose material follow, by the material
+    /// they win and the most valuable victim taken by the least valuable attacker first, then
+    /// the killer moves and the countermove, the other quiet moves by their history, and last
+    /// the captures that lose material.
+    fn order_moves(&self, moves: &mut [ChessMove], ply: usize, table_move: Option<PackedMove>) {
+        let pv_move = self.previous_pv.get(ply).map(|chess_move| chess_move.pack());
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        let countermove = self.previous_move().and_then(|(piece, target)| self.countermoves[piece][target]);
+        moves.sort_by_cached_key(|&chess_move| {
+            let packed = Some(chess_move.pack());
+            if packed == table_move {
+                return i32::MIN;
+            }
+            if packed == pv_move {
+                return i32::MIN + 1;
+            }
+            if is_quiet(chess_move) {
+                return match packed {
+                    _ if packed == killers[0] => -KILLER - 2,
+                    _ if packed == killers[1] => -KILLER - 1,
+                    _ if packed == countermove => -KILLER,
+                    _ => self.board.get_piece_at(chess_move.get_origin()).map_or(0, |piece| {
+                        -self.history_scores[piece.to_index()][chess_move.get_target() as usize]
+                    }),
+                };
+            }
+
+            let meta_data = chess_move.get_meta_data();
+            let victim = match self.board.get_piece_at(chess_move.get_target()) {
+                Some(piece) => piece_value(piece),
+                None if meta_data.is_en_passant() => piece_value(Piece::WhitePawn),
+                None => 0,
+            };
+            let promotion = if meta_data.is_promotion() { piece_value(chess_move.get_piece()) } else { 0 };
+            let attacker = self.board.get_piece_at(chess_move.get_origin()).map_or(0, piece_value);
+            let mvv_lva = (victim + promotion) * 10 - attacker / 100;
+            let class = if self.board.see(chess_move) >= 0 { GOOD_CAPTURE } else { BAD_CAPTURE };
+            -(class + mvv_lva)
+        });
+    }
+}
+
+/// A capture winning back at least the material the side to move is down, likely the answer
+/// to a capture just made.
+fn easy_recapture(board: &Board, root_moves: &[ChessMove]) -> Option<PackedMove> {
+    let color = board.active_player;
+    let deficit = material(board, color.toggle_color()) - material(board, color);
+    if deficit <= 0 {
+        return None;
+    }
+    root_moves
+        .iter()
+        .filter(|chess_move| chess_move.get_meta_data().is_capture())
+        .map(|&chess_move| (board.see(chess_move), chess_move))
+        .filter(|&(gain, _)| gain >= deficit)
+        .max_by_key(|&(gain, _)| gain)
+        .map(|(_, chess_move)| chess_move.pack())
+}
+
+fn material(board: &Board, color: Color) -> i32 {
+    Piece::iter_color_pieces(&color)
+        .map(|piece| board.get_bitboard(piece).count_ones() as i32 * piece_value(piece))
+        .sum()
+}
+
+/// Moves that neither capture nor promote, ordered by the killer, countermove and history
+/// heuristics.
+fn is_quiet(chess_move: ChessMove) -> bool {
+    let meta_data = chess_move.get_meta_data();
+    !meta_data.is_capture() && !meta_data.is_promotion()
+}
+
+/// Converts a search score to centipawns or moves to mate.
+pub fn to_score(score: i32) -> Score {
+    if score >= MATE_THRESHOLD {
+        Score::Mate((MATE_SCORE - score + 1) / 2)
+    } else if score <= -MATE_THRESHOLD {
+        Score::Mate(-(MATE_SCORE + score) / 2)
+    } else {
+        Score::Centipawns(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, depth: u8) -> SearchOutcome {
+        let board = Board::from_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+        ChessAI::new(limits.clone()).search(
+            &board,
+            &PositionHistory::new(&board),
+            &limits,
+            &SearchSignals::default(),
+            &mut |_| {},
+        )
+    }
+
+    fn best_move(outcome: &SearchOutcome) -> String {
+        outcome.best_move.unwrap().to_coordinate_notation()
+    }
+
+    #[test]
+    fn finds_mates_by_their_distance() {
+        // Scholar's mate
+        let outcome = search("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", 3);
+        assert_eq!(best_move(&outcome), "h5f7");
+        assert_eq!(outcome.score, Score::Mate(1));
+
+        // A back rank mate, found at the first depth that sees it
+        let outcome = search("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 5);
+        assert_eq!(best_move(&outcome), "a1a8");
+        assert_eq!((outcome.score, outcome.depth), (Score::Mate(1), 1));
+
+        // The rook ladder mates in two
+        let outcome = search("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 3);
+        assert_eq!(outcome.score, Score::Mate(2));
+
+        // Getting mated
+        let outcome = search("7k/8/6K1/8/8/8/8/R7 b - - 0 1", 2);
+        assert_eq!(best_move(&outcome), "h8g8");
+        assert_eq!(outcome.score, Score::Mate(-1));
+        let outcome = search("R6k/8/6K1/8/8/8/8/8 b - - 0 1", 1);
+        assert_eq!((outcome.best_move.is_none(), outcome.score), (true, Score::Mate(0)));
+    }
+
+    #[test]
+    fn wins_material_and_avoids_losing_it() {
+        // A queen hanging to a pawn
+        let outcome = search("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1", 2);
+        assert_eq!(best_move(&outcome), "e4d5");
+        // The rook is defended, quiescence sees the recapture
+        let outcome = search("4k3/8/2p5/3r4/8/8/8/3QK3 w - - 0 1", 1);
+        assert_ne!(best_move(&outcome), "d1d5");
+        assert!(matches!(outcome.score, Score::Centipawns(score) if score > 0));
+    }
+
+    #[test]
+    fn scores_draws() {
+        // Stalemate
+        let outcome = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+        assert!(outcome.best_move.is_none());
+        assert_eq!(outcome.score, Score::Centipawns(0));
+        // A queen up, but any move that doesn't mate is the hundredth without progress
+        let outcome = search("7k/8/5K2/8/8/8/8/Q7 w - - 99 80", 3);
+        assert_eq!(outcome.score, Score::Centipawns(0));
+    }
+
+    #[test]
+    fn reuses_what_earlier_searches_learnt() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..SearchLimits::default()
+        };
+        let ai = ChessAI::new(limits.clone());
+        let search = |ai: &ChessAI| {
+            let signals = SearchSignals::default();
+            ai.search(&board, &PositionHistory::new(&board), &limits, &signals, &mut |_| {})
+        };
+        let first = search(&ai);
+        // Clones share the table
+        let second = search(&ai.clone());
+        assert!(second.nodes < first.nodes / 2, "{} then {}", first.nodes, second.nodes);
+        ai.clear_hash();
+        assert_eq!(search(&ai).nodes, first.nodes);
+    }
+
+    #[test]
+    fn searches_with_helper_threads() {
+        let mut ai = ChessAI::default();
+        ai.set_threads(4);
+        let mut nodes = Vec::new();
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let signals = SearchSignals::default();
+        let limits = SearchLimits {
+            depth: Some(6),
+            ..SearchLimits::default()
+        };
+        let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &signals, &mut |info| {
+            nodes.push(info.nodes)
+        });
+        assert_eq!(best_move(&outcome), "d1d8");
+        assert_eq!(outcome.score, Score::Mate(1));
+        // The helpers' nodes are counted too
+        assert!(outcome.nodes >= *nodes.last().unwrap());
+
+        let limits = SearchLimits {
+            nodes: Some(50_000),
+            ..SearchLimits::default()
+        };
+        let board = Board::std_new();
+        let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &signals, &mut |_| {});
+        assert!(outcome.best_move.is_some());
+        // Each thread notices the limit at its next check
+        assert!(outcome.nodes < 50_000 + 2 * 4 * CHECK_INTERVAL, "{}", outcome.nodes);
+    }
+
+    #[test]
+    fn answers_forced_moves_at_once() {
+        let board = Board::from_fen("7k/8/8/8/8/8/6q1/7K w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            white_time: Some(Duration::from_secs(600)),
+            black_time: Some(Duration::from_secs(600)),
+            ..SearchLimits::default()
+        };
+        let signals = SearchSignals::default();
+        let outcome = ChessAI::default().search(&board, &PositionHistory::new(&board), &limits, &signals, &mut |_| {});
+        assert_eq!(best_move(&outcome), "h1g2");
+        assert_eq!(outcome.depth, 1);
+    }
+
+    #[test]
+    fn plays_book_moves_before_searching() {
+        use crate::ai::book::{polyglot_key, polyglot_move, BookEntry};
+
+        let board = Board::std_new();
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(&board, &mut moves);
+        let h3 = moves.iter().copied().find(|chess_move| chess_move.to_coordinate_notation() == "h2h3").unwrap();
+        let entry = BookEntry { key: polyglot_key(&board), raw_move: polyglot_move(h3), weight: 1, learn: 0 };
+
+        let mut ai = ChessAI::new(SearchLimits { depth: Some(1), ..SearchLimits::default() });
+        ai.set_book(Some(OpeningBook::new(vec![entry])));
+        let history = PositionHistory::new(&board);
+        let chosen = ai.choose_move_within(&board, &history, ai.limits()).best_move;
+        assert_eq!(chosen.unwrap().to_coordinate_notation(), "h2h3");
+        // Out of book it searches
+        let mut board = board;
+        h3.make_move(&mut board);
+        assert!(ai.book_move(&board).is_none());
+        assert!(ai.choose_move_within(&board, &PositionHistory::new(&board), ai.limits()).best_move.is_some());
+    }
+
+    #[test]
+    fn scores_positions_in_the_tablebases() {
+        // A KQvK table calling every position a draw, so the search can tell it was probed
+        let directory = std::env::temp_dir().join(format!("rusty_chess_ai_tablebases_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut table = vec![0x71, 0xE8, 0x23, 0x5D, 1, 0, 0x66, 0x55, 0xEE, 0];
+        table.extend([128, 2, 128, 2]);
+        std::fs::write(directory.join("KQvK.rtbw"), table).unwrap();
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+
+        // Taking the rook would win without the tables, with them it only draws
+        let fen = "7r/8/8/8/8/1k6/8/K6Q w - - 0 1";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(best_move(&search(fen, 3)), "h1h8");
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+        let mut ai = ChessAI::new(limits.clone());
+        ai.set_tablebases(Some(tablebases));
+        let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &SearchSignals::default(), &mut |_| {});
+        assert_ne!(best_move(&outcome), "h1h8");
+        assert!(matches!(outcome.score, Score::Centipawns(score) if score > 0));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn analyses_several_lines() {
+        // Mate in one, winning the queen, or neither
+        let board = Board::from_fen("6k1/5ppp/8/7q/8/8/4BPPP/3R2K1 w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..SearchLimits::default()
+        };
+        let ai = ChessAI::new(limits.clone());
+        let lines = ai.analyse(&board, &PositionHistory::new(&board), &limits, 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0].to_coordinate_notation(), "d1d8");
+        assert_eq!(lines[0].score, Score::Mate(1));
+        assert_eq!(lines[1].pv[0].to_coordinate_notation(), "e2h5");
+        assert!(lines.iter().all(|line| line.depth == lines[0].depth));
+        let scores: Vec<i32> = lines[1..]
+            .iter()
+            .map(|line| match line.score {
+                Score::Centipawns(centipawns) => centipawns,
+                Score::Mate(_) => panic!("{:?}", line),
+            })
+            .collect();
+        assert!(scores[0] > scores[1] + 500, "{:?}", scores);
+
+        // No more lines than legal moves, and every line reported
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K6q w - - 0 1").unwrap();
+        let mut ai = ai.clone();
+        ai.set_multi_pv(5);
+        let mut reported = Vec::new();
+        let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &SearchSignals::default(), &mut |info| {
+            reported.push(info.multi_pv)
+        });
+        assert_eq!(outcome.lines.len(), 2);
+        assert_eq!(reported.len(), 2 * outcome.depth as usize);
+        assert_eq!(&reported[..2], [1, 2]);
+    }
+
+    #[test]
+    fn weakens_to_its_skill_level() {
+        let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let limits = SearchLimits {
+            depth: Some(8),
+            ..SearchLimits::default()
+        };
+        let mut ai = ChessAI::new(limits.clone());
+        ai.set_skill(SkillLevel::new(4).unwrap());
+        // Reporting as many lines as it chooses from shows the moves it picks among
+        ai.set_multi_pv(SKILL_LINES);
+        let mut played = std::collections::HashSet::new();
+        for _ in 0..8 {
+            let mut iterations = Vec::new();
+            let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &SearchSignals::default(), &mut |info| {
+                iterations.push(info)
+            });
+            // Three plies at most, fewer if the node limit comes first
+            assert!(outcome.depth <= 3);
+            assert_eq!(iterations.len(), SKILL_LINES * outcome.depth as usize);
+            // The move played is one of the best few, though not always the best
+            let chess_move = outcome.best_move.unwrap();
+            assert_eq!(outcome.pv[0].pack(), chess_move.pack());
+            assert_eq!(outcome.lines.len(), SKILL_LINES);
+            assert!(outcome.lines.iter().any(|line| line.pv[0].pack() == chess_move.pack()));
+            played.insert(chess_move.pack());
+        }
+        assert!(played.len() <= SKILL_LINES);
+
+        // Full strength searches as deep as asked
+        ai.set_skill(SkillLevel::MAX);
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..limits
+        };
+        let outcome = ai.search(&board, &PositionHistory::new(&board), &limits, &SearchSignals::default(), &mut |_| {});
+        assert_eq!(outcome.depth, 4);
+    }
+
+    #[test]
+    fn weakened_ai_takes_its_time() {
+        let board = Board::std_new();
+        let history = PositionHistory::new(&board);
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+        let mut ai = ChessAI::new(limits.clone());
+        ai.set_skill(SkillLevel::new(0).unwrap());
+        // Level 0 takes at least half its usual second
+        let start = Instant::now();
+        assert!(ai.choose_move_within(&board, &history, &limits).best_move.is_some());
+        assert!(start.elapsed() >= SkillLevel::new(0).unwrap().think_time(0));
+
+        // A stop cuts the pause short
+        let signals = SearchSignals::default();
+        signals.stop.store(true, Ordering::Relaxed);
+        let start = Instant::now();
+        ai.pause(start, Duration::MAX, &signals);
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // Full strength answers at once
+        assert_eq!(SkillLevel::MAX.think_time(12345), Duration::ZERO);
+    }
+
+    #[test]
+    fn reports_every_iteration_and_stops_on_limits() {
+        let board = Board::std_new();
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+        let mut depths = Vec::new();
+        let outcome = ChessAI::default().search(
+            &board,
+            &PositionHistory::new(&board),
+            &limits,
+            &SearchSignals::default(),
+            &mut |info| depths.push(info.depth),
+        );
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(outcome.depth, 3);
+        assert_eq!(outcome.pv.len(), 3);
+
+        let limits = SearchLimits {
+            nodes: Some(2_000),
+            ..SearchLimits::default()
+        };
+        let result = ChessAI::default().choose_move_within(&board, &PositionHistory::new(&board), &limits);
+        assert!(result.best_move.is_some());
+    }
+}
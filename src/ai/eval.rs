@@ -0,0 +1,755 @@
+//! Static evaluation.
+//!
+//! [`StandardEvaluator`] scores a position as the sum of a few terms, each computed for both
+//! sides: material, piece-square tables, mobility, pawn structure, king safety and the bishop
+//! pair. Every term has a middlegame and an endgame value, blended by how much material is
+//! left, so e.g. the king hides behind its pawns while queens are on the board and walks to
+//! the centre once they are gone.
+//!
+//! The weights live in [`EvalWeights`] and can be read from a text file, one weight per line
+//! in the same `name: value` form as the game's config file:
+//!
+//! ```text
+//! # Middlegame and endgame value of the bishop pair
+//! bishop_pair: 30 50
+//! # Tables list the middlegame values, then the endgame values, separately
+//! passed_pawn.mg: 0 5 10 15 25 40 60 0
+//! passed_pawn.eg: 0 10 20 30 50 80 120 0
+//! ```
+//!
+//! Weights left out of the file keep their defaults. Per piece weights are listed in the
+//! board's piece order: pawn, rook, knight, bishop, queen, king, and tables from a8 to h1 as
+//! seen by white.
+use std::fmt::{self, Debug, Display, Formatter};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+use crate::board::attacks;
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Bitboard, Board};
+
+/// Scores positions for the search.
+pub trait Evaluator: Debug + Send + Sync {
+    /// Scores `board` in centipawns from the side to move's view.
+    fn evaluate(&self, board: &Board) -> i32;
+}
+
+/// A middlegame and an endgame value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseScore {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl PhaseScore {
+    pub const ZERO: PhaseScore = PhaseScore::new(0, 0);
+
+    pub const fn new(mg: i32, eg: i32) -> PhaseScore {
+        PhaseScore { mg, eg }
+    }
+
+    /// Blends the two values by `phase`, from [`MAX_PHASE`] with all pieces on the board to 0
+    /// with only kings and pawns left.
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for PhaseScore {
+    type Output = PhaseScore;
+    fn add(self, other: PhaseScore) -> PhaseScore {
+        PhaseScore::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for PhaseScore {
+    fn add_assign(&mut self, other: PhaseScore) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for PhaseScore {
+    type Output = PhaseScore;
+    fn sub(self, other: PhaseScore) -> PhaseScore {
+        PhaseScore::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Neg for PhaseScore {
+    type Output = PhaseScore;
+    fn neg(self) -> PhaseScore {
+        PhaseScore::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for PhaseScore {
+    type Output = PhaseScore;
+    fn mul(self, factor: i32) -> PhaseScore {
+        PhaseScore::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+/// The phase with every piece on the board.
+pub const MAX_PHASE: i32 = 24;
+/// How much each kind of piece counts towards the phase, in the board's piece order.
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+/// Mobility is scored relative to these typical square counts, in the board's piece order.
+const MOBILITY_BASELINE: [i32; 6] = [0, 7, 4, 7, 14, 0];
+const PIECE_NAMES: [&str; 6] = ["pawn", "rook", "knight", "bishop", "queen", "king"];
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+/// Index of a piece kind in per piece weights, the board's piece order with colours merged.
+fn kind(piece: Piece) -> usize {
+    piece.to_index() / 2
+}
+
+fn file_mask(col: u8) -> Bitboard {
+    FILE_A << col
+}
+
+/// The file of `col` and its neighbours.
+fn adjacent_files(col: u8) -> Bitboard {
+    let mut files = file_mask(col);
+    if col > 0 {
+        files |= file_mask(col - 1);
+    }
+    if col < 7 {
+        files |= file_mask(col + 1);
+    }
+    files
+}
+
+/// Squares on rows strictly ahead of `row` for `color`.
+fn ahead_of(row: u8, color: Color) -> Bitboard {
+    match color {
+        Color::White => (1u64 << (row * 8)) - 1,
+        Color::Black if row == 7 => 0,
+        Color::Black => !((1u64 << ((row + 1) * 8)) - 1),
+    }
+}
+
+/// Row `row` seen from `color`'s side, 0 for its back rank.
+fn relative_row(row: u8, color: Color) -> usize {
+    match color {
+        Color::White => 7 - row as usize,
+        Color::Black => row as usize,
+    }
+}
+
+fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros() as Square;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+/// Weights of the [`StandardEvaluator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalWeights {
+    /// Value of each kind of piece.
+    pub material: [PhaseScore; 6],
+    /// Bonus of each kind of piece for each square, from a8 to h1 for white. Black's tables are
+    /// mirrored.
+    pub piece_square: [[PhaseScore; 64]; 6],
+    /// Bonus per square a piece reaches, beyond the usual number for its kind.
+    pub mobility: [PhaseScore; 6],
+    /// Per pawn sharing its file with a pawn of its own colour.
+    pub doubled_pawn: PhaseScore,
+    /// Per pawn with no pawn of its own colour on the neighbouring files.
+    pub isolated_pawn: PhaseScore,
+    /// Per pawn no enemy pawn can stop, by rank from the pawn's side.
+    pub passed_pawn: [PhaseScore; 8],
+    /// Per pawn on the two ranks in front of the king.
+    pub king_shield: PhaseScore,
+    /// Per file next to the king without a pawn of its own colour.
+    pub king_open_file: PhaseScore,
+    /// Per square around the king attacked by an enemy piece.
+    pub king_attack: PhaseScore,
+    pub bishop_pair: PhaseScore,
+}
+
+/// Builds a table with the same value for middlegame and endgame from a8 to h1.
+const fn table(values: [i32; 64]) -> [PhaseScore; 64] {
+    tapered_table(values, values)
+}
+
+const fn tapered_table(mg: [i32; 64], eg: [i32; 64]) -> [PhaseScore; 64] {
+    let mut table = [PhaseScore::ZERO; 64];
+    let mut square = 0;
+    while square < 64 {
+        table[square] = PhaseScore::new(mg[square], eg[square]);
+        square += 1;
+    }
+    table
+}
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+impl Default for EvalWeights {
+    fn default() -> EvalWeights {
+        let score = PhaseScore::new;
+        EvalWeights {
+            material: [score(82, 94), score(477, 512), score(337, 281), score(365, 297), score(1025, 936), score(0, 0)],
+            piece_square: [
+                tapered_table(PAWN_MG, PAWN_EG),
+                table(ROOK),
+                table(KNIGHT),
+                table(BISHOP),
+                table(QUEEN),
+                tapered_table(KING_MG, KING_EG),
+            ],
+            mobility: [score(0, 0), score(2, 4), score(4, 4), score(5, 5), score(1, 2), score(0, 0)],
+            doubled_pawn: score(-10, -20),
+            isolated_pawn: score(-10, -15),
+            passed_pawn: [
+                score(0, 0),
+                score(5, 10),
+                score(10, 15),
+                score(15, 25),
+                score(25, 45),
+                score(40, 75),
+                score(60, 110),
+                score(0, 0),
+            ],
+            king_shield: score(12, 0),
+            king_open_file: score(-20, 0),
+            king_attack: score(-8, -2),
+            bishop_pair: score(30, 50),
+        }
+    }
+}
+
+impl EvalWeights {
+    /// The weights a config file can name, with the name of each.
+    fn named_weights(&mut self) -> Vec<(String, &mut [PhaseScore])> {
+        let mut weights: Vec<(String, &mut [PhaseScore])> = vec![
+            (String::from("material"), &mut self.material),
+            (String::from("mobility"), &mut self.mobility),
+            (String::from("doubled_pawn"), std::slice::from_mut(&mut self.doubled_pawn)),
+            (String::from("isolated_pawn"), std::slice::from_mut(&mut self.isolated_pawn)),
+            (String::from("passed_pawn"), &mut self.passed_pawn),
+            (String::from("king_shield"), std::slice::from_mut(&mut self.king_shield)),
+            (String::from("king_open_file"), std::slice::from_mut(&mut self.king_open_file)),
+            (String::from("king_attack"), std::slice::from_mut(&mut self.king_attack)),
+            (String::from("bishop_pair"), std::slice::from_mut(&mut self.bishop_pair)),
+        ];
+        for (name, table) in PIECE_NAMES.iter().zip(self.piece_square.iter_mut()) {
+            weights.push((format!("piece_square.{}", name), table));
+        }
+        weights
+    }
+
+    /// Reads weights from a config file's text, starting from the defaults.
+    ///
+    /// Each line names a weight and its values: `name: mg eg` for a single weight, or
+    /// `name.mg: ...` and `name.eg: ...` listing one half of every entry of a table. Empty lines
+    /// and lines starting with `#` are skipped.
+    ///
+    /// # Returns
+    /// The weights, or a message naming the first line that couldn't be read.
+    pub fn from_config(text: &str) -> Result<EvalWeights, String> {
+        let mut weights = EvalWeights::default();
+        let mut named_weights = weights.named_weights();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", line_number + 1, message);
+            let (name, values) = line.split_once(':').ok_or_else(|| error("expected 'name: values'"))?;
+            let values = values
+                .split_whitespace()
+                .map(|value| value.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| error("values must be whole numbers"))?;
+
+            let name = name.trim();
+            let (base_name, half) = match name.rsplit_once('.') {
+                Some((base_name, half @ ("mg" | "eg"))) => (base_name, Some(half)),
+                _ => (name, None),
+            };
+            let (_, entries) = named_weights
+                .iter_mut()
+                .find(|(weight_name, _)| weight_name == base_name)
+                .ok_or_else(|| error(&format!("unknown weight '{}'", base_name)))?;
+            match half {
+                None if entries.len() > 1 => return Err(error(&format!("'{}' needs '.mg' and '.eg' lines", name))),
+                None => match values[..] {
+                    [mg, eg] => entries[0] = PhaseScore::new(mg, eg),
+                    _ => return Err(error("expected a middlegame and an endgame value")),
+                },
+                Some(_) if values.len() != entries.len() => {
+                    return Err(error(&format!("expected {} values, found {}", entries.len(), values.len())));
+                }
+                Some(half) => {
+                    for (entry, value) in entries.iter_mut().zip(values) {
+                        match half {
+                            "mg" => entry.mg = value,
+                            _ => entry.eg = value,
+                        }
+                    }
+                }
+            }
+        }
+        Ok(weights)
+    }
+
+    /// Reads weights from the config file at `path`, see [`EvalWeights::from_config`].
+    pub fn load(path: &str) -> Result<EvalWeights, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        EvalWeights::from_config(&text).map_err(|error| format!("{}, {}", path, error))
+    }
+
+    /// Writes every weight in the format [`EvalWeights::from_config`] reads, as a starting
+    /// point for tuning.
+    pub fn to_config(&self) -> String {
+        let mut weights = self.clone();
+        let mut config = String::new();
+        for (name, entries) in weights.named_weights() {
+            if let [entry] = entries {
+                config.push_str(&format!("{}: {} {}\n", name, entry.mg, entry.eg));
+                continue;
+            }
+            let mg: Vec<String> = entries.iter().map(|entry| entry.mg.to_string()).collect();
+            let eg: Vec<String> = entries.iter().map(|entry| entry.eg.to_string()).collect();
+            config.push_str(&format!("{}.mg: {}\n{}.eg: {}\n", name, mg.join(" "), name, eg.join(" ")));
+        }
+        config
+    }
+}
+
+/// The terms of the evaluation, in the order [`EvalTrace`] lists them.
+const TERMS: [&str; 6] = ["Material", "Piece squares", "Mobility", "Pawn structure", "King safety", "Bishop pair"];
+
+/// The evaluation of a position broken down by term, for both sides.
+#[derive(Debug, Clone)]
+pub struct EvalTrace {
+    /// Each term's name and its white and black scores.
+    pub terms: Vec<(&'static str, [PhaseScore; 2])>,
+    pub phase: i32,
+    /// The final score in centipawns, from white's view.
+    pub score: i32,
+}
+
+/// The default evaluation, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct StandardEvaluator {
+    weights: EvalWeights,
+}
+
+/// Everything the terms share, computed once per evaluation.
+struct Position<'a> {
+    board: &'a Board,
+    pieces: [Bitboard; 2],
+    pawns: [Bitboard; 2],
+    /// Squares each side's pawns attack.
+    pawn_attacks: [Bitboard; 2],
+}
+
+impl StandardEvaluator {
+    pub fn new(weights: EvalWeights) -> StandardEvaluator {
+        StandardEvaluator { weights }
+    }
+
+    pub fn weights(&self) -> &EvalWeights {
+        &self.weights
+    }
+
+    /// Breaks the evaluation of `board` down by term.
+    pub fn trace(&self, board: &Board) -> EvalTrace {
+        let terms = self.terms(board);
+        let phase = phase(board);
+        EvalTrace {
+            terms: TERMS.iter().copied().zip(terms).collect(),
+            phase,
+            score: terms.iter().fold(PhaseScore::ZERO, |total, [white, black]| total + *white - *black).taper(phase),
+        }
+    }
+
+    /// Scores every term for white and black.
+    fn terms(&self, board: &Board) -> [[PhaseScore; 2]; TERMS.len()] {
+        let pawns = [board.get_bitboard(Piece::WhitePawn), board.get_bitboard(Piece::BlackPawn)];
+        let mut pawn_attacks = [0; 2];
+        for color in [Color::White, Color::Black] {
+            for square in squares(pawns[color as usize]) {
+                pawn_attacks[color as usize] |= attacks::pawn_attacks(color, square);
+            }
+        }
+        let position = Position {
+            board,
+            pieces: [board.get_color_bitboard(Color::White), board.get_color_bitboard(Color::Black)],
+            pawns,
+            pawn_attacks,
+        };
+
+        let mut terms = [[PhaseScore::ZERO; 2]; TERMS.len()];
+        for color in [Color::White, Color::Black] {
+            let side = color as usize;
+            let (material, piece_squares) = self.material_and_piece_squares(&position, color);
+            let (mobility, attacks) = self.mobility(&position, color);
+            terms[0][side] = material;
+            terms[1][side] = piece_squares;
+            terms[2][side] = mobility;
+            terms[3][side] = self.pawn_structure(&position, color);
+            // The attacks are on the other king
+            terms[4][1 - side] += self.king_attacks(&position, color.toggle_color(), attacks);
+            terms[4][side] += self.king_shelter(&position, color);
+            if board.get_bitboard(color.get_bishop()).count_ones() >= 2 {
+                terms[5][side] = self.weights.bishop_pair;
+            }
+        }
+        terms
+    }
+
+    fn material_and_piece_squares(&self, position: &Position, color: Color) -> (PhaseScore, PhaseScore) {
+        let mut material = PhaseScore::ZERO;
+        let mut piece_squares = PhaseScore::ZERO;
+        for piece in Piece::iter_color_pieces(&color) {
+            let kind = kind(piece);
+            for square in squares(position.board.get_bitboard(piece)) {
+                // Tables are from white's side, mirror the rows for black
+                let square = match color {
+                    Color::White => square,
+                    Color::Black => square ^ 56,
+                };
+                material += self.weights.material[kind];
+                piece_squares += self.weights.piece_square[kind][square as usize];
+            }
+        }
+        (material, piece_squares)
+    }
+
+    /// Scores the squares each piece reaches, leaving out squares of its own pieces and squares
+    /// attacked by enemy pawns.
+    ///
+    /// # Returns
+    /// The score, and every square the side's pieces attack.
+    fn mobility(&self, position: &Position, color: Color) -> (PhaseScore, Bitboard) {
+        let board = position.board;
+        let side = color as usize;
+        let available = !position.pieces[side] & !position.pawn_attacks[1 - side];
+        let mut score = PhaseScore::ZERO;
+        let mut attacked = position.pawn_attacks[side];
+        for piece in [color.get_knight(), color.get_bishop(), color.get_rook(), color.get_queen()] {
+            let kind = kind(piece);
+            for square in squares(board.get_bitboard(piece)) {
+                let straight = || {
+                    board.sees_down_file(square, true)
+                        | board.sees_down_file(square, false)
+                        | board.sees_down_rank(square, true)
+                        | board.sees_down_rank(square, false)
+                };
+                let diagonal = || {
+                    board.sees_down_diagonal(square, true, true)
+                        | board.sees_down_diagonal(square, true, false)
+                        | board.sees_down_diagonal(square, false, true)
+                        | board.sees_down_diagonal(square, false, false)
+                };
+                let seen = if piece.is_knight() {
+                    board.sees_like_knight(square)
+                } else if piece.is_bishop() {
+                    diagonal()
+                } else if piece.is_rook() {
+                    straight()
+                } else {
+                    straight() | diagonal()
+                };
+                attacked |= seen;
+                let reachable = (seen & available).count_ones() as i32;
+                score += self.weights.mobility[kind] * (reachable - MOBILITY_BASELINE[kind]);
+            }
+        }
+        (score, attacked)
+    }
+
+    fn pawn_structure(&self, position: &Position, color: Color) -> PhaseScore {
+        let side = color as usize;
+        let own_pawns = position.pawns[side];
+        let enemy_pawns = position.pawns[1 - side];
+        let mut score = PhaseScore::ZERO;
+        for col in 0..8 {
+            let on_file = (own_pawns & file_mask(col)).count_ones() as i32;
+            if on_file > 1 {
+                score += self.weights.doubled_pawn * (on_file - 1);
+            }
+        }
+        for square in squares(own_pawns) {
+            let (row, col) = (square.get_row(), square.get_col());
+            let neighbours = adjacent_files(col) & !file_mask(col);
+            if own_pawns & neighbours == 0 {
+                score += self.weights.isolated_pawn;
+            }
+            if enemy_pawns & adjacent_files(col) & ahead_of(row, color) == 0 {
+                score += self.weights.passed_pawn[relative_row(row, color)];
+            }
+        }
+        score
+    }
+
+    /// Scores the pawns in front of `color`'s king and the open files next to it.
+    fn king_shelter(&self, position: &Position, color: Color) -> PhaseScore {
+        let Some(king) = position.board.king_square_by_color(color) else {
+            return PhaseScore::ZERO;
+        };
+        let own_pawns = position.pawns[color as usize];
+        let (row, col) = (king.get_row(), king.get_col());
+        let mut score = PhaseScore::ZERO;
+        // The two rows in front of the king
+        let in_front = ahead_of(row, color)
+            & match color {
+                Color::White => !ahead_of(row.saturating_sub(2), color),
+                Color::Black => !ahead_of((row + 2).min(7), color),
+            };
+        let shield = own_pawns & adjacent_files(col) & in_front;
+        score += self.weights.king_shield * shield.count_ones() as i32;
+        for file in col.saturating_sub(1)..=(col + 1).min(7) {
+            if own_pawns & file_mask(file) == 0 {
+                score += self.weights.king_open_file;
+            }
+        }
+        score
+    }
+
+    /// Scores the squares around `color`'s king that its opponent attacks.
+    fn king_attacks(&self, position: &Position, color: Color, enemy_attacks: Bitboard) -> PhaseScore {
+        let Some(king) = position.board.king_square_by_color(color) else {
+            return PhaseScore::ZERO;
+        };
+        let zone = attacks::king_attacks(king) | 1 << king;
+        self.weights.king_attack * (zone & enemy_attacks).count_ones() as i32
+    }
+}
+
+/// How far the game is from the endgame, by the pieces left on the board.
+fn phase(board: &Board) -> i32 {
+    let phase: i32 = Piece::iter()
+        .map(|piece| PHASE_WEIGHTS[kind(piece)] * board.get_bitboard(piece).count_ones() as i32)
+        .sum();
+    phase.min(MAX_PHASE)
+}
+
+impl Evaluator for StandardEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let total = self
+            .terms(board)
+            .iter()
+            .fold(PhaseScore::ZERO, |total, [white, black]| total + *white - *black);
+        let score = total.taper(phase(board));
+        match board.active_player {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{:<16}|{:^15}|{:^15}|{:^15}", "Term", "White", "Black", "Total")?;
+        writeln!(f, "{:<16}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}", "", "MG", "EG", "MG", "EG", "MG", "EG")?;
+        writeln!(f, "{}", "-".repeat(16 + 3 * 16))?;
+        let mut total = PhaseScore::ZERO;
+        for (name, [white, black]) in &self.terms {
+            let difference = *white - *black;
+            total += difference;
+            writeln!(
+                f,
+                "{:<16}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+                name, white.mg, white.eg, black.mg, black.eg, difference.mg, difference.eg
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(16 + 3 * 16))?;
+        writeln!(f, "{:<16}|{:>15}|{:>15}|{:>7}{:>7}", "Total", "", "", total.mg, total.eg)?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{} (0 is the endgame)", self.phase, MAX_PHASE)?;
+        write!(f, "Score: {:+} centipawns for white", self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(fen: &str) -> i32 {
+        StandardEvaluator::default().evaluate(&Board::from_fen(fen).unwrap())
+    }
+
+    /// The position with the colours swapped, mirrored from white's side to black's.
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+                .collect()
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let active_player = if fields[1] == "w" { "b" } else { "w" };
+        format!("{} {} {} - {} {}", placement.join("/"), active_player, swap_case(fields[2]), fields[4], fields[5])
+    }
+
+    #[test]
+    fn is_symmetric() {
+        let evaluator = StandardEvaluator::default();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r5k1/5ppp/8/8/5PPP/8/8/R5K1 w - - 0 1",
+        ] {
+            let score = evaluator.evaluate(&Board::from_fen(fen).unwrap());
+            assert_eq!(score, evaluator.evaluate(&Board::from_fen(&mirror(fen)).unwrap()), "{}", fen);
+        }
+        assert_eq!(evaluate("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), 0);
+    }
+
+    #[test]
+    fn scores_the_terms() {
+        // Development and central pawns
+        assert!(evaluate("rnbqkbnr/pppppppp/8/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1") < 0);
+        // A passed pawn beats a blocked one
+        assert!(evaluate("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1") > evaluate("4k3/8/3p4/3P4/8/8/8/4K3 w - - 0 1") + 100);
+        // Doubled and isolated pawns are weaker than connected ones
+        assert!(evaluate("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1") < evaluate("4k3/8/8/8/8/8/2PP4/4K3 w - - 0 1"));
+        // The bishop pair
+        let trace = StandardEvaluator::default().trace(&Board::from_fen("4k3/8/8/8/8/8/8/2BBK3 w - - 0 1").unwrap());
+        assert_eq!(trace.terms[5].1, [EvalWeights::default().bishop_pair, PhaseScore::ZERO]);
+        // In the endgame the king belongs in the centre
+        assert!(evaluate("8/8/8/4K3/8/8/8/k7 w - - 0 1") > evaluate("8/8/8/8/8/8/8/K6k w - - 0 1"));
+        // Pawns in front of a castled king
+        let sheltered = "r5k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+        let exposed = "r5k1/5ppp/8/8/5PPP/8/8/R5K1 w - - 0 1";
+        let weights = EvalWeights::default();
+        let king_safety = |fen| StandardEvaluator::default().trace(&Board::from_fen(fen).unwrap()).terms[4].1[0];
+        assert_eq!(king_safety(sheltered).mg, 3 * weights.king_shield.mg);
+        assert!(king_safety(exposed).mg < king_safety(sheltered).mg);
+    }
+
+    #[test]
+    fn traces_add_up_to_the_evaluation() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1").unwrap();
+        let evaluator = StandardEvaluator::default();
+        let trace = evaluator.trace(&board);
+        assert_eq!(trace.score, -evaluator.evaluate(&board));
+        assert_eq!(trace.phase, MAX_PHASE);
+        let text = trace.to_string();
+        for term in TERMS {
+            assert!(text.contains(term));
+        }
+    }
+
+    #[test]
+    fn reads_weights_from_config() {
+        let defaults = EvalWeights::default();
+        assert_eq!(EvalWeights::from_config(&defaults.to_config()), Ok(defaults.clone()));
+
+        let weights = EvalWeights::from_config(
+            "# Tuned\n\nbishop_pair: 10 20\nmaterial.mg: 100 500 300 300 900 0\npassed_pawn.eg: 0 1 2 3 4 5 6 0\n",
+        )
+        .unwrap();
+        assert_eq!(weights.bishop_pair, PhaseScore::new(10, 20));
+        assert_eq!(weights.material[2], PhaseScore::new(300, defaults.material[2].eg));
+        assert_eq!(weights.passed_pawn[6].eg, 6);
+        assert_eq!(weights.piece_square, defaults.piece_square);
+
+        for (config, error) in [
+            ("bishop_pair 10 20", "line 1: expected 'name: values'"),
+            ("\nbishop_pair: 10 x", "line 2: values must be whole numbers"),
+            ("queen_bonus: 1 2", "line 1: unknown weight 'queen_bonus'"),
+            ("material.mg: 1 2", "line 1: expected 6 values, found 2"),
+            ("piece_square.king: 1 2", "line 1: 'piece_square.king' needs '.mg' and '.eg' lines"),
+        ] {
+            assert_eq!(EvalWeights::from_config(config), Err(String::from(error)));
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Playing below full strength.
+//!
+//! A [`SkillLevel`] from 0 to 20 weakens the AI the way a weaker player is weak, rather than by
+//! blundering at random:
+//! - it looks fewer plies ahead and at fewer positions,
+//! - it misjudges positions, its evaluation being off by an amount that shrinks with the level,
+//! - it searches its best few moves and doesn't always play the best of them, the worse levels
+//!   straying further and accepting worse moves,
+//! - it takes a moment before answering, instead of replying instantly to every move.
+//!
+//! Level 20 is full strength and changes nothing. The levels can also be chosen by Elo, from
+//! [`MIN_ELO`] at level 0 to [`MAX_ELO`] at level 20, a rough scale meant for picking an
+//! opponent rather than for rating one.
+use std::time::Duration;
+
+use crate::ai::eval::Evaluator;
+use crate::board::Board;
+
+/// Elo of level 0.
+pub const MIN_ELO: u32 = 800;
+/// Elo of level 20, full strength.
+pub const MAX_ELO: u32 = 2400;
+/// Root moves searched by a weakened AI, to choose between.
+pub const SKILL_LINES: usize = 3;
+
+/// How well the AI plays, from 0 to [`SkillLevel::MAX`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SkillLevel(u8);
+
+impl Default for SkillLevel {
+    fn default() -> SkillLevel {
+        SkillLevel::MAX
+    }
+}
+
+impl SkillLevel {
+    /// Full strength.
+    pub const MAX: SkillLevel = SkillLevel(20);
+
+    /// # Returns
+    /// `None` if `level` is above [`SkillLevel::MAX`].
+    pub fn new(level: u8) -> Option<SkillLevel> {
+        (level <= SkillLevel::MAX.0).then_some(SkillLevel(level))
+    }
+
+    /// The level nearest `elo`, clamped to the scale.
+    pub fn from_elo(elo: u32) -> SkillLevel {
+        let step = (MAX_ELO - MIN_ELO) / SkillLevel::MAX.0 as u32;
+        SkillLevel(((elo.clamp(MIN_ELO, MAX_ELO) - MIN_ELO + step / 2) / step) as u8)
+    }
+
+    pub fn level(self) -> u8 {
+        self.0
+    }
+
+    pub fn elo(self) -> u32 {
+        MIN_ELO + (MAX_ELO - MIN_ELO) * self.0 as u32 / SkillLevel::MAX.0 as u32
+    }
+
+    pub fn is_full_strength(self) -> bool {
+        self == SkillLevel::MAX
+    }
+
+    /// Deepest iteration searched, `None` at full strength.
+    pub fn depth_limit(self) -> Option<u8> {
+        (!self.is_full_strength()).then_some(1 + self.0 / 2)
+    }
+
+    /// Nodes searched at most, doubling every two levels, `None` at full strength.
+    pub fn node_limit(self) -> Option<u64> {
+        (!self.is_full_strength()).then_some(500 << (self.0 / 2))
+    }
+
+    /// Largest error added to evaluations, in centipawns.
+    pub fn eval_noise(self) -> i32 {
+        10 * (SkillLevel::MAX.0 - self.0) as i32
+    }
+
+    /// Chooses among the best moves of a search, ordered best first, the way a player of this
+    /// level would: every move gets a random bonus, bigger the weaker the level and the wider
+    /// the spread of scores, and moves further behind the best get more of their shortfall back.
+    ///
+    /// # Arguments
+    /// * `scores` - The moves' scores, best first.
+    /// * `random` - A random number, the same one always making the same choice.
+    ///
+    /// # Returns
+    /// The index of the move to play.
+    pub fn pick(self, scores: &[i32], random: u64) -> usize {
+        let Some(&top) = scores.first() else {
+            return 0;
+        };
+        if self.is_full_strength() {
+            return 0;
+        }
+        let weakness = 120 - 2 * self.0 as i64;
+        let spread = (top as i64 - *scores.last().unwrap_or(&top) as i64).min(100);
+        let mut random = random;
+        let mut best = (0, i64::MIN);
+        for (index, &score) in scores.iter().enumerate() {
+            random = mix(random);
+            let bonus = (weakness * (top as i64 - score as i64) + spread * (random % weakness as u64) as i64) / 128;
+            if score as i64 + bonus > best.1 {
+                best = (index, score as i64 + bonus);
+            }
+        }
+        best.0
+    }
+
+    /// Time to take over a move, at least, so the AI doesn't answer instantly. Weaker levels
+    /// take longer, from nothing at full strength to around a second at level 0.
+    ///
+    /// # Arguments
+    /// * `random` - A random number, varying the time between half and one and a half times
+    ///   the level's usual.
+    pub fn think_time(self, random: u64) -> Duration {
+        let usual = 40 * (SkillLevel::MAX.0 - self.0) as u64;
+        Duration::from_millis(usual / 2 + random % (usual + 1))
+    }
+}
+
+impl std::fmt::Display for SkillLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for SkillLevel {
+    type Err = String;
+
+    /// Reads a level from 0 to 20, or an Elo like `1500 elo`.
+    fn from_str(text: &str) -> Result<SkillLevel, String> {
+        let text = text.trim().to_ascii_lowercase();
+        if let Some(elo) = text.strip_suffix("elo") {
+            let elo = elo.trim().parse().map_err(|_| format!("Invalid Elo '{}'", text))?;
+            return Ok(SkillLevel::from_elo(elo));
+        }
+        text.parse().ok().and_then(SkillLevel::new).ok_or(format!("Invalid skill level '{}', expected 0 to 20", text))
+    }
+}
+
+/// SplitMix64's finaliser, turning a seed or a position's key into a well mixed number.
+pub fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// An evaluator off by up to `noise` centipawns.
+///
+/// The error depends on the position and the seed only, so a position scores the same every
+/// time it is reached in one search, and the transposition table stays consistent.
+#[derive(Debug)]
+pub struct NoisyEvaluator<'a> {
+    inner: &'a dyn Evaluator,
+    noise: i32,
+    seed: u64,
+}
+
+impl<'a> NoisyEvaluator<'a> {
+    pub fn new(inner: &'a dyn Evaluator, noise: i32, seed: u64) -> NoisyEvaluator<'a> {
+        NoisyEvaluator { inner, noise, seed }
+    }
+}
+
+impl Evaluator for NoisyEvaluator<'_> {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let error = mix(board.zobrist_key() ^ self.seed) % (2 * self.noise as u64 + 1);
+        self.inner.evaluate(board) + error as i32 - self.noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::eval::StandardEvaluator;
+
+    #[test]
+    fn converts_levels_and_elo() {
+        assert_eq!(SkillLevel::new(21), None);
+        assert_eq!(SkillLevel::from_elo(0), SkillLevel(0));
+        assert_eq!(SkillLevel::from_elo(1500), SkillLevel(9));
+        assert_eq!(SkillLevel::from_elo(9999), SkillLevel::MAX);
+        assert_eq!((SkillLevel(0).elo(), SkillLevel(10).elo(), SkillLevel::MAX.elo()), (800, 1600, 2400));
+        assert_eq!("7".parse(), Ok(SkillLevel(7)));
+        assert_eq!("1600 Elo".parse(), Ok(SkillLevel(10)));
+        assert!("21".parse::<SkillLevel>().is_err());
+        assert!("x elo".parse::<SkillLevel>().is_err());
+    }
+
+    #[test]
+    fn weakens_with_the_level() {
+        let full = SkillLevel::MAX;
+        assert_eq!((full.depth_limit(), full.node_limit(), full.eval_noise()), (None, None, 0));
+        assert_eq!(full.think_time(12345), Duration::ZERO);
+        let beginner = SkillLevel(0);
+        assert_eq!((beginner.depth_limit(), beginner.node_limit(), beginner.eval_noise()), (Some(1), Some(500), 200));
+        assert!(beginner.think_time(0) >= Duration::from_millis(400));
+        assert!(SkillLevel(10).depth_limit() > beginner.depth_limit());
+
+        let evaluator = StandardEvaluator::default();
+        let noisy = NoisyEvaluator::new(&evaluator, 50, 7);
+        let board = Board::std_new();
+        let error = noisy.evaluate(&board) - evaluator.evaluate(&board);
+        assert!(error.abs() <= 50);
+        assert_eq!(noisy.evaluate(&board) - evaluator.evaluate(&board), error);
+    }
+
+    #[test]
+    fn strays_from_the_best_move_more_when_weaker() {
+        let scores = [50, 20, -300];
+        assert!((0..100).all(|random| SkillLevel::MAX.pick(&scores, random) == 0));
+        let strays = |level| (0..1000).filter(|&random| SkillLevel(level).pick(&scores, random) != 0).count();
+        let (beginner, club) = (strays(0), strays(15));
+        assert!(beginner > club, "{} then {}", beginner, club);
+        assert!(beginner > 0 && beginner < 1000);
+        // A move far behind is still played only rarely
+        let far = (0..1000).filter(|&random| SkillLevel(15).pick(&scores, random) == 2).count();
+        assert!(far < 100, "{}", far);
+    }
+}
@@ -0,0 +1,1294 @@
+//! Syzygy endgame tablebases.
+//!
+//! Syzygy tables hold the exact result of every position with few enough pieces. Each material
+//! balance has two files, named after it like `KRPvKR.rtbw` and `KRPvKR.rtbz`:
+//! - the WDL table tells whether the side to move wins, draws or loses, and whether the win or
+//!   loss comes too late to beat the fifty move rule,
+//! - the DTZ table tells how many plies the winning side needs to reset the fifty move counter,
+//!   by a capture or a pawn move, while keeping the win. Playing the move with the lowest
+//!   distance always wins in the end, which a WDL table alone can't promise.
+//!
+//! The tables leave out positions with castling rights and don't store positions the side to
+//! move can settle with a capture, so a probe first tries the captures and only then reads the
+//! table. DTZ tables are also one sided, storing only the side to move that compresses best,
+//! and for the other side a probe searches one ply to reach a stored position.
+//!
+//! Tables are read from the directories given to [`Tablebases::open`] and a table is loaded into
+//! memory the first time a position needs it. Reading a position means mapping it onto the
+//! table's index, mirroring it so the strongest side is white and its leading piece stands in
+//! one corner of the board, then decompressing the block holding that index.
+//!
+//! The file format, and the order in which pieces are encoded into an index, follow the probing
+//! code published with the tables, which is the only specification of the format.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::Square;
+use crate::board::Board;
+use crate::chess_moves::{ChessMove, MoveList};
+
+/// Most pieces, kings included, any Syzygy table holds.
+pub const MAX_TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Table flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Piece letters in the order they appear in table names.
+const PIECE_LETTERS: [char; 6] = ['K', 'Q', 'R', 'B', 'N', 'P'];
+
+/// A position's result for the side to move.
+///
+/// Cursed wins and blessed losses are wins and losses that take too long: the fifty move rule
+/// turns them into draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// The same result from the opponent's view.
+    pub fn negate(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+
+    /// The distance to zero of a position that a capture or pawn move wins or loses from.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1,
+            Wdl::Draw => 0,
+        }
+    }
+}
+
+/// A root move with its tablebase result.
+#[derive(Debug, Clone, Copy)]
+pub struct RootMove {
+    pub chess_move: ChessMove,
+    /// The result after the move, from the mover's view.
+    pub wdl: Wdl,
+    /// Plies to the next capture or pawn move after this one, negative when losing, if the
+    /// DTZ tables were available.
+    pub dtz: Option<i32>,
+    /// Higher is better, moves of equal rank keep the result equally well.
+    pub rank: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/// Index tables shared by every table, computed once.
+struct Encoding {
+    /// `binomial[k][n]` ways to choose `k` of `n` squares.
+    binomial: [[u64; 64]; 6],
+    /// Squares a2 to h7 numbered from the edges inwards, the leading pawn has the highest.
+    map_pawns: [usize; 64],
+    lead_pawn_index: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+    /// Squares below the a1-h8 diagonal numbered 0 to 27.
+    map_b1h1h7: [usize; 64],
+    /// The a1-d1-d4 triangle numbered 0 to 9, the diagonal last.
+    map_a1d1d4: [usize; 64],
+    /// The 462 placements of two kings with the first in the a1-d1-d4 triangle.
+    map_kk: [[u64; 64]; 10],
+}
+
+/// Rank minus file: negative below the a1-h8 diagonal, positive above it.
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+fn kings_touch(first: usize, second: usize) -> bool {
+    (first / 8).abs_diff(second / 8) <= 1 && (first % 8).abs_diff(second % 8) <= 1
+}
+
+fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(|| {
+        let mut encoding = Encoding {
+            binomial: [[0; 64]; 6],
+            map_pawns: [0; 64],
+            lead_pawn_index: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                encoding.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..28 {
+            if off_diagonal(square) < 0 && square % 8 <= 3 {
+                encoding.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            encoding.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // With the first king on the diagonal the second stays on or below it, placements with
+        // both on the diagonal come last
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for index in 0..10 {
+            for first in (0..28).filter(|&square| encoding.map_a1d1d4[square] == index && (index > 0 || square == 1)) {
+                for second in 0..64 {
+                    if kings_touch(first, second) {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        encoding.map_kk[index][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            encoding.map_kk[index][second] = code;
+            code += 1;
+        }
+
+        encoding.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                encoding.binomial[k][n] = if k > 0 { encoding.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { encoding.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available_squares = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = 8 * rank + file;
+                    if lead_pawns == 1 {
+                        encoding.map_pawns[square] = available_squares;
+                        encoding.map_pawns[square ^ 7] = available_squares - 1;
+                        available_squares -= 2;
+                    }
+                    encoding.lead_pawn_index[lead_pawns][square] = index;
+                    index += encoding.binomial[lead_pawns - 1][encoding.map_pawns[square]];
+                }
+                encoding.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+        encoding
+    })
+}
+
+/// What a table's name says about its pieces.
+#[derive(Debug, Clone)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    /// Some piece other than a king is the only one of its kind and color.
+    has_unique_pieces: bool,
+    /// Pawns of the leading color, the side with fewer pawns if both have some, then the other.
+    pawn_count: [usize; 2],
+    /// Both sides have the same pieces.
+    symmetric: bool,
+}
+
+impl Material {
+    /// Reads a table name like `KRPvKR`.
+    fn from_code(code: &str) -> Option<Material> {
+        let (white, black) = code.split_once('v')?;
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let valid = |side: &str| side.starts_with('K') && count(side, 'K') == 1 && side.chars().all(|c| PIECE_LETTERS.contains(&c));
+        if !valid(white) || !valid(black) || code.len() - 1 > MAX_TB_PIECES {
+            return None;
+        }
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Material {
+            piece_count: code.len() - 1,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: PIECE_LETTERS[1..]
+                .iter()
+                .any(|&letter| count(white, letter) == 1 || count(black, letter) == 1),
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            symmetric: white == black,
+        })
+    }
+}
+
+/// The pieces of `color` in table name order, e.g. `KRP`.
+fn material_code(board: &Board, color: Color) -> String {
+    let pieces = [
+        color.get_king(),
+        color.get_queen(),
+        color.get_rook(),
+        color.get_bishop(),
+        color.get_knight(),
+        color.get_pawn(),
+    ];
+    let mut code = String::new();
+    for (piece, letter) in pieces.into_iter().zip(PIECE_LETTERS) {
+        for _ in 0..board.get_bitboard(piece).count_ones() {
+            code.push(letter);
+        }
+    }
+    code
+}
+
+/// The decoding data of one table: one side to move, and one file of the leading pawn.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Length of the shortest Huffman symbol, or the only value of a single valued table.
+    min_sym_len: u8,
+    num_blocks: u64,
+    block_size: u64,
+    /// Every `span` values have an entry in the sparse index.
+    span: u64,
+    // Offsets of the tables in the file
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: u64,
+    sparse_index: usize,
+    sparse_index_size: u64,
+    data: usize,
+    /// `base64[l]` is the lowest symbol of length `l + min_sym_len`, padded to 64 bits.
+    base64: Vec<u64>,
+    /// Values each symbol expands to, less one.
+    symlen: Vec<u8>,
+    /// The pieces in the order they are encoded, as 1 to 6 for white and 9 to 14 for black.
+    pieces: [u8; MAX_TB_PIECES],
+    /// Multiplier of each group's index.
+    group_index: [u64; MAX_TB_PIECES + 1],
+    /// Pieces in each group, ending with a zero.
+    group_len: [usize; MAX_TB_PIECES + 1],
+    /// Where the DTZ values of wins, losses, cursed wins and blessed losses start in the map.
+    map_index: [usize; 4],
+}
+
+/// A loaded table file.
+struct Table {
+    bytes: Vec<u8>,
+    /// Decoding data by side to move, then by file of the leading pawn.
+    items: Vec<Vec<PairsData>>,
+    /// Offset of the DTZ value map.
+    map: usize,
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Option<u8> {
+    bytes.get(offset).copied()
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+impl PairsData {
+    /// The two symbols `symbol` expands to. A symbol without children stores its value on the
+    /// left.
+    fn children(&self, bytes: &[u8], symbol: usize) -> Option<(usize, usize)> {
+        let entry = bytes.get(self.btree + 3 * symbol..self.btree + 3 * symbol + 3)?;
+        let left = ((entry[1] as usize & 0xF) << 8) | entry[0] as usize;
+        let right = ((entry[2] as usize) << 4) | (entry[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    /// Groups pieces encoded together: the leading group, then pieces of the same kind and
+    /// color, e.g. `KRvKN` is `KRK` and `N`. The order byte says in which order the groups'
+    /// indices are combined.
+    fn set_groups(&mut self, material: &Material, order: [u8; 2], file: usize) {
+        let encoding = encoding();
+        let mut n = 0;
+        let mut first_len: i32 = if material.has_pawns {
+            0
+        } else if material.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        self.group_len[0] = 1;
+        for i in 1..material.piece_count {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[n] += 1;
+            } else {
+                n += 1;
+                self.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_len[n] = 0;
+
+        let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if both_pawns { self.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while (next < n || k == order[0] || k == order[1]) && k < 16 {
+            if k == order[0] {
+                self.group_index[0] = index;
+                index *= if material.has_pawns {
+                    encoding.lead_pawns_size[self.group_len[0]][file]
+                } else if material.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                self.group_index[1] = index;
+                index *= encoding.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_index[next] = index;
+                index *= encoding.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_index[n] = index;
+    }
+
+    /// Reads the sizes and the Huffman code of the table from `offset`.
+    ///
+    /// # Returns
+    /// The offset after them.
+    fn set_sizes(&mut self, bytes: &[u8], mut offset: usize) -> Option<usize> {
+        self.flags = read_u8(bytes, offset)?;
+        offset += 1;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            self.min_sym_len = read_u8(bytes, offset)?;
+            return Some(offset + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0).unwrap_or(MAX_TB_PIECES);
+        let table_size = self.group_index[groups];
+        self.block_size = 1 << read_u8(bytes, offset)?.min(63);
+        self.span = 1 << read_u8(bytes, offset + 1)?.min(63);
+        self.sparse_index_size = table_size.div_ceil(self.span);
+        let padding = read_u8(bytes, offset + 2)? as u64;
+        self.num_blocks = read_u32_le(bytes, offset + 3)? as u64;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = read_u8(bytes, offset + 7)?;
+        self.min_sym_len = read_u8(bytes, offset + 8)?;
+        offset += 9;
+        if max_sym_len < self.min_sym_len || self.min_sym_len == 0 {
+            return None;
+        }
+        self.lowest_sym = offset;
+
+        // Longer symbols have lower values, so base64[] decreases with the length
+        let lengths = (max_sym_len - self.min_sym_len) as usize + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(bytes, self.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16_le(bytes, self.lowest_sym + 2 * i + 2)? as u64;
+            self.base64[i] = self.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base.checked_shl(64 - i as u32 - self.min_sym_len as u32).unwrap_or(0);
+        }
+        offset += 2 * lengths;
+
+        let symbols = read_u16_le(bytes, offset)? as usize;
+        offset += 2;
+        self.btree = offset;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.symlen[symbol] = self.set_symlen(bytes, symbol, &mut visited)?;
+            }
+        }
+        Some(offset + 3 * symbols + (symbols & 1))
+    }
+
+    /// Counts the values `symbol` expands to, less one, filling in its children on the way.
+    fn set_symlen(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let (left, right) = self.children(bytes, symbol)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        if left >= self.symlen.len() || right >= self.symlen.len() {
+            return None;
+        }
+        if !visited[left] {
+            self.symlen[left] = self.set_symlen(bytes, left, visited)?;
+        }
+        if !visited[right] {
+            self.symlen[right] = self.set_symlen(bytes, right, visited)?;
+        }
+        Some(self.symlen[left].wrapping_add(self.symlen[right]).wrapping_add(1))
+    }
+
+    fn block_length(&self, bytes: &[u8], block: u64) -> Option<i64> {
+        Some(read_u16_le(bytes, self.block_length + 2 * block as usize)? as i64)
+    }
+
+    /// Decompresses the value stored at `index`.
+    fn decompress(&self, bytes: &[u8], index: u64) -> Option<u16> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as u16);
+        }
+
+        // The sparse index points into the block holding every span-th value, walk the block
+        // lengths from there
+        let entry = self.sparse_index + 6 * (index / self.span) as usize;
+        let mut block = read_u32_le(bytes, entry)? as u64;
+        let mut offset = read_u16_le(bytes, entry + 4)? as i64;
+        offset += (index % self.span) as i64 - (self.span / 2) as i64;
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += self.block_length(bytes, block)? + 1;
+        }
+        while offset > self.block_length(bytes, block)? {
+            offset -= self.block_length(bytes, block)? + 1;
+            block += 1;
+        }
+
+        // Find the symbol covering the offset among the canonical Huffman symbols of the block
+        let mut pointer = self.data + (block * self.block_size) as usize;
+        let mut buffer = read_u64_be(bytes, pointer)?;
+        pointer += 8;
+        let mut buffer_size = 64;
+        let min_sym_len = self.min_sym_len as usize;
+        let mut symbol;
+        loop {
+            let mut len = 0;
+            while buffer < *self.base64.get(len)? {
+                len += 1;
+            }
+            symbol = ((buffer - self.base64[len]) >> (64 - len - min_sym_len)) as u16;
+            symbol = symbol.wrapping_add(read_u16_le(bytes, self.lowest_sym + 2 * len)?);
+            let values = *self.symlen.get(symbol as usize)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+            let len = len + min_sym_len;
+            buffer <<= len;
+            buffer_size -= len;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_u32_be(bytes, pointer).unwrap_or(0) as u64) << (64 - buffer_size);
+                pointer += 4;
+            }
+        }
+
+        // Symbols stand for pairs of symbols, expand them down to the value at the offset
+        let mut symbol = symbol as usize;
+        while self.symlen[symbol] != 0 {
+            let (left, right) = self.children(bytes, symbol)?;
+            let left_values = *self.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                symbol = left;
+            } else {
+                offset -= left_values;
+                symbol = right;
+            }
+            if symbol >= self.symlen.len() {
+                return None;
+            }
+        }
+        Some(self.children(bytes, symbol)?.0 as u16)
+    }
+}
+
+impl Table {
+    /// Reads a table file's header.
+    fn parse(bytes: Vec<u8>, kind: TableKind, material: &Material) -> Option<Table> {
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if bytes.get(0..4)? != magic {
+            return None;
+        }
+        let header = read_u8(&bytes, 4)?;
+        // WDL tables of unsymmetric material store both sides to move
+        let split = kind == TableKind::Wdl && header & 1 != 0;
+        if (header & 2 != 0) != material.has_pawns || (kind == TableKind::Wdl && split == material.symmetric) {
+            return None;
+        }
+        let mut offset = 5;
+
+        let sides = if kind == TableKind::Wdl && !material.symmetric { 2 } else { 1 };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        for file in 0..files {
+            let first = read_u8(&bytes, offset)?;
+            let second = if both_pawns { read_u8(&bytes, offset + 1)? } else { 0xFF };
+            let orders = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            offset += 1 + both_pawns as usize;
+            for k in 0..material.piece_count {
+                let byte = read_u8(&bytes, offset)?;
+                for (side, side_items) in items.iter_mut().enumerate() {
+                    side_items[file].pieces[k] = if side == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                offset += 1;
+            }
+            for (side_items, order) in items.iter_mut().zip(orders) {
+                side_items[file].set_groups(material, order, file);
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                offset = side_items[file].set_sizes(&bytes, offset)?;
+            }
+        }
+
+        let map = offset;
+        if kind == TableKind::Dtz {
+            for pairs in items[0].iter_mut() {
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if pairs.flags & FLAG_WIDE != 0 {
+                    offset += offset & 1;
+                    for index in pairs.map_index.iter_mut() {
+                        *index = (offset - map) / 2 + 1;
+                        offset += 2 * read_u16_le(&bytes, offset)? as usize + 2;
+                    }
+                } else {
+                    for index in pairs.map_index.iter_mut() {
+                        *index = offset - map + 1;
+                        offset += read_u8(&bytes, offset)? as usize + 1;
+                    }
+                }
+            }
+            offset += offset & 1;
+        }
+
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                side_items[file].sparse_index = offset;
+                offset += 6 * side_items[file].sparse_index_size as usize;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                side_items[file].block_length = offset;
+                offset += 2 * side_items[file].block_length_size as usize;
+            }
+        }
+        for file in 0..files {
+            for side_items in items.iter_mut() {
+                offset = (offset + 0x3F) & !0x3F;
+                side_items[file].data = offset;
+                offset += (side_items[file].num_blocks * side_items[file].block_size) as usize;
+            }
+        }
+        Some(Table { bytes, items, map })
+    }
+
+    /// Converts a DTZ table value to plies, reading it through the map if the table has one.
+    fn map_dtz(&self, file: usize, value: u16, wdl: Wdl) -> Option<i32> {
+        let pairs = &self.items[0][file];
+        let mut value = value as usize;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let start = pairs.map_index[[1, 3, 0, 2, 0][(wdl as i32 + 2) as usize]];
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.bytes, self.map + 2 * (start + value))? as usize
+            } else {
+                read_u8(&self.bytes, self.map + start + value)? as usize
+            };
+        }
+        // Tables store moves rather than plies where that loses nothing
+        let in_moves = match wdl {
+            Wdl::Win => pairs.flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => pairs.flags & FLAG_LOSS_PLIES == 0,
+            _ => true,
+        };
+        Some(if in_moves { 2 * value as i32 } else { value as i32 } + 1)
+    }
+}
+
+/// The result of reading a table.
+enum TableValue {
+    Value(i32),
+    /// The DTZ table only stores the other side to move.
+    ChangeSide,
+}
+
+/// A material balance's tables.
+struct TableFiles {
+    material: Material,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    /// Loaded on first use, `None` if the file turned out unreadable.
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl TableFiles {
+    fn table(&self, kind: TableKind) -> Option<&Table> {
+        let (cell, path) = match kind {
+            TableKind::Wdl => (&self.wdl, Some(&self.wdl_path)),
+            TableKind::Dtz => (&self.dtz, self.dtz_path.as_ref()),
+        };
+        cell.get_or_init(|| Table::parse(std::fs::read(path?).ok()?, kind, &self.material))
+            .as_ref()
+    }
+}
+
+/// Squares count from a1 in the tables and from a8 on the board.
+fn table_square(square: Square) -> usize {
+    (square ^ 56) as usize
+}
+
+/// A piece as the tables number them: pawn to king 1 to 6, plus 8 for black.
+fn table_piece(piece: Piece) -> u8 {
+    let kind = if piece.is_pawn() {
+        1
+    } else if piece.is_knight() {
+        2
+    } else if piece.is_bishop() {
+        3
+    } else if piece.is_rook() {
+        4
+    } else if piece.is_queen() {
+        5
+    } else {
+        6
+    };
+    kind + if piece.get_color() == Color::Black { 8 } else { 0 }
+}
+
+fn is_capture(board: &Board, chess_move: ChessMove) -> bool {
+    chess_move.get_meta_data().is_en_passant()
+        || (!chess_move.get_meta_data().is_castle() && board.get_piece_at(chess_move.get_target()).is_some())
+}
+
+fn is_zeroing(board: &Board, chess_move: ChessMove) -> bool {
+    is_capture(board, chess_move) || board.get_piece_at(chess_move.get_origin()).is_some_and(|piece| piece.is_pawn())
+}
+
+fn legal_moves(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+    ChessMove::generate_legal_moves(board, &mut moves);
+    moves
+}
+
+fn after(board: &Board, chess_move: ChessMove) -> Board {
+    let mut board = board.clone();
+    chess_move.make_move(&mut board);
+    board
+}
+
+fn is_mate(board: &Board) -> bool {
+    board.is_in_check(board.active_player) && legal_moves(board).is_empty()
+}
+
+/// Pieces on the board, kings included.
+pub fn piece_count(board: &Board) -> usize {
+    (board.get_color_bitboard(Color::White) | board.get_color_bitboard(Color::Black)).count_ones() as usize
+}
+
+/// A set of Syzygy tables found on disk.
+pub struct Tablebases {
+    /// By table name, e.g. `KRPvKR`.
+    tables: HashMap<String, TableFiles>,
+    max_pieces: usize,
+}
+
+impl std::fmt::Debug for Tablebases {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Tablebases({} tables)", self.tables.len())
+    }
+}
+
+impl Tablebases {
+    /// Finds the tables in `paths`, a list of directories separated like the `PATH` variable.
+    /// Tables are only read when first probed.
+    pub fn open(paths: &str) -> Result<Tablebases, String> {
+        let mut wdl_paths = Vec::new();
+        let mut dtz_paths = HashMap::new();
+        for directory in std::env::split_paths(paths).filter(|path| !path.as_os_str().is_empty()) {
+            let entries = std::fs::read_dir(&directory).map_err(|error| format!("{}: {}", directory.display(), error))?;
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let Some(code) = path.file_stem().and_then(|stem| stem.to_str()).map(String::from) else {
+                    continue;
+                };
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("rtbw") => wdl_paths.push((code, path)),
+                    Some("rtbz") => {
+                        dtz_paths.insert(code, path);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut tables = HashMap::new();
+        for (code, wdl_path) in wdl_paths {
+            let Some(material) = Material::from_code(&code) else {
+                continue;
+            };
+            let files = TableFiles {
+                material,
+                wdl_path,
+                dtz_path: dtz_paths.remove(&code),
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            };
+            tables.entry(code).or_insert(files);
+        }
+        let max_pieces = tables.values().map(|files| files.material.piece_count).max().unwrap_or(0);
+        Ok(Tablebases { tables, max_pieces })
+    }
+
+    /// Number of WDL tables found.
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Most pieces of any table found.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether `board` could be in the tables: few enough pieces and no castling rights.
+    pub fn covers(&self, board: &Board) -> bool {
+        board.castling_rights == 0 && piece_count(board) <= self.max_pieces
+    }
+
+    /// Reads the value stored for `board`, not minding captures.
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: Wdl) -> Option<TableValue> {
+        if piece_count(board) == 2 {
+            return Some(TableValue::Value(0));
+        }
+        let (white, black) = (material_code(board, Color::White), material_code(board, Color::Black));
+        // Tables have the stronger side as white, positions where black is stronger are
+        // mirrored
+        let (files, black_stronger) = match self.tables.get(&format!("{}v{}", white, black)) {
+            Some(files) => (files, false),
+            None => (self.tables.get(&format!("{}v{}", black, white))?, true),
+        };
+        let table = files.table(kind)?;
+        let material = &files.material;
+        let encoding = encoding();
+
+        // Symmetric tables only store white to move
+        let black_to_move = board.active_player == Color::Black;
+        let flip = black_stronger || (material.symmetric && black_to_move);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let side = (flip ^ black_to_move) as usize;
+
+        let mut occupied: Vec<(usize, u8)> = Vec::with_capacity(MAX_TB_PIECES);
+        for piece in Piece::iter() {
+            let mut bitboard = board.get_bitboard(piece);
+            while bitboard != 0 {
+                let square = bitboard.trailing_zeros() as Square;
+                occupied.push((table_square(square), table_piece(piece)));
+                bitboard &= bitboard - 1;
+            }
+        }
+        occupied.sort_unstable();
+
+        let mut squares = [0; MAX_TB_PIECES];
+        let mut pieces = [0; MAX_TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        // The tables split by the file of the leading pawn, the one nearest the edge and
+        // among those the one least advanced
+        let lead_piece = table.items[0][0].pieces[0] ^ flip_color;
+        if material.has_pawns {
+            for &(square, _) in occupied.iter().filter(|&&(_, piece)| piece == lead_piece) {
+                squares[size] = square ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns = size;
+            let lead = (0..lead_pawns).max_by_key(|&i| encoding.map_pawns[squares[i]])?;
+            squares.swap(0, lead);
+            file = squares[0] % 8;
+            if file > 3 {
+                file = 7 - file;
+            }
+        }
+
+        let items = table.items.get(if kind == TableKind::Wdl { side } else { 0 })?;
+        if kind == TableKind::Dtz {
+            // Symmetric tables without pawns hold both sides to move in one
+            let stored = (items[file].flags & FLAG_STM) as usize == side || (material.symmetric && !material.has_pawns);
+            if !stored {
+                return Some(TableValue::ChangeSide);
+            }
+        }
+        for &(square, piece) in occupied.iter().filter(|&&(_, piece)| !material.has_pawns || piece != lead_piece) {
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece ^ flip_color;
+            size += 1;
+        }
+        let pairs = &items[file];
+
+        // Order the pieces the way the table encodes them
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| pairs.pieces[i] == pieces[j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror the leading piece onto files a to d
+        if squares[0] % 8 > 3 {
+            for square in squares[..size].iter_mut() {
+                *square ^= 7;
+            }
+        }
+
+        let mut index: u64;
+        if material.has_pawns {
+            index = encoding.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| encoding.map_pawns[square]);
+            for (i, &square) in squares[1..lead_pawns].iter().enumerate() {
+                index += encoding.binomial[i + 1][encoding.map_pawns[square]];
+            }
+        } else {
+            // Without pawns the leading piece is also mirrored onto ranks 1 to 4, then below the
+            // a1-h8 diagonal
+            if squares[0] / 8 > 3 {
+                for square in squares[..size].iter_mut() {
+                    *square ^= 56;
+                }
+            }
+            for i in 0..pairs.group_len[0] {
+                match off_diagonal(squares[i]) {
+                    0 => continue,
+                    off if off > 0 => {
+                        for square in squares[i..size].iter_mut() {
+                            *square = ((*square >> 3) | (*square << 3)) & 63;
+                        }
+                    }
+                    _ => {}
+                }
+                break;
+            }
+
+            index = if material.has_unique_pieces {
+                let rank = |square: usize| (square / 8) as i64;
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as i64 + (s2 > s0) as i64;
+                let adjust2 = (s2 > s0) as i64 + (s2 > s1) as i64;
+                let index = if off_diagonal(s0) != 0 {
+                    (encoding.map_a1d1d4[s0] as i64 * 63 + (s1 as i64 - adjust1)) * 62 + s2 as i64 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + rank(s0) * 28 + encoding.map_b1h1h7[s1] as i64) * 62 + s2 as i64 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28
+                        + encoding.map_b1h1h7[s2] as i64
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(s0) * 7 * 6 + (rank(s1) - adjust1) * 6
+                        + (rank(s2) - adjust2)
+                };
+                index as u64
+            } else {
+                encoding.map_kk[encoding.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+
+        // The other groups, each as a combination of the squares the groups before it left free
+        index *= pairs.group_index[0];
+        let mut start = pairs.group_len[0];
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&earlier| square > earlier).count();
+                n += encoding.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            index += n * pairs.group_index[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = pairs.decompress(&table.bytes, index)?;
+        Some(TableValue::Value(match kind {
+            TableKind::Wdl => value as i32 - 2,
+            TableKind::Dtz => table.map_dtz(file, value, wdl)?,
+        }))
+    }
+
+    /// Probes the WDL table after trying the captures, and with `zeroing` the pawn moves too.
+    ///
+    /// # Returns
+    /// The result, and whether a capture or pawn move is best, where DTZ tables can't be
+    /// trusted.
+    fn search(&self, board: &Board, zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = legal_moves(board);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for &chess_move in moves.iter() {
+            let searched_move = if zeroing { is_zeroing(board, chess_move) } else { is_capture(board, chess_move) };
+            if !searched_move {
+                continue;
+            }
+            searched += 1;
+            let (wdl, _) = self.search(&after(board, chess_move), false)?;
+            let wdl = wdl.negate();
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        // With every move searched the table isn't needed, and may be wrong for positions with
+        // an en passant capture
+        let every_move = searched > 0 && searched == moves.len();
+        let stored = if every_move {
+            best
+        } else {
+            match self.probe_table(board, TableKind::Wdl, Wdl::Draw)? {
+                TableValue::Value(value) => Wdl::from_value(value),
+                TableValue::ChangeSide => return None,
+            }
+        };
+        // The table stores anything for positions a capture wins
+        if best >= stored {
+            return Some((best, best > Wdl::Draw || every_move));
+        }
+        Some((stored, false))
+    }
+
+    /// Probes the result of `board` for the side to move.
+    ///
+    /// # Returns
+    /// `None` if a table needed is missing.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        Some(self.search(board, false)?.0)
+    }
+
+    /// Probes the plies to the next capture or pawn move for the side to move, counting the
+    /// move itself, positive when winning, negative when losing and zero for draws. Cursed wins
+    /// and blessed losses count 100 more plies.
+    ///
+    /// # Returns
+    /// `None` if a table needed is missing.
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        let (wdl, zeroing) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(wdl.dtz_before_zeroing());
+        }
+        let sign = (wdl as i32).signum();
+        if let TableValue::Value(dtz) = self.probe_table(board, TableKind::Dtz, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * sign);
+        }
+
+        // The table stores the other side to move, look one move ahead for the move that
+        // keeps the result quickest when winning, and puts it off longest when losing
+        let mut best: Option<i32> = None;
+        for &chess_move in legal_moves(board).iter() {
+            let child = after(board, chess_move);
+            let dtz = if is_zeroing(board, chess_move) {
+                -self.search(&child, false)?.0.dtz_before_zeroing()
+            } else {
+                let dtz = -self.probe_dtz(&child)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && is_mate(&child) {
+                return Some(1);
+            }
+            if dtz.signum() == sign && best.is_none_or(|best| dtz < best) {
+                best = Some(dtz);
+            }
+        }
+        Some(best.unwrap_or(-1))
+    }
+
+    /// Ranks the legal moves of `board` by how well they keep its result, by distance to zero
+    /// if the DTZ tables are there and by result alone otherwise.
+    ///
+    /// Winning moves rank highest while the win can still beat the fifty move rule, and losing
+    /// moves put off the loss until the rule might save the game. `repeated` tells that a
+    /// position has repeated since the last capture or pawn move, so winning moves rank by
+    /// distance to make progress.
+    ///
+    /// # Returns
+    /// `None` if a table needed is missing or the position is out of the tables.
+    pub fn rank_root_moves(&self, board: &Board, repeated: bool) -> Option<Vec<RootMove>> {
+        if !self.covers(board) {
+            return None;
+        }
+        let half_moves = board.half_move_clock as i32;
+        let mut moves = Vec::new();
+        for &chess_move in legal_moves(board).iter() {
+            let child = after(board, chess_move);
+            let wdl = self.probe_wdl(&child)?.negate();
+            let dtz = if child.half_move_clock == 0 {
+                Some(wdl.dtz_before_zeroing())
+            } else {
+                self.probe_dtz(&child).map(|dtz| -dtz - dtz.signum())
+            };
+            // A mating move wins at once
+            let dtz = dtz.map(|dtz| if dtz == 2 && is_mate(&child) { 1 } else { dtz });
+            moves.push(RootMove { chess_move, wdl, dtz, rank: 0 });
+        }
+
+        let with_dtz = moves.iter().all(|root_move| root_move.dtz.is_some());
+        for root_move in moves.iter_mut() {
+            root_move.rank = match root_move.dtz {
+                Some(dtz) if with_dtz && dtz > 0 => {
+                    if dtz + half_moves <= 99 && !repeated { 1000 } else { 1000 - (dtz + half_moves) }
+                }
+                Some(dtz) if with_dtz && dtz < 0 => {
+                    if -2 * dtz + half_moves < 100 { -1000 } else { -1000 + (-dtz + half_moves) }
+                }
+                Some(_) if with_dtz => 0,
+                _ => [-1000, -899, 0, 899, 1000][(root_move.wdl as i32 + 2) as usize],
+            };
+            if !with_dtz {
+                root_move.dtz = None;
+            }
+        }
+        Some(moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// A directory of its own for each test's tables.
+    fn table_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("rusty_chess_syzygy_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Writes a table of a three piece material without pawns where every position has the
+    /// same value, `values[side]` for each side to move stored.
+    fn write_single_value_table(directory: &Path, name: &str, magic: [u8; 4], pieces: [u8; 3], values: &[u8]) {
+        let mut bytes = magic.to_vec();
+        bytes.push(if values.len() == 2 { 1 } else { 0 });
+        // Group order, then the pieces of each side to move in a nibble each
+        bytes.push(0);
+        bytes.extend(pieces.map(|piece| piece | piece << 4));
+        bytes.push(0);
+        for &value in values {
+            bytes.extend([FLAG_SINGLE_VALUE, value]);
+        }
+        std::fs::write(directory.join(name), bytes).unwrap();
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn builds_the_index_tables() {
+        let encoding = encoding();
+        assert_eq!(encoding.map_kk.iter().flatten().max(), Some(&461));
+        assert_eq!(encoding.map_b1h1h7.iter().max(), Some(&27));
+        assert_eq!(encoding.map_a1d1d4[1], 0);
+        // The diagonal comes last: a1, b2, c3 and d4
+        assert_eq!([0, 9, 18, 27].map(|square| encoding.map_a1d1d4[square]), [6, 7, 8, 9]);
+        assert_eq!(encoding.binomial[2][5], 10);
+        assert_eq!(encoding.binomial[5][63], 7_028_847);
+        // a2 and h2 are the first pawn squares, then a3 and h3
+        assert_eq!([8, 15, 16, 23].map(|square| encoding.map_pawns[square]), [47, 46, 45, 44]);
+        assert_eq!(encoding.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn names_tables_by_material() {
+        let position = board("8/8/4k3/8/2r5/8/3PK3/3R4 w - - 0 1");
+        assert_eq!(material_code(&position, Color::White), "KRP");
+        assert_eq!(material_code(&position, Color::Black), "KR");
+
+        let material = Material::from_code("KRPvKR").unwrap();
+        assert_eq!((material.piece_count, material.has_pawns, material.symmetric), (5, true, false));
+        assert!(material.has_unique_pieces);
+        assert_eq!(material.pawn_count, [1, 0]);
+        assert_eq!(Material::from_code("KPvKPP").unwrap().pawn_count, [1, 2]);
+        assert!(!Material::from_code("KNNvK").unwrap().has_unique_pieces);
+        assert!(Material::from_code("KQvKQ").unwrap().symmetric);
+        for code in ["KQK", "QKvK", "KXvK", "KKvK", "KQQQQQvKQ"] {
+            assert!(Material::from_code(code).is_none(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn probes_single_valued_tables() {
+        let directory = table_directory("single");
+        // KQvK: white to move wins, black to move loses. In DTZ the win is 5 moves away,
+        // stored for white to move
+        let pieces = [6, 5, 14];
+        write_single_value_table(&directory, "KQvK.rtbw", WDL_MAGIC, pieces, &[4, 0]);
+        write_single_value_table(&directory, "KQvK.rtbz", DTZ_MAGIC, pieces, &[4]);
+        std::fs::write(directory.join("notes.txt"), "not a table").unwrap();
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        assert_eq!((tablebases.len(), tablebases.max_pieces()), (1, 3));
+
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/2k5/8/K6Q w - - 0 1")), Some(Wdl::Win));
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/2k5/8/K6Q b - - 0 1")), Some(Wdl::Loss));
+        // Black with the queen is mirrored onto the same table
+        assert_eq!(tablebases.probe_wdl(&board("k6q/8/2K5/8/8/8/8/8 b - - 0 1")), Some(Wdl::Win));
+        // Taking the queen draws, whatever the table says
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/8/1kQ5/K7 b - - 0 1")), Some(Wdl::Draw));
+        // Bare kings need no table, other material has none
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/2k5/8/K7 w - - 0 1")), Some(Wdl::Draw));
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/2k5/8/K6R w - - 0 1")), None);
+
+        // Stored as 4 moves, 9 plies counting the move that zeroes
+        assert_eq!(tablebases.probe_dtz(&board("8/8/8/8/8/2k5/8/K6Q w - - 0 1")), Some(9));
+        // Black to move isn't stored, every black move leads to a position 9 plies from zeroing
+        assert_eq!(tablebases.probe_dtz(&board("8/8/8/8/8/2k5/8/K6Q b - - 0 1")), Some(-10));
+
+        // Moves that give the queen away are ranked below those that keep the win
+        let position = board("8/8/8/8/8/1k6/8/K1Q5 w - - 0 1");
+        let ranked = tablebases.rank_root_moves(&position, false).unwrap();
+        let rank = |notation: &str| ranked.iter().find(|m| m.chess_move.to_coordinate_notation() == notation).unwrap();
+        assert_eq!((rank("c1h1").wdl, rank("c1h1").rank), (Wdl::Win, 1000));
+        assert_eq!(rank("c1h1").dtz, Some(11));
+        assert_eq!((rank("c1c2").wdl, rank("c1c2").rank), (Wdl::Draw, 0));
+        assert!(tablebases.rank_root_moves(&Board::std_new(), false).is_none());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /// Real tables are too big to keep in the repository. Fetch KQvK, KRvK and KPvK, `.rtbw` and
+    /// `.rtbz`, from https://tablebase.lichess.ovh/tables/standard/3-4-5/ into a directory and
+    /// run `SYZYGY_PATH=<directory> cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs the KQvK, KRvK and KPvK tables in SYZYGY_PATH"]
+    fn probes_real_tables() {
+        let paths = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH names the directory of the tables");
+        let tablebases = Tablebases::open(&paths).unwrap();
+        for table in ["KQvK", "KRvK", "KPvK"] {
+            let position = board(&format!("4k3/8/8/8/8/8/{}7/4K3 w - - 0 1", &table[1..2]));
+            assert!(tablebases.probe_dtz(&position).is_some(), "{} is missing from {}", table, paths);
+        }
+
+        // Positions with known results, the DTZ where it is certain, with each side stronger
+        let positions = [
+            // Qb7 mates at once, black's only move Kb8 walks into it
+            ("k7/7Q/1K6/8/8/8/8/8 w - - 0 1", Wdl::Win, Some(1)),
+            ("k7/7Q/1K6/8/8/8/8/8 b - - 0 1", Wdl::Loss, Some(-2)),
+            // The queen is lost
+            ("8/8/8/8/8/8/2q5/k2K4 w - - 0 1", Wdl::Draw, Some(0)),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Wdl::Win, None),
+            ("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", Wdl::Loss, None),
+            ("r3k3/8/8/8/8/8/8/4K3 w - - 0 1", Wdl::Loss, None),
+            // So is the rook
+            ("8/8/8/8/8/8/kR6/4K3 b - - 0 1", Wdl::Draw, Some(0)),
+            // The king in front of its pawn on the sixth rank wins whoever moves
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", Wdl::Win, None),
+            ("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", Wdl::Loss, None),
+            ("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1", Wdl::Win, None),
+            ("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1", Wdl::Loss, None),
+            // The defender holds the opposition in front of the pawn
+            ("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1", Wdl::Draw, Some(0)),
+            ("4k3/8/8/8/8/8/4p3/4K3 b - - 0 1", Wdl::Draw, Some(0)),
+            // A rook pawn can't be queened against a king in the corner
+            ("k7/8/8/P7/8/8/8/6K1 w - - 0 1", Wdl::Draw, Some(0)),
+            // Stalemate
+            ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Wdl::Draw, Some(0)),
+            // Promoting zeroes the count
+            ("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", Wdl::Win, Some(1)),
+            ("4K3/8/k7/8/8/8/4p3/8 b - - 0 1", Wdl::Win, Some(1)),
+        ];
+        for (fen, wdl, dtz) in positions {
+            let position = board(fen);
+            assert_eq!(tablebases.probe_wdl(&position), Some(wdl), "{}", fen);
+            let probed_dtz = tablebases.probe_dtz(&position).unwrap();
+            match dtz {
+                Some(dtz) => assert_eq!(probed_dtz, dtz, "{}", fen),
+                None => assert_eq!(probed_dtz.signum(), (wdl as i32).signum(), "{}", fen),
+            }
+        }
+
+        // Every move keeping the win is ranked above those throwing it away
+        let ranked = tablebases.rank_root_moves(&board("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), false).unwrap();
+        let best = ranked.iter().max_by_key(|root_move| root_move.rank).unwrap();
+        assert_eq!(best.wdl, Wdl::Win);
+        assert!(ranked.iter().any(|root_move| root_move.wdl == Wdl::Draw));
+    }
+
+    #[test]
+    fn rejects_missing_directories_and_bad_files() {
+        assert!(Tablebases::open("/nonexistent/syzygy").is_err());
+        let directory = table_directory("bad");
+        std::fs::write(directory.join("KRvK.rtbw"), [0; 64]).unwrap();
+        let tablebases = Tablebases::open(directory.to_str().unwrap()).unwrap();
+        assert_eq!(tablebases.len(), 1);
+        assert_eq!(tablebases.probe_wdl(&board("8/8/8/8/8/2k5/8/K6R w - - 0 1")), None);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+//! Deciding when to stop thinking.
+//!
+//! A search on the clock has a [`TimeBudget`]. The hard limit is checked while searching and
+//! stops the search wherever it is, throwing the unfinished iteration away. The soft limit is
+//! only checked between iterations, to decide whether to start another one, and is scaled by
+//! how settled the search looks:
+//! - a best move that just changed, or a score that just dropped, means the search hasn't
+//!   seen the end of something yet, so it may think longer,
+//! - a best move that has held for many iterations is unlikely to change with one more,
+//! - an obvious recapture is played quickly once the search agrees with it,
+//! - a single legal move is played after the first iteration, only to have a score to report.
+use std::time::Duration;
+
+use crate::chess_moves::packed_move::PackedMove;
+use crate::engine::TimeBudget;
+
+/// A score this much below the last iteration's counts as failing low, in centipawns.
+const FAIL_LOW_MARGIN: i32 = 30;
+/// Iterations the best move must hold for before it counts as stable.
+const STABLE_ITERATIONS: u32 = 4;
+/// Iterations an easy move must hold for before it is played.
+const EASY_MOVE_ITERATIONS: u32 = 2;
+
+// Soft limit scales
+const BEST_MOVE_CHANGED: f64 = 1.6;
+const FAILED_LOW: f64 = 1.5;
+const STABLE: f64 = 0.7;
+const EASY_MOVE: f64 = 0.25;
+
+#[derive(Debug)]
+pub struct TimeManager {
+    budget: TimeBudget,
+    root_moves: usize,
+    /// A move expected to be right without much thought, like winning back a piece.
+    easy_move: Option<PackedMove>,
+    best_move: Option<PackedMove>,
+    /// Iterations in a row that ended with the same best move.
+    stable_iterations: u32,
+    previous_score: Option<i32>,
+}
+
+impl TimeManager {
+    /// # Arguments
+    /// * `budget` - The limits of the search.
+    /// * `root_moves` - The number of legal moves.
+    /// * `easy_move` - A move to play sooner if the search settles on it.
+    pub fn new(budget: TimeBudget, root_moves: usize, easy_move: Option<PackedMove>) -> TimeManager {
+        TimeManager {
+            budget,
+            root_moves,
+            easy_move,
+            best_move: None,
+            stable_iterations: 0,
+            previous_score: None,
+        }
+    }
+
+    /// Takes in a finished iteration.
+    ///
+    /// # Arguments
+    /// * `best_move` - The iteration's best move.
+    /// * `score` - Its score, from the side to move's view.
+    /// * `elapsed` - Time spent on the clock so far.
+    ///
+    /// # Returns
+    /// `true` if another iteration should start.
+    pub fn iteration_done(&mut self, best_move: PackedMove, score: i32, elapsed: Duration) -> bool {
+        let changed = self.best_move != Some(best_move);
+        self.stable_iterations = if changed { 0 } else { self.stable_iterations + 1 };
+        let failed_low = self.previous_score.is_some_and(|previous| score < previous - FAIL_LOW_MARGIN);
+        let first_iteration = self.best_move.is_none();
+        self.best_move = Some(best_move);
+        self.previous_score = Some(score);
+
+        if self.root_moves == 1 {
+            return false;
+        }
+        let mut scale = 1.0;
+        if changed && !first_iteration {
+            scale *= BEST_MOVE_CHANGED;
+        } else if self.stable_iterations >= STABLE_ITERATIONS {
+            scale *= STABLE;
+        }
+        if failed_low {
+            scale *= FAILED_LOW;
+        }
+        if self.easy_move == Some(best_move) && self.stable_iterations >= EASY_MOVE_ITERATIONS {
+            scale *= EASY_MOVE;
+        }
+        elapsed < self.budget.soft.mul_f64(scale).min(self.budget.hard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Color;
+    use crate::clock::ChessClock;
+    use crate::engine::SearchLimits;
+    use crate::rules::{TimeControls, Timer};
+
+    const BUDGET: TimeBudget = TimeBudget {
+        soft: Duration::from_millis(1000),
+        hard: Duration::from_millis(4000),
+    };
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn budgets_from_the_clock() {
+        let seconds = Duration::from_secs;
+        let time_controls = TimeControls {
+            initial_time_white: seconds(300),
+            initial_time_black: seconds(300),
+            time_per_move_white: seconds(2),
+            time_per_move_black: seconds(2),
+        };
+        let mut clock = ChessClock::new_from_time_controls(time_controls).unwrap();
+        clock.set_player_time_left(Color::Black, seconds(1));
+        let limits = SearchLimits {
+            move_time: Some(seconds(5)),
+            ..SearchLimits::default()
+        };
+        let mut limits = limits.with_clock(&clock, time_controls);
+        assert_eq!(limits.move_time, None);
+        // A thirtieth of the time left plus most of the increment
+        assert_eq!(limits.time_budget(Color::White), Some(TimeBudget { soft: millis(11_500), hard: seconds(46) }));
+        // Little time left caps both limits
+        assert_eq!(limits.time_budget(Color::Black), Some(TimeBudget { soft: millis(500), hard: millis(750) }));
+
+        limits.moves_to_go = Some(1);
+        limits.move_overhead = seconds(100);
+        assert_eq!(limits.time_budget(Color::White), Some(TimeBudget { soft: seconds(100), hard: seconds(150) }));
+        limits.move_time = Some(seconds(5));
+        assert_eq!(limits.time_budget(Color::White), Some(TimeBudget { soft: millis(1), hard: millis(1) }));
+        assert_eq!(SearchLimits::default().time_budget(Color::White), None);
+    }
+
+    #[test]
+    fn stretches_the_soft_limit_while_unsettled() {
+        let (first, second) = (PackedMove::from_u16(1), PackedMove::from_u16(2));
+        let mut time = TimeManager::new(BUDGET, 20, None);
+        assert!(time.iteration_done(first, 10, millis(100)));
+        assert!(time.iteration_done(first, 10, millis(900)));
+        assert!(!time.iteration_done(first, 10, millis(1100)));
+
+        // The best move just changed
+        assert!(time.iteration_done(second, 10, millis(1500)));
+        assert!(!time.iteration_done(second, 10, millis(1500)));
+        // The score dropped
+        assert!(time.iteration_done(second, -50, millis(1400)));
+        // Both at once
+        assert!(time.iteration_done(first, -100, millis(2300)));
+        assert!(!time.iteration_done(second, -200, millis(4000)));
+    }
+
+    #[test]
+    fn stops_early_when_the_move_is_clear() {
+        let (best, other) = (PackedMove::from_u16(1), PackedMove::from_u16(2));
+        let mut time = TimeManager::new(BUDGET, 20, None);
+        for _ in 0..STABLE_ITERATIONS {
+            assert!(time.iteration_done(best, 10, millis(600)));
+        }
+        assert!(!time.iteration_done(best, 10, millis(800)));
+
+        let mut time = TimeManager::new(BUDGET, 20, Some(best));
+        assert!(time.iteration_done(best, 300, millis(10)));
+        assert!(time.iteration_done(best, 300, millis(200)));
+        assert!(!time.iteration_done(best, 300, millis(300)));
+        // Only once the search agrees
+        let mut time = TimeManager::new(BUDGET, 20, Some(best));
+        assert!(time.iteration_done(other, 0, millis(300)));
+
+        let mut time = TimeManager::new(BUDGET, 1, None);
+        assert!(!time.iteration_done(best, 0, millis(0)));
+    }
+}
@@ -0,0 +1,334 @@
+//! Transposition table.
+//!
+//! The same position is reached by many move orders, and iterative deepening searches every
+//! position again on each iteration. The table remembers what the search learnt about each
+//! position, keyed by its Zobrist key: the depth it was searched to, its score and whether that
+//! score is exact or only a bound, and the best move, which is searched first next time even
+//! when the score can't be reused.
+//!
+//! The table is a fixed number of buckets of two entries. The first entry keeps the deepest
+//! search of the current generation, the second is always replaced, so a deep result isn't
+//! pushed out by the many shallow ones searched after it while recent positions still find a
+//! place.
+//!
+//! Search threads share one table without locks. Each entry is two atomic words, the data and
+//! the key XORed with the data. A probe recomputes the key from both words, so an entry torn by
+//! two threads writing at once fails the check and reads as a miss instead of returning another
+//! position's data.
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::ai::MATE_THRESHOLD;
+use crate::board::zobrist::ZobristKey;
+use crate::chess_moves::packed_move::PackedMove;
+
+/// Table size used until another is set, in megabytes.
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+
+/// What a stored score says about the position's true score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact = 1,
+    /// The search failed high, the true score is at least this.
+    Lower = 2,
+    /// The search failed low, the true score is at most this.
+    Upper = 3,
+}
+
+/// A probed entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Option<PackedMove>,
+    /// Score from the side to move's view, with mate scores counted from the probing node.
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+// Layout of an entry's data word
+const SCORE_SHIFT: u32 = 16;
+const SCORE_BITS: u32 = 20;
+const DEPTH_SHIFT: u32 = 36;
+const BOUND_SHIFT: u32 = 44;
+const AGE_SHIFT: u32 = 46;
+const AGE_MASK: u8 = 0b11_1111;
+
+#[derive(Default)]
+struct Slot {
+    /// The position's key XORed with `data`.
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// One depth preferred slot followed by one always replaced slot.
+#[derive(Default)]
+struct Bucket([Slot; 2]);
+
+struct Data {
+    best_move: PackedMove,
+    score: i32,
+    depth: u8,
+    bound: u8,
+    age: u8,
+}
+
+impl Data {
+    fn pack(&self) -> u64 {
+        let score = (self.score as u64) & ((1 << SCORE_BITS) - 1);
+        self.best_move.to_u16() as u64
+            | score << SCORE_SHIFT
+            | (self.depth as u64) << DEPTH_SHIFT
+            | (self.bound as u64) << BOUND_SHIFT
+            | ((self.age & AGE_MASK) as u64) << AGE_SHIFT
+    }
+
+    fn unpack(data: u64) -> Data {
+        // Shift the score to the top of the word and back to extend its sign
+        let score = ((data << (64 - SCORE_SHIFT - SCORE_BITS)) as i64 >> (64 - SCORE_BITS)) as i32;
+        Data {
+            best_move: PackedMove::from_u16(data as u16),
+            score,
+            depth: (data >> DEPTH_SHIFT) as u8,
+            bound: (data >> BOUND_SHIFT) as u8 & 0b11,
+            age: (data >> AGE_SHIFT) as u8 & AGE_MASK,
+        }
+    }
+}
+
+impl Slot {
+    /// Reads the slot if it holds `key`.
+    fn read(&self, key: ZobristKey) -> Option<Data> {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        (check ^ data == key && data != 0).then(|| Data::unpack(data))
+    }
+
+    fn write(&self, key: ZobristKey, data: u64) {
+        self.data.store(data, Ordering::Relaxed);
+        self.check.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+/// A fixed size table of search results, shared by every thread of a search.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    /// Incremented by every search, so entries of earlier searches are replaced first.
+    age: AtomicU8,
+}
+
+impl std::fmt::Debug for TranspositionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TranspositionTable({} buckets)", self.buckets.len())
+    }
+}
+
+/// Mate scores count plies from the root. Stored, they count from the entry's position, so
+/// they stay right when it is reached at another ply.
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl TranspositionTable {
+    /// Creates a table of `megabytes`, at least one bucket.
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let bucket_count = (megabytes.min(MAX_HASH_MB) << 20) / size_of::<Bucket>();
+        TranspositionTable {
+            buckets: (0..bucket_count.max(1)).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// Empties the table, e.g. for a new game.
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.0) {
+            slot.write(0, 0);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    /// Starts a new generation, called once per search.
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: ZobristKey) -> &Bucket {
+        // Maps the key onto the buckets without needing a power of two count
+        let index = (key as u128 * self.buckets.len() as u128) >> 64;
+        &self.buckets[index as usize]
+    }
+
+    /// Looks up `key`, reached `ply` plies from the root.
+    pub fn probe(&self, key: ZobristKey, ply: usize) -> Option<TtEntry> {
+        let data = self.bucket(key).0.iter().find_map(|slot| slot.read(key))?;
+        Some(TtEntry {
+            best_move: (!data.best_move.is_none()).then_some(data.best_move),
+            score: score_from_table(data.score, ply),
+            depth: data.depth,
+            bound: match data.bound {
+                1 => Bound::Exact,
+                2 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+        })
+    }
+
+    /// Stores a search result for `key`, reached `ply` plies from the root.
+    ///
+    /// The result goes to the depth preferred slot if it is at least as deep as that slot's
+    /// entry, or that entry is for the same position or from an earlier search, and to the
+    /// always replaced slot otherwise.
+    pub fn store(
+        &self,
+        key: ZobristKey,
+        ply: usize,
+        depth: u8,
+        bound: Bound,
+        score: i32,
+        best_move: Option<PackedMove>,
+    ) {
+        let age = self.age.load(Ordering::Relaxed) & AGE_MASK;
+        let [preferred, always] = &self.bucket(key).0;
+        let slot = match Data::unpack(preferred.data.load(Ordering::Relaxed)) {
+            existing if existing.bound == 0 || existing.age != age || depth >= existing.depth => preferred,
+            _ if preferred.read(key).is_some() => preferred,
+            _ => always,
+        };
+        // A result without a move, from failing low, keeps the move found before
+        let best_move = best_move.or_else(|| slot.read(key).map(|data| data.best_move)).unwrap_or(PackedMove::NONE);
+        let data = Data {
+            best_move,
+            score: score_to_table(score, ply),
+            depth,
+            bound: bound as u8,
+            age,
+        };
+        slot.write(key, data.pack());
+    }
+
+    /// How full the table is in permille, sampled from the entries of the current search.
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed) & AGE_MASK;
+        let sample = self.buckets.iter().take(500).flat_map(|bucket| &bucket.0);
+        let (mut used, mut total) = (0, 0);
+        for slot in sample {
+            let data = Data::unpack(slot.data.load(Ordering::Relaxed));
+            total += 1;
+            if data.bound != 0 && data.age == age {
+                used += 1;
+            }
+        }
+        used * 1000 / total.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::MATE_SCORE;
+    use std::sync::Arc;
+
+    fn packed(value: u16) -> Option<PackedMove> {
+        Some(PackedMove::from_u16(value))
+    }
+
+    #[test]
+    fn stores_and_probes_entries() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.buckets.len(), (1 << 20) / 32);
+        assert_eq!(table.probe(42, 0), None);
+
+        table.store(42, 3, 7, Bound::Lower, -1234, packed(0x1234));
+        let entry = table.probe(42, 5).unwrap();
+        assert_eq!(entry, TtEntry { best_move: packed(0x1234), score: -1234, depth: 7, bound: Bound::Lower });
+
+        // Failing low leaves the best move known
+        table.store(42, 3, 8, Bound::Upper, 50, None);
+        assert_eq!(table.probe(42, 3).unwrap().best_move, packed(0x1234));
+
+        // Mate 9 plies from the root found 4 plies deep is mate in 5 from that node, and 7
+        // plies away when the position is reached at ply 2
+        table.store(99, 4, 1, Bound::Exact, MATE_SCORE - 9, None);
+        assert_eq!(table.probe(99, 2).unwrap().score, MATE_SCORE - 7);
+        table.store(99, 4, 1, Bound::Exact, -MATE_SCORE + 9, None);
+        assert_eq!(table.probe(99, 2).unwrap().score, -MATE_SCORE + 7);
+
+        table.clear();
+        assert_eq!(table.probe(42, 0), None);
+    }
+
+    #[test]
+    fn keeps_deep_entries_and_replaces_old_ones() {
+        let table = TranspositionTable::new(0);
+        assert_eq!(table.buckets.len(), 1);
+
+        table.store(1, 0, 10, Bound::Exact, 1, None);
+        // Shallower results of other positions share the always replaced slot
+        table.store(2, 0, 3, Bound::Exact, 2, None);
+        table.store(3, 0, 3, Bound::Exact, 3, None);
+        assert_eq!(table.probe(1, 0).unwrap().depth, 10);
+        assert_eq!(table.probe(2, 0), None);
+        assert_eq!(table.probe(3, 0).unwrap().score, 3);
+
+        // The same position is updated in place, even by a shallower search
+        table.store(1, 0, 5, Bound::Lower, 11, None);
+        assert_eq!(table.probe(1, 0).unwrap().score, 11);
+
+        // A deep entry of an earlier search makes way
+        table.store(1, 0, 10, Bound::Exact, 1, None);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+        table.store(4, 0, 1, Bound::Exact, 4, None);
+        assert_eq!(table.probe(1, 0), None);
+        assert_eq!(table.probe(4, 0).unwrap().score, 4);
+        assert_eq!(table.hashfull(), 500);
+    }
+
+    #[test]
+    fn torn_entries_read_as_misses() {
+        let table = TranspositionTable::new(0);
+        table.store(7, 0, 5, Bound::Exact, 70, packed(7));
+        // Another thread's data landing between the two words of this entry
+        table.buckets[0].0[0].data.store(12345, Ordering::Relaxed);
+        assert_eq!(table.probe(7, 0), None);
+
+        // Threads hammering one bucket only ever read entries that belong to the key
+        let table = Arc::new(TranspositionTable::new(0));
+        let threads: Vec<_> = (1..=4u64)
+            .map(|thread| {
+                let table = Arc::clone(&table);
+                std::thread::spawn(move || {
+                    for i in 0..20_000u64 {
+                        let key = thread * 1_000_003 + i % 7;
+                        table.store(key, 0, (key % 64) as u8, Bound::Exact, (key % 100_000) as i32, None);
+                        for probe in [key, key + 1] {
+                            if let Some(entry) = table.probe(probe, 0) {
+                                assert_eq!(entry.score, (probe % 100_000) as i32);
+                                assert_eq!(entry.depth, (probe % 64) as u8);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
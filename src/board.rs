@@ -2,22 +2,72 @@
 //!
 //! This module defines the board, castling rights, bitboards, and extended square functionality.
 //! It provides methods to query and update board state, add/remove pieces, and render the board.
+pub mod attacks;
+pub mod fen;
 pub(crate) mod pieces;
+pub mod see;
 pub mod square;
+mod validation;
+pub mod zobrist;
 
 pub(crate) use pieces::Color;
 pub use pieces::Piece;
 pub(crate) use square::Square;
+use zobrist::{ZobristKey, ZOBRIST_KEYS};
 pub(crate) use square::SquareExt;
 use crate::board::square::Col;
 use crate::board::square::Row;
+use crate::rules::CastleType;
 
-/// Errors that may occur when performing board operations.
+/// Errors that may occur when performing board operations, or that make a position illegal
+/// (see [`Board::validate`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BoardError {
     /// The requested piece could not be found at the given square.
     PieceNotFound,
     /// The target square is already occupied.
     SquareOccupied,
+    /// More than one piece occupies the square.
+    OverlappingPieces(Square),
+    /// The given side has no king.
+    MissingKing(Color),
+    /// The given side has more than one king.
+    TooManyKings(Color),
+    /// The given side has more than 8 pawns.
+    TooManyPawns(Color),
+    /// The given side has more than 16 pieces.
+    TooManyPieces(Color),
+    /// A pawn stands on the first or eighth rank.
+    PawnOnBackRank(Square),
+    /// The castling rights allow a castle while the king or rook is not on its starting square.
+    InvalidCastlingRights(Color, CastleType),
+    /// The en passant square could not have been created by the previous move.
+    InvalidEnPassantSquare(Square),
+    /// The side that is not to move is in check.
+    OpponentInCheck(Color),
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::PieceNotFound => write!(f, "Piece not found"),
+            BoardError::SquareOccupied => write!(f, "Square is already occupied"),
+            BoardError::OverlappingPieces(square) => write!(f, "More than one piece on {}", square.to_square_str()),
+            BoardError::MissingKing(color) => write!(f, "{:?} has no king", color),
+            BoardError::TooManyKings(color) => write!(f, "{:?} has more than one king", color),
+            BoardError::TooManyPawns(color) => write!(f, "{:?} has more than 8 pawns", color),
+            BoardError::TooManyPieces(color) => write!(f, "{:?} has more than 16 pieces", color),
+            BoardError::PawnOnBackRank(square) => write!(f, "Pawn on back rank square {}", square.to_square_str()),
+            BoardError::InvalidCastlingRights(color, castle_type) => write!(
+                f,
+                "{:?} may not castle {}, the king or rook has left its starting square",
+                color,
+                castle_type.to_string()
+            ),
+            BoardError::InvalidEnPassantSquare(square) => write!(f, "Impossible en passant square {}", square.to_square_str()),
+            BoardError::OpponentInCheck(color) => write!(f, "{:?} is in check but it is not their move", color),
+        }
+    }
 }
 
 /// Contains castling rights for both white and black.
@@ -110,9 +160,13 @@ impl BitboardExt for Bitboard {
 #[derive(Clone, Debug)]
 /// Represents a chess board with pieces, castling rights, en passant state,
 /// move counters, and the active player.
+///
+/// Writing `castling_rights`, `en_passant_square` or `active_player` directly leaves the Zobrist
+/// key stale; prefer their setters or call `refresh_zobrist_key` afterwards.
 pub struct Board {
     data: [Bitboard; 12],
     piece_locations: Bitboard,
+    zobrist_key: ZobristKey,
     pub castling_rights: CastlingRights,
     pub en_passant_square: Option<Square>,
     pub half_move_clock: u8,
@@ -162,7 +216,7 @@ impl Board {
     /// assert_eq!(board.active_player, Color::White);
     /// ```
     pub fn std_new() -> Board {
-        Board{
+        let mut board = Board{
             castling_rights : CastlingRights::ALL_CAN_CASTLE,
             en_passant_square : None,
             half_move_clock : 0,
@@ -178,7 +232,10 @@ impl Board {
                 0x0800000000000000,0x0000000000000008, // Queens
                 0x1000000000000000,0x0000000000000010, // Kings
             ],
-        }
+            zobrist_key : 0,
+        };
+        board.refresh_zobrist_key();
+        board
     }
     /// Creates an empty board with no pieces.
     ///
@@ -186,7 +243,7 @@ impl Board {
     ///
     /// A `Board` with no pieces, no castling rights, and white as the active player.
     pub fn empty_new() -> Board {
-        Board{
+        let mut board = Board{
             castling_rights : CastlingRights::NONE_CAN_CASTLE,
             en_passant_square : None,
             half_move_clock : 0,
@@ -194,7 +251,10 @@ impl Board {
             active_player : Color::White,
             piece_locations : 0,
             data : [0; 12],
-        }
+            zobrist_key : 0,
+        };
+        board.refresh_zobrist_key();
+        board
     }
     pub fn clone(&self) -> Board {
         Board{
@@ -204,7 +264,8 @@ impl Board {
             full_move_number : self.full_move_number,
             active_player : self.active_player,
             piece_locations : self.piece_locations,
-            data : self.data
+            data : self.data,
+            zobrist_key : self.zobrist_key,
         }
     }
 
@@ -227,9 +288,16 @@ impl Board {
     /// * `piece` - The piece to update.
     /// * `bitboard` - The new bitboard for the piece.
     pub fn set_bitboard(&mut self, piece: Piece, bitboard: u64) {
-        let bitboard_change = self.data[piece as usize] ^ bitboard;
+        let mut bitboard_change = self.data[piece as usize] ^ bitboard;
+        self.toggle_en_passant_key_for(piece);
         self.data[piece as usize] = bitboard;
         self.piece_locations ^= bitboard_change;
+        while bitboard_change != 0 {
+            let square = bitboard_change.trailing_zeros() as Square;
+            self.zobrist_key ^= ZOBRIST_KEYS.piece(piece, square);
+            bitboard_change &= !(1 << square);
+        }
+        self.toggle_en_passant_key_for(piece);
     }
     /// Returns the total number of pieces on the board.
     ///
@@ -401,11 +469,14 @@ impl Board {
     ///
     /// `Ok(())` if removal succeeds, otherwise a `BoardError`.
     pub fn remove_piece_at(&mut self, square: Square, piece: Piece) -> Result<(), BoardError>{
-        if self.piece_locations & (1 << square) == 0 {
+        if self.data[piece as usize] & (1 << square) == 0 {
             return Err(BoardError::PieceNotFound);
         }
+        self.toggle_en_passant_key_for(piece);
         self.data[piece as usize] &= !(1 << square);
         self.piece_locations &= !(1 << square);
+        self.zobrist_key ^= ZOBRIST_KEYS.piece(piece, square);
+        self.toggle_en_passant_key_for(piece);
         Ok(())
     }
     /// Adds a piece to the specified square.
@@ -422,8 +493,11 @@ impl Board {
         if self.piece_locations & (1 << square) != 0 {
             return Err(BoardError::SquareOccupied);
         }
+        self.toggle_en_passant_key_for(piece);
         self.data[piece as usize] |= 1 << square;
         self.piece_locations |= 1 << square;
+        self.zobrist_key ^= ZOBRIST_KEYS.piece(piece, square);
+        self.toggle_en_passant_key_for(piece);
         Ok(())
     }
 
@@ -500,113 +574,69 @@ impl Board {
     /// - `true` if the square is under attack.
     /// - `false` if the square is safe.
     pub fn is_threatened(&self, square: Square) -> bool {
-        let opponent = match self.get_piece_at(square) {
-            Some(piece) => piece.get_opponent_color(),
-            None => return false,
-        };
-
-        fn captures_straight(board: &Board, distance: u8, square: Square, opponent: Color) -> Option<bool> {
-            if board.piece_locations & (1 << square) == 0 {
-                return None;
-            }
-            if let Some(piece)= board.get_colored_piece_at(square, opponent) {match piece{
-                Piece::WhiteKing | Piece::BlackKing => {
-                    Some(distance==1)
-                }
-                Piece::WhiteQueen | Piece::BlackQueen | Piece::WhiteRook | Piece::BlackRook => {
-                    Some(true)
-                }
-                _ => Some(false)
-            }} else {
-                Some(false)
-            }
-        }
-        fn captures_diagonally(board: &Board, distance: u8, square: Square, opponent: Color, ascending_row: bool) -> Option<bool> {
-            if board.piece_locations & (1 << square) == 0 {
-                return None;
-            }
-            if let Some(piece)= board.get_colored_piece_at(square, opponent) {match piece{
-                Piece::WhiteQueen | Piece::BlackQueen | Piece::WhiteBishop | Piece::BlackBishop => {
-                    Some(true)
-                }
-                Piece::WhitePawn => {
-                    if distance == 1 && ascending_row == false {
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                }
-                Piece::BlackPawn => {
-                    if distance == 1 && ascending_row == true {
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                }
-                Piece::WhiteKing | Piece::BlackKing => {
-                    if distance == 1 {
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                }
-                _ => Some(false)
-            }} else {
-                Some(false)
-            }
+        match self.get_piece_at(square) {
+            Some(piece) => self.is_attacked_by(square, piece.get_opponent_color()),
+            None => false,
         }
+    }
 
-        //Check ranks and file
-        for i in 0..2 {
-            let ascending = i== 1;
-            let mut distance = 1;
-            for s in square.get_row_squares(ascending) {
-                let threatening_square = captures_straight(self, distance, s, opponent);
-                match threatening_square {
-                    Some(true) => { return true; }
-                    Some(false) => { break; }
-                    None => { distance += 1; }
-                }
-            }
-            distance = 1;
-            for s in square.get_col_squares(ascending) {
-                let threatening_square = captures_straight(self, distance, s, opponent);
-                match threatening_square {
-                    Some(true) => { return true; }
-                    Some(false) => { break; }
-                    None => { distance += 1; }
-                }
-            }
-        }
-        //Check diagonals
-        for i in 0..4 {
-            let ascending_row = i/2 == 0;
-            let ascending_col = i%2 == 0;
+    /// Checks if the given square, occupied or not, is attacked by any piece of `opponent`.
+    ///
+    /// # Parameters
+    /// - `square`: The board position to check.
+    /// - `opponent`: The color of the attacking pieces.
+    ///
+    /// # Returns
+    /// - `true` if the square is under attack.
+    /// - `false` if the square is safe.
+    pub fn is_attacked_by(&self, square: Square, opponent: Color) -> bool {
+        self.attackers_to(square, opponent, self.piece_locations) != 0
+    }
 
-            let mut distance = 1;
-            for diagonal_square in square.iter_diagonal(ascending_row, ascending_col) {
-                let threatening_square = captures_diagonally(self, distance, diagonal_square, opponent, ascending_row);
-                match threatening_square {
-                    Some(true) => { return true; }
-                    Some(false) => { break; }
-                    None => { distance += 1; }
-                }
-            }
-        }
-        //Check knights
-        let enemy_knight = opponent.get_knight();
-        for knight_square in square.iter_knight_offsets() {
-            if self.is_piece_at(knight_square, enemy_knight) {
-                return true;
-            }
-        }
+    /// Finds the pieces of `opponent` attacking the given square.
+    ///
+    /// # Parameters
+    /// - `square`: The board position to check.
+    /// - `opponent`: The color of the attacking pieces.
+    /// - `occupied`: The occupancy sliding pieces are blocked by, which may differ from the board's
+    ///   to see through pieces about to move.
+    ///
+    /// # Returns
+    /// - A bitboard of the attacking pieces.
+    pub fn attackers_to(&self, square: Square, opponent: Color, occupied: Bitboard) -> Bitboard {
+        let queens = self.data[opponent.get_queen().to_index()];
+        let straight = self.data[opponent.get_rook().to_index()] | queens;
+        let diagonal = self.data[opponent.get_bishop().to_index()] | queens;
 
-        false
+        // A pawn of the defending colour on `square` would capture exactly where attacking pawns stand
+        attacks::rook_attacks(square, occupied) & straight
+            | attacks::bishop_attacks(square, occupied) & diagonal
+            | attacks::knight_attacks(square) & self.data[opponent.get_knight().to_index()]
+            | attacks::pawn_attacks(opponent.toggle_color(), square) & self.data[opponent.get_pawn().to_index()]
+            | attacks::king_attacks(square) & self.data[opponent.get_king().to_index()]
+    }
+
+    /// Returns the squares occupied by pieces of the given color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - The color whose pieces are collected.
+    ///
+    /// # Returns
+    ///
+    /// A `Bitboard` of every square holding a piece of `color`.
+    pub fn get_color_bitboard(&self, color: Color) -> Bitboard {
+        Piece::iter_color_pieces(&color).fold(0, |bitboard, piece| bitboard | self.data[piece.to_index()])
+    }
+
+    /// Returns the bitboard of all occupied squares.
+    pub fn get_occupied_bitboard(&self) -> Bitboard {
+        self.piece_locations
     }
 
     /// Computes clear squares along a file starting from a given square.
     ///
-    /// Iterates through squares along the file in the specified direction until a piece is encountered.
+    /// Masks the rook attack table to the file ray in the specified direction, which ends on the first piece.
     ///
     /// # Arguments
     ///
@@ -617,18 +647,11 @@ impl Board {
     ///
     /// A bitboard representing clear squares along the file.
     pub fn sees_down_file(&self, square:Square, ascending: bool) -> Bitboard {
-        let mut vision_board : Bitboard = 0;
-        for s in square.get_row_squares(ascending) {
-            vision_board |= 1 << s;
-            if self.piece_locations & (1 << s) != 0 {
-                break;
-            }
-        }
-        vision_board
+        attacks::rook_attacks(square, self.piece_locations) & attacks::file_ray(square, ascending)
     }
     /// Computes clear squares along a rank starting from a given square.
     ///
-    /// Iterates through squares along the rank in the specified direction until a piece is encountered.
+    /// Masks the rook attack table to the rank ray in the specified direction, which ends on the first piece.
     ///
     /// # Arguments
     ///
@@ -639,18 +662,11 @@ impl Board {
     ///
     /// A bitboard representing clear squares along the rank.
     pub fn sees_down_rank(&self, square:Square, ascending: bool) -> Bitboard {
-        let mut vision_board : Bitboard = 0;
-        for s in square.get_col_squares(ascending) {
-            vision_board |= 1 << s;
-            if self.piece_locations & (1 << s) != 0 {
-                break;
-            };
-        }
-        vision_board
+        attacks::rook_attacks(square, self.piece_locations) & attacks::rank_ray(square, ascending)
     }
     /// Computes clear squares along a diagonal starting from a given square.
     ///
-    /// Iterates along the diagonal defined by `ascending_row` and `ascending_col` until a piece is encountered.
+    /// Masks the bishop attack table to the diagonal defined by `ascending_row` and `ascending_col`, which ends on the first piece.
     ///
     /// # Arguments
     ///
@@ -662,14 +678,7 @@ impl Board {
     ///
     /// A bitboard representing clear squares along the diagonal.
     pub fn sees_down_diagonal(&self, square:Square, ascending_row: bool, ascending_col: bool) -> Bitboard {
-        let mut vision_board : Bitboard = 0;
-        for diagonal_square in square.iter_diagonal(ascending_row, ascending_col) {
-            vision_board |= 1 << diagonal_square;
-            if self.piece_locations & (1 << diagonal_square) != 0 {
-                break;
-            }
-        }
-        vision_board
+        attacks::bishop_attacks(square, self.piece_locations) & attacks::diagonal_ray(square, ascending_row, ascending_col)
     }
     /// Computes the squares a knight can reach from the given square.
     ///
@@ -681,16 +690,12 @@ impl Board {
     ///
     /// A bitboard representing the knight's move possibilities.
     pub fn sees_like_knight(&self, square:Square) -> Bitboard {
-        let mut vision_board : Bitboard = 0;
-        for knight_square in square.iter_knight_offsets(){
-            vision_board |= 1 << knight_square;
-        }
-        vision_board
+        attacks::knight_attacks(square)
     }
 
     /// Computes clear moves along a file and returns potential capture information.
     ///
-    /// Iterates along the file until a piece is encountered. If a piece of the given color is found,
+    /// Follows the file until a piece is encountered. If a piece of the given color is found,
     /// returns that square as capturable.
     ///
     /// # Arguments
@@ -705,22 +710,11 @@ impl Board {
     /// - A bitboard of clear squares.
     /// - An optional square where a capturable piece is found.
     pub fn clear_n_capture_down_file(&self, square:Square, ascending: bool, color: Color) -> (Bitboard, Option<Square>) {
-        //Returns a bitboard of clear moves (without capture) and an Option of Square if a capturable piece is present.
-        let mut vision_board : Bitboard = 0;
-        for s in square.get_row_squares(ascending) {
-            if self.piece_locations & (1 << s) != 0 {
-                return match self.get_colored_piece_at(s, color) {
-                    Some(_) => (vision_board, Some(s)),
-                    None => (vision_board, None),
-                }
-            }
-            vision_board |= 1 << s;
-        }
-        (vision_board, None)
+        self.split_blocker(self.sees_down_file(square, ascending), color)
     }
     /// Computes clear moves along a rank and returns potential capture information.
     ///
-    /// Iterates along the rank until a piece is encountered. If a piece of the given color is found,
+    /// Follows the rank until a piece is encountered. If a piece of the given color is found,
     /// returns that square as capturable.
     ///
     /// # Arguments
@@ -735,21 +729,11 @@ impl Board {
     /// - A bitboard of clear squares.
     /// - An optional square where a capturable piece is found.
     pub fn clear_n_capture_down_rank(&self, square:Square, ascending: bool, color: Color) -> (Bitboard, Option<Square>) {
-        let mut vision_board : Bitboard = 0;
-        for s in square.get_col_squares(ascending) {
-            if self.piece_locations & (1 << s) != 0 {
-                return match self.get_colored_piece_at(s, color) {
-                    Some(_) => (vision_board, Some(s)),
-                    None => (vision_board, None),
-                }
-            }
-            vision_board |= 1 << s;
-        }
-        (vision_board, None)
+        self.split_blocker(self.sees_down_rank(square, ascending), color)
     }
     /// Computes clear moves along a diagonal and returns potential capture information.
     ///
-    /// Iterates along the diagonal defined by the given row and column directions until a piece is encountered.
+    /// Follows the diagonal defined by the given row and column directions until a piece is encountered.
     /// If a piece of the specified color is encountered, that square is returned as capturable.
     ///
     /// # Arguments
@@ -765,18 +749,17 @@ impl Board {
     /// - A bitboard of clear squares along the diagonal.
     /// - An optional square where a capturable piece is found.
     pub fn clear_n_capture_down_diagonal(&self, square:Square, ascending_row: bool, ascending_col: bool, color: Color) -> (Bitboard, Option<Square>) {
-        let mut vision_board : Bitboard = 0;
-        for diagonal_square in square.iter_diagonal(ascending_row, ascending_col) {
-            // println!("{}", diagonal_square.to_square_string());
-            if self.piece_locations & (1 << diagonal_square) != 0 {
-                return match self.get_colored_piece_at(diagonal_square, color) {
-                    Some(_) => (vision_board, Some(diagonal_square)),
-                    None => (vision_board, None),
-                }
-            }
-            vision_board |= 1 << diagonal_square;
+        self.split_blocker(self.sees_down_diagonal(square, ascending_row, ascending_col), color)
+    }
+    /// Splits a single ray of vision into its empty squares and the blocking piece, if that
+    /// piece has the given color.
+    fn split_blocker(&self, seen: Bitboard, color: Color) -> (Bitboard, Option<Square>) {
+        let blocker = seen & self.piece_locations;
+        if blocker == 0 {
+            return (seen, None);
         }
-        (vision_board, None)
+        let blocker_square = blocker.trailing_zeros() as Square;
+        (seen & !blocker, self.get_colored_piece_at(blocker_square, color).map(|_| blocker_square))
     }
 
     /// Retrieves all pieces present on the provided bitboard.
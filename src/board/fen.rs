@@ -0,0 +1,454 @@
+//! Forsyth–Edwards Notation (FEN) support for [`Board`].
+//!
+//! A FEN record describes a position with six space separated fields:
+//! piece placement, active player, castling rights, en passant square,
+//! half move clock and full move number.
+//!
+//! The two move counters may be omitted (as is common in EPD files), in which case they default
+//! to `0` and `1`.
+use std::fmt;
+
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Board, CastlingRights, CastlingRightsExt};
+
+/// FEN record of the standard starting position.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The six fields of a FEN record, in the order they appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenField {
+    PiecePlacement,
+    ActivePlayer,
+    CastlingRights,
+    EnPassantSquare,
+    HalfMoveClock,
+    FullMoveNumber,
+}
+
+/// Errors that may occur while parsing a FEN record.
+///
+/// Every variant names the [`FenField`] it was raised in so that callers can point users at the
+/// offending part of the record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// The record ended before the given field.
+    MissingField(FenField),
+    /// The record holds more than six fields, contains the first unexpected one.
+    UnexpectedField(String),
+    /// A character that is not allowed in the field. `index` is the character offset of
+    /// `character` inside the full record.
+    InvalidCharacter {
+        field: FenField,
+        character: char,
+        index: usize,
+    },
+    /// A rank of the piece placement field doesn't describe exactly 8 squares.
+    /// `rank` is the 1-based rank number (8 is the first rank of the record).
+    InvalidRankLength { rank: u8, squares: u8 },
+    /// The piece placement field doesn't describe exactly 8 ranks.
+    InvalidRankCount(u8),
+    /// A move counter that doesn't fit in the board's counters.
+    NumberOutOfRange { field: FenField, value: String },
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            FenField::PiecePlacement => "piece placement",
+            FenField::ActivePlayer => "active player",
+            FenField::CastlingRights => "castling rights",
+            FenField::EnPassantSquare => "en passant square",
+            FenField::HalfMoveClock => "half move clock",
+            FenField::FullMoveNumber => "full move number",
+        })
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {} field", field),
+            FenError::UnexpectedField(value) => write!(f, "FEN has an unexpected seventh field \"{}\"", value),
+            FenError::InvalidCharacter { field, character, index } => write!(
+                f,
+                "Invalid character '{}' in the {} field at position {}",
+                character, field, index
+            ),
+            FenError::InvalidRankLength { rank, squares } => write!(
+                f,
+                "Rank {} of the piece placement field describes {} squares instead of 8",
+                rank, squares
+            ),
+            FenError::InvalidRankCount(count) => write!(
+                f,
+                "The piece placement field describes {} ranks instead of 8",
+                count
+            ),
+            FenError::NumberOutOfRange { field, value } => write!(
+                f,
+                "The {} field \"{}\" is not a valid number",
+                field, value
+            ),
+        }
+    }
+}
+
+/// Splits a record into whitespace separated fields, keeping the character offset of each field.
+fn split_fields(fen: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::with_capacity(6);
+    let mut start: Option<(usize, usize)> = None; // (char index, byte index)
+
+    for (char_index, (byte_index, c)) in fen.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (true, Some((field_char, field_byte))) => {
+                fields.push((field_char, &fen[field_byte..byte_index]));
+                start = None;
+            }
+            (false, None) => start = Some((char_index, byte_index)),
+            _ => {}
+        }
+    }
+    if let Some((field_char, field_byte)) = start {
+        fields.push((field_char, &fen[field_byte..]));
+    }
+    fields
+}
+
+impl Board {
+    /// Creates a board from a FEN record.
+    ///
+    /// # Arguments
+    ///
+    /// * `fen` - The FEN record, e.g. `"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"`.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Board`, or a `FenError` naming the field and character that could not be parsed.
+    ///
+    /// The position is not checked for legality, see [`Board::validate`] for that.
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields = split_fields(fen);
+        let mut board = Board::empty_new();
+
+        let field = |index: usize, field: FenField| -> Result<(usize, &str), FenError> {
+            fields.get(index).copied().ok_or(FenError::MissingField(field))
+        };
+
+        let (offset, placement) = field(0, FenField::PiecePlacement)?;
+        board.parse_piece_placement(placement, offset)?;
+
+        let (offset, active_player) = field(1, FenField::ActivePlayer)?;
+        board.active_player = match active_player {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(invalid_character(FenField::ActivePlayer, active_player, offset, |c| c == 'w' || c == 'b')),
+        };
+
+        let (offset, castling_rights) = field(2, FenField::CastlingRights)?;
+        board.castling_rights = parse_castling_rights(castling_rights, offset)?;
+
+        let (offset, en_passant) = field(3, FenField::EnPassantSquare)?;
+        board.en_passant_square = parse_en_passant_square(en_passant, offset)?;
+
+        if let Some(&(_, half_move_clock)) = fields.get(4) {
+            board.half_move_clock = parse_counter(half_move_clock, FenField::HalfMoveClock)?;
+        }
+        if let Some(&(_, full_move_number)) = fields.get(5) {
+            board.full_move_number = parse_counter(full_move_number, FenField::FullMoveNumber)?;
+            if board.full_move_number == 0 {
+                return Err(FenError::NumberOutOfRange {
+                    field: FenField::FullMoveNumber,
+                    value: full_move_number.to_string(),
+                });
+            }
+        }
+        if let Some(&(_, extra)) = fields.get(6) {
+            return Err(FenError::UnexpectedField(extra.to_string()));
+        }
+
+        board.refresh_zobrist_key();
+        Ok(board)
+    }
+
+    /// Fills the bitboards from the piece placement field.
+    ///
+    /// # Arguments
+    ///
+    /// * `placement` - The piece placement field.
+    /// * `offset` - Character offset of the field inside the record, used for error reporting.
+    fn parse_piece_placement(&mut self, placement: &str, offset: usize) -> Result<(), FenError> {
+        let mut row: u8 = 0;
+        let mut col: u8 = 0;
+
+        for (index, c) in placement.chars().enumerate() {
+            let invalid = FenError::InvalidCharacter {
+                field: FenField::PiecePlacement,
+                character: c,
+                index: offset + index,
+            };
+            match c {
+                '/' => {
+                    if col != Square::COLS {
+                        return Err(FenError::InvalidRankLength { rank: Square::ROWS - row, squares: col });
+                    }
+                    row += 1;
+                    col = 0;
+                    if row >= Square::ROWS {
+                        return Err(FenError::InvalidRankCount(row + 1));
+                    }
+                }
+                '1'..='8' => {
+                    col += c as u8 - b'0';
+                    if col > Square::COLS {
+                        return Err(FenError::InvalidRankLength { rank: Square::ROWS - row, squares: col });
+                    }
+                }
+                _ => {
+                    let piece = Piece::from_char(c).ok_or(invalid)?;
+                    if col >= Square::COLS {
+                        return Err(FenError::InvalidRankLength { rank: Square::ROWS - row, squares: col + 1 });
+                    }
+                    let _ = self.add_piece_at(Square::new(row, col), piece);
+                    col += 1;
+                }
+            }
+        }
+
+        if row != Square::ROWS - 1 {
+            return Err(FenError::InvalidRankCount(row + 1));
+        }
+        if col != Square::COLS {
+            return Err(FenError::InvalidRankLength { rank: Square::ROWS - row, squares: col });
+        }
+        Ok(())
+    }
+
+    /// Renders the board as a FEN record.
+    ///
+    /// # Returns
+    ///
+    /// The six field FEN record describing the board.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::with_capacity(90);
+
+        for row in Square::iter_ranks() {
+            let mut empty_squares = 0;
+            for col in 0..Square::COLS {
+                match self.get_piece_at(Square::new(row, col)) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            fen.push((b'0' + empty_squares) as char);
+                            empty_squares = 0;
+                        }
+                        fen.push(piece.to_char());
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                fen.push((b'0' + empty_squares) as char);
+            }
+            if row < Square::ROWS - 1 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.active_player {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        if self.castling_rights == CastlingRights::NONE_CAN_CASTLE {
+            fen.push('-');
+        } else {
+            if self.castling_rights.can_castle_white_king_side() { fen.push('K'); }
+            if self.castling_rights.can_castle_white_queen_side() { fen.push('Q'); }
+            if self.castling_rights.can_castle_black_king_side() { fen.push('k'); }
+            if self.castling_rights.can_castle_black_queen_side() { fen.push('q'); }
+        }
+
+        fen.push(' ');
+        match self.en_passant_square {
+            Some(square) => fen.push_str(square.to_square_str()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.half_move_clock, self.full_move_number));
+        fen
+    }
+}
+
+/// Builds an `InvalidCharacter` error for the first character of `value` rejected by `is_valid`.
+///
+/// Falls back to the second character, the surplus one, when every character is valid on its own (e.g. `"ww"`).
+fn invalid_character(field: FenField, value: &str, offset: usize, is_valid: impl Fn(char) -> bool) -> FenError {
+    let (index, character) = value
+        .chars()
+        .enumerate()
+        .find(|&(_, c)| !is_valid(c))
+        .unwrap_or_else(|| {
+            // Every character is valid on its own, so the second one is the surplus
+            value.chars().enumerate().nth(1).unwrap_or((0, ' '))
+        });
+    FenError::InvalidCharacter {
+        field,
+        character,
+        index: offset + index,
+    }
+}
+
+fn parse_castling_rights(castling_rights: &str, offset: usize) -> Result<CastlingRights, FenError> {
+    if castling_rights == "-" {
+        return Ok(CastlingRights::NONE_CAN_CASTLE);
+    }
+
+    let mut rights = CastlingRights::NONE_CAN_CASTLE;
+    for (index, c) in castling_rights.chars().enumerate() {
+        let right = match c {
+            'K' => CastlingRights::WHITE_KING_MASK & CastlingRights::KINGSIDE_MASK,
+            'Q' => CastlingRights::WHITE_KING_MASK & CastlingRights::QUEENSIDE_MASK,
+            'k' => CastlingRights::BLACK_KING_MASK & CastlingRights::KINGSIDE_MASK,
+            'q' => CastlingRights::BLACK_KING_MASK & CastlingRights::QUEENSIDE_MASK,
+            _ => 0,
+        };
+        // Unknown characters and repeated rights are both rejected
+        if right == 0 || rights & right != 0 {
+            return Err(FenError::InvalidCharacter {
+                field: FenField::CastlingRights,
+                character: c,
+                index: offset + index,
+            });
+        }
+        rights |= right;
+    }
+    Ok(rights)
+}
+
+fn parse_en_passant_square(en_passant: &str, offset: usize) -> Result<Option<Square>, FenError> {
+    if en_passant == "-" {
+        return Ok(None);
+    }
+
+    let mut chars = en_passant.chars();
+    let file = chars.next();
+    let rank = chars.next();
+    let invalid = |index: usize, character: char| FenError::InvalidCharacter {
+        field: FenField::EnPassantSquare,
+        character,
+        index: offset + index,
+    };
+
+    let col = match file {
+        Some(c @ 'a'..='h') => c as u8 - b'a',
+        Some(c) => return Err(invalid(0, c)),
+        None => return Err(FenError::MissingField(FenField::EnPassantSquare)),
+    };
+    let row = match rank {
+        Some(c @ '1'..='8') => Square::ROWS - (c as u8 - b'0'),
+        Some(c) => return Err(invalid(1, c)),
+        None => return Err(FenError::MissingField(FenField::EnPassantSquare)),
+    };
+    if let Some(c) = chars.next() {
+        return Err(invalid(2, c));
+    }
+
+    Ok(Some(Square::new(row, col)))
+}
+
+fn parse_counter(value: &str, field: FenField) -> Result<u8, FenError> {
+    value.parse::<u8>().map_err(|_| FenError::NumberOutOfRange {
+        field,
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn starting_fen_matches_std_new() {
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+        let std_board = Board::std_new();
+
+        for piece in Piece::iter() {
+            assert_eq!(board.get_bitboard(piece), std_board.get_bitboard(piece));
+        }
+        assert_eq!(board.castling_rights, std_board.castling_rights);
+        assert_eq!(board.active_player, std_board.active_player);
+        assert_eq!(std_board.to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn round_trips_all_fields() {
+        let fens = [
+            KIWIPETE,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 57",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 99 200",
+        ];
+        for fen in fens {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn parses_counters_and_en_passant() {
+        let board = Board::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        assert_eq!(board.en_passant_square, Some(Square::D6));
+        assert_eq!(board.half_move_clock, 0);
+        assert_eq!(board.full_move_number, 3);
+        assert!(board.is_piece_at(Square::E5, Piece::WhitePawn));
+    }
+
+    #[test]
+    fn move_counters_are_optional() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!(board.active_player, Color::Black);
+        assert_eq!(board.half_move_clock, 0);
+        assert_eq!(board.full_move_number, 1);
+    }
+
+    #[test]
+    fn reports_field_and_character() {
+        assert_eq!(
+            Board::from_fen("rnbqkbnr/ppppXppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").err(),
+            Some(FenError::InvalidCharacter { field: FenField::PiecePlacement, character: 'X', index: 13 })
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").err(),
+            Some(FenError::InvalidCharacter { field: FenField::ActivePlayer, character: 'x', index: 16 })
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w KQKq - 0 1").err(),
+            Some(FenError::InvalidCharacter { field: FenField::CastlingRights, character: 'K', index: 20 })
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - e9 0 1").err(),
+            Some(FenError::InvalidCharacter { field: FenField::EnPassantSquare, character: '9', index: 21 })
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - - x 1").err(),
+            Some(FenError::NumberOutOfRange { field: FenField::HalfMoveClock, value: "x".to_string() })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_placement() {
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::InvalidRankCount(7)));
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/ppppppppp w - - 0 1").err(),
+            Some(FenError::InvalidRankLength { rank: 1, squares: 9 })
+        );
+        assert_eq!(
+            Board::from_fen("8/7/8/8/8/8/8/8 w - - 0 1").err(),
+            Some(FenError::InvalidRankLength { rank: 7, squares: 7 })
+        );
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w").err(), Some(FenError::MissingField(FenField::CastlingRights)));
+    }
+}
@@ -186,6 +186,17 @@ impl Piece {
             Piece::WhiteKing => 'K', Piece::BlackKing => 'k',
         }
     }
+    pub fn from_char(c: char) -> Option<Piece> {
+        match c {
+            'P' => Some(Piece::WhitePawn), 'p' => Some(Piece::BlackPawn),
+            'R' => Some(Piece::WhiteRook), 'r' => Some(Piece::BlackRook),
+            'N' => Some(Piece::WhiteKnight), 'n' => Some(Piece::BlackKnight),
+            'B' => Some(Piece::WhiteBishop), 'b' => Some(Piece::BlackBishop),
+            'Q' => Some(Piece::WhiteQueen), 'q' => Some(Piece::BlackQueen),
+            'K' => Some(Piece::WhiteKing), 'k' => Some(Piece::BlackKing),
+            _ => None,
+        }
+    }
     pub fn to_str(&self) -> Option<&str> {
         match self {
             Piece::WhiteRook => Some("R"), Piece::BlackRook => Some("r"),
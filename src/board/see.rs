@@ -0,0 +1,177 @@
+//! Static exchange evaluation.
+//!
+//! Before searching a capture it helps to know whether it wins material at all. [`Board::see`]
+//! plays out every capture on the target square, each side recapturing with its least valuable
+//! attacker, and lets either side stop capturing when going on would lose more. Pieces moving
+//! onto the square uncover the sliders behind them, which join the exchange in turn.
+//!
+//! Pins, checks and promotions by recapturing pawns are left out, so the result is an estimate,
+//! but a cheap one.
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Bitboard, Board};
+use crate::chess_moves::ChessMove;
+
+/// Captures on one square can't outnumber the pieces on the board.
+const MAX_EXCHANGES: usize = 32;
+
+/// Value of a piece in exchanges, in centipawns. A king is never exchanged, so it counts for
+/// nothing.
+pub fn piece_value(piece: Piece) -> i32 {
+    if piece.is_pawn() {
+        100
+    } else if piece.is_knight() {
+        320
+    } else if piece.is_bishop() {
+        330
+    } else if piece.is_rook() {
+        500
+    } else if piece.is_queen() {
+        900
+    } else {
+        0
+    }
+}
+
+impl Board {
+    /// Estimates the material the side to move wins with `chess_move` once every capture on
+    /// its target square has been played out.
+    ///
+    /// # Returns
+    ///
+    /// The balance in centipawns, negative if the move loses material. Quiet moves score what
+    /// the opponent would win by capturing the moved piece, or zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // The pawn on e5 is defended, taking it with the queen loses the queen for a pawn
+    /// let board = Board::from_fen("4k3/8/3p4/4p3/8/8/4Q3/4K3 w - - 0 1").unwrap();
+    /// let mut captures = MoveList::new();
+    /// ChessMove::generate_captures(&board, &mut captures);
+    /// assert_eq!(board.see(captures.as_slice()[0]), 100 - 900);
+    /// ```
+    pub fn see(&self, chess_move: ChessMove) -> i32 {
+        let origin = chess_move.get_origin();
+        let target = chess_move.get_target();
+        let meta_data = chess_move.get_meta_data();
+        let Some(mover) = self.get_piece_at(origin) else {
+            return 0;
+        };
+
+        let mut occupied = self.piece_locations & !(1 << origin);
+        let mut gains = [0; MAX_EXCHANGES];
+        gains[0] = match self.get_piece_at(target) {
+            Some(piece) => piece_value(piece),
+            None if meta_data.is_en_passant() => {
+                // The captured pawn stands beside the moving one
+                occupied &= !(1 << Square::new(origin.get_row(), target.get_col()));
+                piece_value(mover)
+            }
+            None => 0,
+        };
+        // The piece standing on the target square, next to be captured
+        let mut on_target = piece_value(mover);
+        if meta_data.is_promotion() {
+            let promoted = piece_value(chess_move.get_piece());
+            gains[0] += promoted - on_target;
+            on_target = promoted;
+        }
+
+        let mut side = mover.get_opponent_color();
+        let mut exchanges = 0;
+        while exchanges + 1 < MAX_EXCHANGES {
+            let attackers = self.attackers_to(target, side, occupied) & occupied;
+            let Some((square, piece)) = self.least_valuable_attacker(attackers, side) else {
+                break;
+            };
+            // A king can't capture onto a defended square
+            if piece.is_king() && self.attackers_to(target, side.toggle_color(), occupied) & occupied != 0 {
+                break;
+            }
+            exchanges += 1;
+            gains[exchanges] = on_target - gains[exchanges - 1];
+            on_target = piece_value(piece);
+            occupied &= !(1 << square);
+            side = side.toggle_color();
+        }
+
+        // Each side only captures if that beats stopping the exchange before it
+        while exchanges > 0 {
+            gains[exchanges - 1] = -(-gains[exchanges - 1]).max(gains[exchanges]);
+            exchanges -= 1;
+        }
+        gains[0]
+    }
+
+    fn least_valuable_attacker(&self, attackers: Bitboard, color: Color) -> Option<(Square, Piece)> {
+        let pieces = [
+            color.get_pawn(),
+            color.get_knight(),
+            color.get_bishop(),
+            color.get_rook(),
+            color.get_queen(),
+            color.get_king(),
+        ];
+        pieces.into_iter().find_map(|piece| {
+            let bitboard = attackers & self.data[piece.to_index()];
+            (bitboard != 0).then(|| (bitboard.trailing_zeros() as Square, piece))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::square::{Square, SquareExt};
+    use crate::board::Board;
+    use crate::chess_moves::{ChessMove, MoveList};
+
+    fn see(fen: &str, origin: &str, target: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(&board, &mut moves);
+        let chess_move = moves
+            .iter()
+            .find(|chess_move| {
+                Square::SQUARES[chess_move.get_origin() as usize] == origin
+                    && Square::SQUARES[chess_move.get_target() as usize] == target
+                    && (!chess_move.get_meta_data().is_promotion() || chess_move.get_piece().is_queen())
+            })
+            .copied()
+            .unwrap_or_else(|| panic!("{}{} is not legal in {}", origin, target, fen));
+        board.see(chess_move)
+    }
+
+    #[test]
+    fn plays_out_exchanges() {
+        // Undefended pawn
+        assert_eq!(see("4k3/8/8/4p3/8/8/4R3/4K3 w - - 0 1", "e2", "e5"), 100);
+        // Defended pawn, the rook is lost for it
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/4R3/4K3 w - - 0 1", "e2", "e5"), 100 - 500);
+        // Pawn takes a defended knight
+        assert_eq!(see("4k3/8/3b4/4n3/3P4/8/8/4K3 w - - 0 1", "d4", "e5"), 320 - 100);
+        // Knight takes a pawn defended by a knight, backed by a rook: N x P, N x N, R x N
+        assert_eq!(see("4k3/3n4/8/4p3/8/3N4/8/4RK2 w - - 0 1", "d3", "e5"), 100);
+        // Quiet move onto a square the opponent's pawn covers
+        assert_eq!(see("4k3/8/3p4/8/8/8/8/2B1K3 w - - 0 1", "c1", "e3"), 0);
+        assert_eq!(see("4k3/8/3p4/8/8/8/8/2B1K3 w - - 0 1", "c1", "f4"), 0);
+        assert_eq!(see("4k3/8/8/3p4/8/8/8/2B1K3 w - - 0 1", "c1", "e3"), 0);
+        assert_eq!(see("4k3/8/8/3p4/8/8/8/1B2K3 w - - 0 1", "b1", "e4"), -330);
+    }
+
+    #[test]
+    fn uncovers_sliders_and_spares_kings() {
+        // The rook behind the first one recaptures: R x P, R x R, R x R
+        assert_eq!(see("4k3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2", "d5"), 100);
+        // Then the queen behind the black rook takes the last one, so white stops after R x P
+        assert_eq!(see("3qk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2", "d5"), 100 - 500);
+        // The king only recaptures an undefended piece
+        assert_eq!(see("8/8/8/3k4/4p3/8/2B5/K7 w - - 0 1", "c2", "e4"), 100 - 330);
+        assert_eq!(see("8/8/8/3k4/4p3/8/2B5/K3R3 w - - 0 1", "c2", "e4"), 100);
+        // En passant
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6"), 100);
+        // Promoting where the queen is taken
+        assert_eq!(see("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1", "c7", "c8"), -100);
+        assert_eq!(see("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1", "c7", "d8"), 500 - 100);
+    }
+}
@@ -0,0 +1,200 @@
+//! Legality checks for arbitrary positions.
+//!
+//! Positions built with [`Board::from_fen`], [`Board::add_piece_at`] or [`Board::set_bitboard`]
+//! may describe positions that can never arise in a game. Move generation assumes a reachable
+//! position, so such boards should be rejected with [`Board::validate`] first.
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Bitboard, BitboardExt, Board, BoardError, CastlingRightsExt};
+use crate::rules::CastleType;
+
+impl Board {
+    /// Checks that the position could occur in a legal game.
+    ///
+    /// # Returns
+    ///
+    /// Every `BoardError` found on the board, or an empty vector if the position is legal.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let board = Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").unwrap();
+    /// assert!(board.validate().contains(&BoardError::MissingKing(Color::White)));
+    /// ```
+    pub fn validate(&self) -> Vec<BoardError> {
+        let mut errors: Vec<BoardError> = Vec::new();
+
+        self.validate_occupancy(&mut errors);
+        for color in [Color::White, Color::Black] {
+            self.validate_material(color, &mut errors);
+            self.validate_castling_rights(color, &mut errors);
+        }
+        self.validate_pawn_ranks(&mut errors);
+        self.validate_en_passant_square(&mut errors);
+
+        // Check detection assumes one king per side and one piece per square
+        if errors.is_empty() {
+            let opponent = self.active_player.toggle_color();
+            if self.is_in_check(opponent) {
+                errors.push(BoardError::OpponentInCheck(opponent));
+            }
+        }
+
+        errors
+    }
+
+    /// Reports squares claimed by more than one bitboard.
+    fn validate_occupancy(&self, errors: &mut Vec<BoardError>) {
+        let mut seen: Bitboard = 0;
+        let mut overlapping: Bitboard = 0;
+        for piece in Piece::iter() {
+            let bitboard = self.data[piece.to_index()];
+            overlapping |= seen & bitboard;
+            seen |= bitboard;
+        }
+        while overlapping != 0 {
+            let square = overlapping.trailing_zeros() as Square;
+            errors.push(BoardError::OverlappingPieces(square));
+            overlapping &= !(1 << square);
+        }
+    }
+
+    /// Checks king, pawn and total piece counts for one side.
+    fn validate_material(&self, color: Color, errors: &mut Vec<BoardError>) {
+        match self.data[color.get_king().to_index()].count_ones() {
+            0 => errors.push(BoardError::MissingKing(color)),
+            1 => {}
+            _ => errors.push(BoardError::TooManyKings(color)),
+        }
+        if self.data[color.get_pawn().to_index()].count_ones() > 8 {
+            errors.push(BoardError::TooManyPawns(color));
+        }
+        let piece_count: u32 = Piece::iter_color_pieces(&color)
+            .map(|piece| self.data[piece.to_index()].count_ones())
+            .sum();
+        if piece_count > 16 {
+            errors.push(BoardError::TooManyPieces(color));
+        }
+    }
+
+    fn validate_pawn_ranks(&self, errors: &mut Vec<BoardError>) {
+        let back_ranks = Bitboard::get_bitboard_from_row(0) | Bitboard::get_bitboard_from_row(Square::ROWS - 1);
+        let mut misplaced = (self.data[Piece::WhitePawn.to_index()] | self.data[Piece::BlackPawn.to_index()]) & back_ranks;
+        while misplaced != 0 {
+            let square = misplaced.trailing_zeros() as Square;
+            errors.push(BoardError::PawnOnBackRank(square));
+            misplaced &= !(1 << square);
+        }
+    }
+
+    /// A castling right requires the king and the matching rook on their starting squares.
+    fn validate_castling_rights(&self, color: Color, errors: &mut Vec<BoardError>) {
+        let back_rank = color.get_back_rank_row();
+        let king_home = self.is_piece_at(color.king_starting_square(), color.get_king());
+
+        for (castle_type, rook_col) in [(CastleType::KingSide, Square::COLS - 1), (CastleType::QueenSide, 0)] {
+            if !self.castling_rights.can_castle(color, castle_type == CastleType::KingSide) {
+                continue;
+            }
+            let rook_home = self.is_piece_at(Square::new(back_rank, rook_col), color.get_rook());
+            if !king_home || !rook_home {
+                errors.push(BoardError::InvalidCastlingRights(color, castle_type));
+            }
+        }
+    }
+
+    /// The en passant square must be the empty square a pawn of the side that just moved skipped over.
+    fn validate_en_passant_square(&self, errors: &mut Vec<BoardError>) {
+        let square = match self.en_passant_square {
+            Some(square) => square,
+            None => return,
+        };
+        if square >= Square::MAX_SQUARES {
+            errors.push(BoardError::InvalidEnPassantSquare(square));
+            return;
+        }
+
+        // The pawn that moved belongs to the side that is not to move
+        let mover = self.active_player.toggle_color();
+        let expected_row = mover.get_pawn_starting_row() + mover.get_pawn_row_offset();
+        let valid = square.get_row() == expected_row
+            && !self.is_square_occupied(square)
+            && !self.is_square_occupied(square - mover.get_pawn_direction())
+            && self.is_piece_at(square + mover.get_pawn_direction(), mover.get_pawn());
+
+        if !valid {
+            errors.push(BoardError::InvalidEnPassantSquare(square));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(fen: &str) -> Vec<BoardError> {
+        Board::from_fen(fen).unwrap().validate()
+    }
+
+    #[test]
+    fn legal_positions_have_no_errors() {
+        assert!(Board::std_new().validate().is_empty());
+        assert!(errors("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").is_empty());
+        assert!(errors("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").is_empty());
+    }
+
+    #[test]
+    fn detects_king_errors() {
+        let found = errors("8/8/8/8/8/8/8/K1K5 w - - 0 1");
+        assert!(found.contains(&BoardError::TooManyKings(Color::White)));
+        assert!(found.contains(&BoardError::MissingKing(Color::Black)));
+    }
+
+    #[test]
+    fn detects_pawns_on_back_rank() {
+        let found = errors("P3k3/8/8/8/8/8/8/4K2p w - - 0 1");
+        assert_eq!(found, vec![BoardError::PawnOnBackRank(Square::A8), BoardError::PawnOnBackRank(Square::H1)]);
+    }
+
+    #[test]
+    fn detects_castling_rights_mismatch() {
+        let found = errors("r3k2r/8/8/8/8/8/8/R4K1R w KQkq - 0 1");
+        assert_eq!(found, vec![
+            BoardError::InvalidCastlingRights(Color::White, CastleType::KingSide),
+            BoardError::InvalidCastlingRights(Color::White, CastleType::QueenSide),
+        ]);
+        let found = errors("r3k3/8/8/8/8/8/8/4K3 b q - 0 1");
+        assert!(found.is_empty());
+        let found = errors("r3k3/8/8/8/8/8/8/4K3 b k - 0 1");
+        assert_eq!(found, vec![BoardError::InvalidCastlingRights(Color::Black, CastleType::KingSide)]);
+    }
+
+    #[test]
+    fn detects_impossible_en_passant_square() {
+        // No black pawn in front of d6
+        let found = errors("rnbqkbnr/pppppppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3");
+        assert_eq!(found, vec![BoardError::InvalidEnPassantSquare(Square::D6)]);
+        // Wrong rank for the side to move
+        let found = errors("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1");
+        assert_eq!(found, vec![BoardError::InvalidEnPassantSquare(Square::E3)]);
+    }
+
+    #[test]
+    fn detects_side_not_to_move_in_check() {
+        // White to move while the black king is attacked by the rook
+        let found = errors("4k3/8/8/8/8/8/8/K3R3 w - - 0 1");
+        assert_eq!(found, vec![BoardError::OpponentInCheck(Color::Black)]);
+        // Pawn checks are seen from both sides
+        let found = errors("8/8/8/8/8/5k2/4P3/K7 w - - 0 1");
+        assert_eq!(found, vec![BoardError::OpponentInCheck(Color::Black)]);
+        let found = errors("k7/3p4/4K3/8/8/8/8/8 b - - 0 1");
+        assert_eq!(found, vec![BoardError::OpponentInCheck(Color::White)]);
+    }
+
+    #[test]
+    fn detects_overlapping_pieces() {
+        let mut board = Board::std_new();
+        board.set_bitboard(Piece::WhiteQueen, board.get_bitboard(Piece::WhiteQueen) | (1 << Square::E2));
+        assert!(board.validate().contains(&BoardError::OverlappingPieces(Square::E2)));
+    }
+}
@@ -0,0 +1,264 @@
+//! Zobrist hashing for [`Board`].
+//!
+//! Every (piece, square) pair, the side to move, each castling rights combination and each
+//! en passant file is assigned a fixed pseudo random 64-bit key. A position's key is the XOR of
+//! the keys of everything present in it, which lets [`Board`] update its key incrementally as
+//! pieces are added and removed.
+//!
+//! The en passant file only contributes to the key while a pawn of the side to move stands next
+//! to the pawn that just advanced two squares, so positions that only differ by an en passant
+//! square that can't be used hash the same.
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Bitboard, Board, CastlingRights};
+
+/// 64-bit position identity.
+pub type ZobristKey = u64;
+
+pub struct ZobristKeys {
+    pieces: [[ZobristKey; 64]; 12],
+    black_to_move: ZobristKey,
+    castling_rights: [ZobristKey; 16],
+    en_passant_files: [ZobristKey; 8],
+}
+
+/// SplitMix64 step, used to fill the key tables at compile time.
+const fn split_mix(state: u64) -> (u64, ZobristKey) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+impl ZobristKeys {
+    const SEED: u64 = 0x5275_7374_7943_6873; // "RustyChs"
+
+    const fn generate() -> ZobristKeys {
+        let mut state = ZobristKeys::SEED;
+        let mut key;
+
+        let mut pieces = [[0; 64]; 12];
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                (state, key) = split_mix(state);
+                pieces[piece][square] = key;
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        (state, key) = split_mix(state);
+        let black_to_move = key;
+
+        // Each right gets its own key, combinations are the XOR of their rights
+        let mut rights = [0; 4];
+        let mut index = 0;
+        while index < 4 {
+            (state, key) = split_mix(state);
+            rights[index] = key;
+            index += 1;
+        }
+        let mut castling_rights = [0; 16];
+        let mut combination = 0;
+        while combination < 16 {
+            let mut bit = 0;
+            while bit < 4 {
+                if combination & (1 << bit) != 0 {
+                    castling_rights[combination] ^= rights[bit];
+                }
+                bit += 1;
+            }
+            combination += 1;
+        }
+
+        let mut en_passant_files = [0; 8];
+        let mut file = 0;
+        while file < 8 {
+            (state, key) = split_mix(state);
+            en_passant_files[file] = key;
+            file += 1;
+        }
+
+        ZobristKeys {
+            pieces,
+            black_to_move,
+            castling_rights,
+            en_passant_files,
+        }
+    }
+
+    #[inline]
+    pub fn piece(&self, piece: Piece, square: Square) -> ZobristKey {
+        self.pieces[piece.to_index()][square as usize]
+    }
+    #[inline]
+    pub fn side(&self, color: Color) -> ZobristKey {
+        match color {
+            Color::White => 0,
+            Color::Black => self.black_to_move,
+        }
+    }
+    #[inline]
+    pub fn castling_rights(&self, castling_rights: CastlingRights) -> ZobristKey {
+        self.castling_rights[(castling_rights & 0x0F) as usize]
+    }
+    #[inline]
+    pub fn en_passant_file(&self, square: Square) -> ZobristKey {
+        self.en_passant_files[square.get_col() as usize]
+    }
+}
+
+pub static ZOBRIST_KEYS: ZobristKeys = ZobristKeys::generate();
+
+impl Board {
+    /// Returns the incrementally maintained Zobrist key of the position.
+    #[inline]
+    pub fn zobrist_key(&self) -> ZobristKey {
+        self.zobrist_key
+    }
+
+    /// Computes the Zobrist key of the position from scratch.
+    ///
+    /// # Returns
+    ///
+    /// The key, which should always equal [`Board::zobrist_key`].
+    pub fn compute_zobrist_key(&self) -> ZobristKey {
+        let mut key: ZobristKey = 0;
+        for piece in Piece::iter() {
+            let mut bitboard = self.data[piece.to_index()];
+            while bitboard != 0 {
+                let square = bitboard.trailing_zeros() as Square;
+                key ^= ZOBRIST_KEYS.piece(piece, square);
+                bitboard &= !(1 << square);
+            }
+        }
+        key ^= ZOBRIST_KEYS.side(self.active_player);
+        key ^= ZOBRIST_KEYS.castling_rights(self.castling_rights);
+        key ^ self.en_passant_key()
+    }
+
+    /// Recomputes the stored Zobrist key, needed after writing the public fields directly.
+    pub fn refresh_zobrist_key(&mut self) {
+        self.zobrist_key = self.compute_zobrist_key();
+    }
+
+    /// Sets the active player, keeping the Zobrist key in sync.
+    pub fn set_active_player(&mut self, color: Color) {
+        self.zobrist_key ^= ZOBRIST_KEYS.side(self.active_player) ^ ZOBRIST_KEYS.side(color);
+        self.active_player = color;
+    }
+
+    /// Sets the castling rights, keeping the Zobrist key in sync.
+    pub fn set_castling_rights(&mut self, castling_rights: CastlingRights) {
+        self.zobrist_key ^= ZOBRIST_KEYS.castling_rights(self.castling_rights)
+            ^ ZOBRIST_KEYS.castling_rights(castling_rights);
+        self.castling_rights = castling_rights;
+    }
+
+    /// Sets the en passant square, keeping the Zobrist key in sync.
+    pub fn set_en_passant_square(&mut self, en_passant_square: Option<Square>) {
+        self.zobrist_key ^= self.en_passant_key();
+        self.en_passant_square = en_passant_square;
+        self.zobrist_key ^= self.en_passant_key();
+    }
+
+    /// Pawns decide whether the en passant file is part of the key, so changes to a pawn
+    /// bitboard are bracketed by two calls to this.
+    #[inline]
+    pub(crate) fn toggle_en_passant_key_for(&mut self, piece: Piece) {
+        if piece.is_pawn() && self.en_passant_square.is_some() {
+            self.zobrist_key ^= self.en_passant_key();
+        }
+    }
+
+    /// Key contribution of the en passant square, zero unless a pawn could capture onto it.
+    pub(crate) fn en_passant_key(&self) -> ZobristKey {
+        match self.en_passant_square {
+            Some(square) if self.en_passant_capturers(square) != 0 => ZOBRIST_KEYS.en_passant_file(square),
+            _ => 0,
+        }
+    }
+
+    /// Returns the pawns standing next to the pawn that skipped over `square`.
+    pub(crate) fn en_passant_capturers(&self, square: Square) -> Bitboard {
+        // A pawn that skipped rank 6 (row 2) is black and may be taken by white pawns on row 3
+        let (capturer, pawn_row) = match square.get_row() {
+            2 => (Piece::WhitePawn, 3),
+            5 => (Piece::BlackPawn, 4),
+            _ => return 0,
+        };
+        let col = square.get_col();
+        let mut neighbours: Bitboard = 0;
+        if col > 0 {
+            neighbours |= 1 << Square::new(pawn_row, col - 1);
+        }
+        if col < Square::COLS - 1 {
+            neighbours |= 1 << Square::new(pawn_row, col + 1);
+        }
+        self.data[capturer.to_index()] & neighbours
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_moves::ChessMove;
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys: Vec<ZobristKey> = ZOBRIST_KEYS.pieces.iter().flatten().copied().collect();
+        keys.push(ZOBRIST_KEYS.black_to_move);
+        keys.extend_from_slice(&ZOBRIST_KEYS.castling_rights[1..]);
+        keys.extend_from_slice(&ZOBRIST_KEYS.en_passant_files);
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+
+    #[test]
+    fn incremental_key_matches_recomputation() {
+        let mut board = Board::std_new();
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+
+        for (origin, target) in [(Square::E2, Square::E4), (Square::D7, Square::D5), (Square::E4, Square::D5), (Square::G8, Square::F6)] {
+            let chess_move = ChessMove::new_from_squares(&mut board, origin, target, false).ok().unwrap();
+            chess_move.make_move(&mut board);
+            assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+        }
+
+        let _ = board.remove_piece_at(Square::D5, Piece::WhitePawn);
+        let _ = board.add_piece_at(Square::D4, Piece::WhiteQueen);
+        board.set_bitboard(Piece::BlackRook, board.get_bitboard(Piece::BlackRook) | 1 << Square::A6);
+        board.set_castling_rights(0b0101);
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+    }
+
+    #[test]
+    fn unusable_en_passant_square_is_not_hashed() {
+        let with_square = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let without_square = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(with_square.zobrist_key(), without_square.zobrist_key());
+
+        let with_square = Board::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3").unwrap();
+        let without_square = Board::from_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3").unwrap();
+        assert_ne!(with_square.zobrist_key(), without_square.zobrist_key());
+    }
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let mut first = Board::std_new();
+        let mut second = Board::std_new();
+        for (origin, target) in [(Square::G1, Square::F3), (Square::G8, Square::F6), (Square::B1, Square::C3)] {
+            ChessMove::new_from_squares(&mut first, origin, target, false).ok().unwrap().make_move(&mut first);
+        }
+        for (origin, target) in [(Square::B1, Square::C3), (Square::G8, Square::F6), (Square::G1, Square::F3)] {
+            ChessMove::new_from_squares(&mut second, origin, target, false).ok().unwrap().make_move(&mut second);
+        }
+        assert_eq!(first.zobrist_key(), second.zobrist_key());
+        assert_ne!(first.zobrist_key(), Board::std_new().zobrist_key());
+    }
+}
@@ -1,15 +1,21 @@
-use std::collections::HashSet;
+mod generator;
+mod move_list;
+pub mod packed_move;
+mod san;
+
 use std::fmt;
 use std::fmt::Display;
 
 use crate::board::pieces::{Color, Piece};
 use crate::board::square::{Col, Row, Square, SquareExt};
-use crate::board::{Bitboard, BitboardExt, Board, CastlingRightsExt};
+use crate::board::{Bitboard, BitboardExt, Board, CastlingRights, CastlingRightsExt};
 use crate::chess_moves::MoveError::{
     CastleNotPermmited, IllegalPromotion, KingCannotSeeRook, LeavesKingInCheck,
 };
 use crate::rules::{CastleType, MoveResult, MoveType};
 
+pub use move_list::MoveList;
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone)]
 pub enum MoveData {
@@ -82,7 +88,7 @@ pub enum Disambiguity {
     Square(Square),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct ChessMove {
     piece: Piece,
     origin: Square,
@@ -90,6 +96,16 @@ pub struct ChessMove {
     meta_data: MoveData,
 }
 
+/// Board state a move can't restore on its own, returned by `make_reversible_move` and consumed
+/// by `undo_move`.
+#[derive(Debug, Clone, Copy)]
+pub struct MoveUndo {
+    removed_piece: Option<Piece>,
+    en_passant_square: Option<Square>,
+    half_move_clock: u8,
+    castling_rights: CastlingRights,
+}
+
 const WHITE_KINGSIDE_CASTLE: ChessMove = ChessMove {
     piece: Piece::WhiteKing,
     origin: Color::White.king_starting_square(),
@@ -136,6 +152,19 @@ impl ChessMove {
             meta_data: special,
         }
     }
+    /// The piece that ends up on the target square, the promoted piece for promotions.
+    pub fn get_piece(&self) -> Piece {
+        self.piece
+    }
+    pub fn get_origin(&self) -> Square {
+        self.origin
+    }
+    pub fn get_target(&self) -> Square {
+        self.target
+    }
+    pub fn get_meta_data(&self) -> MoveData {
+        self.meta_data
+    }
     /// Constructs and returns a valid move if it is legal on the given board.
     ///
     /// This method first verifies that the move follows the movement rules of the given piece.
@@ -189,41 +218,30 @@ impl ChessMove {
         Ok(castle)
     }
 
+    /// Finds the least information that tells the first origin apart from the others, following
+    /// the SAN rules: the file if no other origin shares it, otherwise the rank if no other
+    /// origin shares that, otherwise the whole square.
+    ///
+    /// # Arguments
+    /// * `possible_origins` - The origins of every piece that can make the move, the one to
+    ///   disambiguate first.
     #[inline]
-    pub fn disambiguate_from_squares(possible_origins: &Vec<Square>) -> Disambiguity {
-        if possible_origins.len() <= 1 {
-            return Disambiguity::None;
-        } // 0 doesn't make sense, but neither does passing an empty vector
-
-        let mut rows: HashSet<Row> = HashSet::new();
-        let mut cols: HashSet<Col> = HashSet::new();
-        let mut disambiguation_need: Disambiguity = Disambiguity::None;
-
-        for origin in possible_origins {
-            let row: Row = origin.get_row();
-            let col: Col = origin.get_col();
-
-            if !cols.insert(col) {
-                // If col is present, row disambiguation is needed
-                if disambiguation_need == Disambiguity::None {
-                    disambiguation_need = Disambiguity::Rank(row);
-                } else {
-                    // If both row and col are present, square disambiguation is needed
-                    return Disambiguity::Square(*origin);
-                }
-            }
-            if !rows.insert(row) {
-                // If row is present, col disambiguation is needed
-                if disambiguation_need == Disambiguity::None {
-                    disambiguation_need = Disambiguity::File(col);
-                } else {
-                    // If both row and col are present, square disambiguation is needed
-                    return Disambiguity::Square(*origin);
-                }
-            }
-        }
+    pub fn disambiguate_from_squares(possible_origins: &[Square]) -> Disambiguity {
+        let (origin, others) = match possible_origins.split_first() {
+            Some((origin, others)) if !others.is_empty() => (*origin, others),
+            // 0 doesn't make sense, but neither does passing an empty vector
+            _ => return Disambiguity::None,
+        };
 
-        disambiguation_need
+        let col: Col = origin.get_col();
+        let row: Row = origin.get_row();
+        if others.iter().all(|other| other.get_col() != col) {
+            Disambiguity::File(col)
+        } else if others.iter().all(|other| other.get_row() != row) {
+            Disambiguity::Rank(row)
+        } else {
+            Disambiguity::Square(origin)
+        }
     }
     pub fn new_with_disambiguation(
             board: &mut Board,
@@ -267,41 +285,11 @@ impl ChessMove {
             )),
         }
     }
+    /// Same as `disambiguate_from_squares`, for the origins of `possible_moves`.
     #[inline]
-    pub fn disambiguate_from_moves(possible_moves: &Vec<ChessMove>) -> Disambiguity {
-        if possible_moves.len() <= 1 {
-            return Disambiguity::None;
-        } // 0 doesn't make sense, but neither does passing an empty vector
-
-        let mut rows: HashSet<Row> = HashSet::new();
-        let mut cols: HashSet<Col> = HashSet::new();
-        let mut disambiguation_need: Disambiguity = Disambiguity::None;
-
-        for chess_move in possible_moves {
-            let col = chess_move.origin.get_col();
-            let row = chess_move.origin.get_row();
-
-            if !cols.insert(col) {
-                // If col is present, row disambiguation is needed
-                if disambiguation_need == Disambiguity::None {
-                    disambiguation_need = Disambiguity::Rank(row);
-                } else {
-                    // If both row and col are present, square disambiguation is needed
-                    return Disambiguity::Square(chess_move.origin);
-                }
-            }
-            if !rows.insert(row) {
-                // If row is present, col disambiguation is needed
-                if disambiguation_need == Disambiguity::None {
-                    disambiguation_need = Disambiguity::File(col);
-                } else {
-                    // If both row and col are present, square disambiguation is needed
-                    return Disambiguity::Square(chess_move.origin);
-                }
-            }
-        }
-
-        disambiguation_need
+    pub fn disambiguate_from_moves(possible_moves: &[ChessMove]) -> Disambiguity {
+        let origins: Vec<Square> = possible_moves.iter().map(|chess_move| chess_move.origin).collect();
+        ChessMove::disambiguate_from_squares(&origins)
     }
     pub fn new_without_disambiguation(
         board: &mut Board,
@@ -431,7 +419,7 @@ impl ChessMove {
         }
         match moves.len() {
             0 => Err(MoveError::OriginNotFound(piece)),
-            1 => Ok(moves[0].clone()),
+            1 => Ok(moves[0]),
             _ => Err(MoveError::DisambiguousMove(
                 Self::disambiguate_from_moves(
                     &moves,
@@ -477,7 +465,7 @@ impl ChessMove {
         }
     }
 
-    pub fn to_long_algebraic(&self) -> String {
+    pub fn to_long_algebraic(self) -> String {
         let is_promotion = self.meta_data.is_promotion();
         let initial_char: Option<&str> = if is_promotion {
             None
@@ -486,7 +474,7 @@ impl ChessMove {
         };
 
         let mut algebraic_notation = if self.meta_data.is_castle() {
-            if self.origin.get_col() > self.target.get_col() {
+            if self.target.get_col() > self.origin.get_col() {
                 CastleType::KingSide.to_string()
             } else {
                 CastleType::QueenSide.to_string()
@@ -514,16 +502,29 @@ impl ChessMove {
         }
         algebraic_notation
     }
-    pub fn to_simplified(&self) -> String {
-        //@TODO give own logic
+    /// Formats the move in pure coordinate notation, e.g. `e2e4` or `e7e8q`.
+    pub fn to_coordinate_notation(self) -> String {
+        let mut notation = format!(
+            "{}{}",
+            self.origin.to_square_string(),
+            self.target.to_square_string()
+        );
+        if self.meta_data.is_promotion() {
+            notation.push(self.piece.to_char().to_ascii_lowercase());
+        }
+        notation
+    }
+    /// Without the position the move is played from SAN can't be disambiguated, so this falls
+    /// back to long algebraic notation. Use `to_san` where the board is available.
+    pub fn to_simplified(self) -> String {
         self.to_long_algebraic()
     }
 
     fn make_move_on_board(&self, board: &mut Board) {
         if self.meta_data.is_enable_en_passant() {
-            board.en_passant_square = Some((self.origin + self.target) / 2)
+            board.set_en_passant_square(Some((self.origin + self.target) / 2));
         } else {
-            board.en_passant_square = None;
+            board.set_en_passant_square(None);
         }
         match self.meta_data.get_move_type() {
             MoveType::Regular => {
@@ -579,7 +580,8 @@ impl ChessMove {
                 _ = board.add_piece_at(self.target, self.piece);
             }
         }
-        board.active_player = board.active_player.toggle_color();
+        board.set_castling_rights(self.updated_castling_rights(board.castling_rights));
+        board.set_active_player(board.active_player.toggle_color());
         match board.active_player {
             Color::White => {
                 board.full_move_number += 1;
@@ -588,15 +590,44 @@ impl ChessMove {
         }
         if self.meta_data.reset_half_move() || self.piece.is_pawn() {
             board.half_move_clock = 0;
+        } else {
+            board.half_move_clock = board.half_move_clock.saturating_add(1);
         }
+        debug_assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
+    }
+
+    /// Castling rights left after this move. Moving the king forfeits both of its rights, and a
+    /// rook leaving or being captured on its starting corner forfeits that side.
+    fn updated_castling_rights(&self, mut castling_rights: CastlingRights) -> CastlingRights {
+        if self.piece.is_king() {
+            castling_rights.king_moved(self.piece.get_color());
+        }
+        for square in [self.origin, self.target] {
+            match square {
+                Square::H1 => castling_rights.rook_moved(Color::White, true),
+                Square::A1 => castling_rights.rook_moved(Color::White, false),
+                Square::H8 => castling_rights.rook_moved(Color::Black, true),
+                Square::A8 => castling_rights.rook_moved(Color::Black, false),
+                _ => {}
+            }
+        }
+        castling_rights
     }
 
     pub fn make_move(&self, board: &mut Board) {
         self.make_move_on_board(board);
     }
-    fn make_reversible_move(&self, board: &mut Board) -> (Option<Piece>, Option<Square>, u8) {
+    /// Plays the move on the board, returning what `undo_move` needs to take it back.
+    ///
+    /// # Arguments
+    /// * `board` - The board the move was generated or validated for.
+    ///
+    /// # Returns
+    /// * `MoveUndo` - The captured piece and the counters and rights the move overwrote.
+    pub fn make_reversible_move(&self, board: &mut Board) -> MoveUndo {
         let old_en_passant_square = board.en_passant_square;
         let old_half_move_clock = board.half_move_clock;
+        let old_castling_rights = board.castling_rights;
 
         let removed_piece = if self.meta_data.is_capture() {
             match self.meta_data.get_move_type() {
@@ -612,25 +643,31 @@ impl ChessMove {
 
         self.make_move_on_board(board);
 
-        (removed_piece, old_en_passant_square, old_half_move_clock)
+        MoveUndo {
+            removed_piece,
+            en_passant_square: old_en_passant_square,
+            half_move_clock: old_half_move_clock,
+            castling_rights: old_castling_rights,
+        }
     }
 
-    fn undo_move(
-        &self,
-        board: &mut Board,
-        removed_piece: Option<Piece>,
-        old_en_passant_square: Option<Square>,
-        old_half_move_clock: u8,
-    ) -> Result<(), MoveError> {
-        board.active_player = board.active_player.toggle_color();
+    /// Takes back a move played with `make_reversible_move`.
+    ///
+    /// # Arguments
+    /// * `board` - The board the move was played on, with no other moves played since.
+    /// * `undo` - The `MoveUndo` returned when the move was played.
+    pub fn undo_move(&self, board: &mut Board, undo: MoveUndo) -> Result<(), MoveError> {
+        let removed_piece = undo.removed_piece;
+        board.set_active_player(board.active_player.toggle_color());
         match board.active_player {
             Color::White => {}
             Color::Black => {
                 board.full_move_number -= 1;
             }
         }
-        board.en_passant_square = old_en_passant_square;
-        board.half_move_clock = old_half_move_clock;
+        board.set_en_passant_square(undo.en_passant_square);
+        board.set_castling_rights(undo.castling_rights);
+        board.half_move_clock = undo.half_move_clock;
 
         match self.meta_data.get_move_type() {
             MoveType::Regular => {
@@ -661,8 +698,8 @@ impl ChessMove {
                 let rook_row = color.get_back_rank_row();
 
                 //Undo king move
-                _ = board.remove_piece_at(self.origin, self.piece);
-                _ = board.add_piece_at(self.target, self.piece);
+                _ = board.remove_piece_at(self.target, self.piece);
+                _ = board.add_piece_at(self.origin, self.piece);
 
                 //Undo rook move
                 let rook_col_old: u8;
@@ -680,6 +717,7 @@ impl ChessMove {
                 _ = board.add_piece_at(rook_square, rook);
             }
         }
+        debug_assert_eq!(board.zobrist_key(), board.compute_zobrist_key());
 
         Ok(())
     }
@@ -1129,491 +1167,19 @@ impl ChessMove {
     }
 
     pub fn leaves_king_in_check(&self, board: &mut Board) -> bool {
-        let (removed_piece, old_en_passant_range, old_half_move_clock) =
-            self.make_reversible_move(board);
+        let undo = self.make_reversible_move(board);
 
         let king_in_check = board.is_in_check(self.piece.get_color());
 
-        let _ = self.undo_move(
-            board,
-            removed_piece,
-            old_en_passant_range,
-            old_half_move_clock,
-        );
+        let _ = self.undo_move(board, undo);
 
         king_in_check
     }
 
     pub fn get_valid_moves(board: &mut Board) -> Vec<ChessMove> {
-        let possible_moves: Vec<ChessMove> = Self::get_possible_moves(board);
-        Self::validate_moves(possible_moves, board)
-    }
-    fn validate_moves(moves: Vec<ChessMove>, board: &mut Board) -> Vec<ChessMove> {
-        moves
-            .into_iter()
-            .filter(|m| !m.leaves_king_in_check(board))
-            .collect()
-    }
-    fn get_possible_moves(board: &Board) -> Vec<ChessMove> {
-        let mut moves: Vec<ChessMove> = Vec::with_capacity(64);
-        for piece in Piece::iter_color_pieces(&board.active_player) {
-            match piece {
-                Piece::WhitePawn | Piece::BlackPawn => {
-                    ChessMove::add_pawn_moves(&board, &mut moves)
-                }
-                Piece::WhiteRook | Piece::BlackRook => {
-                    ChessMove::add_rook_moves(&board, &mut moves)
-                }
-                Piece::WhiteKnight | Piece::BlackKnight => {
-                    ChessMove::add_knight_moves(&board, &mut moves)
-                }
-                Piece::WhiteBishop | Piece::BlackBishop => {
-                    ChessMove::add_bishop_moves(&board, &mut moves)
-                }
-                Piece::WhiteQueen | Piece::BlackQueen => {
-                    ChessMove::add_queen_moves(&board, &mut moves)
-                }
-                Piece::WhiteKing | Piece::BlackKing => {
-                    ChessMove::add_king_moves(&board, &mut moves)
-                }
-            }
-        }
-        moves
-    }
-
-    /// # !!!Not meant to be called directly!!!
-    /// Checks to see if there is a piece on the destination square of the given board:
-    /// - If yes, adds a capture move if the piece color matches the color provided and set move as capture,
-    /// - If no, adds move as a regular move
-    ///
-    /// # Arguments
-    ///
-    /// * `board`: The board the piece could act upon and will validate against.
-    /// * `moves`: A Vec<ChessMove> of moves to append to
-    /// * `piece`: The piece being moved
-    /// * `origin_square`: The starting square of the piece
-    /// * `target_square`: The destination square of the piece
-    /// * `opponent`: The opponents color to check against.
-    #[inline]
-    fn add_if_valid(
-        board: &Board,
-        moves: &mut Vec<ChessMove>,
-        piece: Piece,
-        origin_square: Square,
-        target_square: Square,
-        opponent: Color,
-    ) {
-        match board.is_square_occupied(target_square) {
-            true => {
-                if board
-                    .get_colored_piece_at(target_square, opponent)
-                    .is_some()
-                {
-                    moves.push(ChessMove::new(
-                        piece,
-                        origin_square,
-                        target_square,
-                        MoveData::Capture,
-                    ));
-                }
-            }
-            false => {
-                moves.push(ChessMove::new(
-                    piece,
-                    origin_square,
-                    target_square,
-                    MoveData::Normal,
-                ));
-            }
-        }
-    }
-    fn add_pawn_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let pawn = active_player.get_pawn();
-        let pawn_row_ascending = active_player.is_pawn_ascending();
-        let starting_row = active_player.get_pawn_starting_row();
-        let promotion_row = active_player.get_pawn_promotion_row();
-        let mut bitboard = board.get_bitboard(pawn);
-
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            let col = origin_square.get_col();
-            let target_square: Square = origin_square + active_player.get_pawn_direction();
-            let promotes = target_square.get_row() == promotion_row;
-            bitboard &= !(1 << origin_square);
-
-            //Add regular forward move
-            if !board.is_square_occupied(target_square) {
-                if promotes {
-                    for promotion_piece in active_player.get_promotion_pieces().iter() {
-                        moves.push(ChessMove::new(
-                            *promotion_piece,
-                            origin_square,
-                            target_square,
-                            MoveData::Promotion,
-                        ))
-                    }
-                } else {
-                    moves.push(ChessMove::new(
-                        pawn,
-                        origin_square,
-                        target_square,
-                        MoveData::Normal,
-                    ));
-
-                    //If regular move is valid, double move may be valid
-                    if origin_square.get_row() == starting_row {
-                        let target_square: Square = if pawn_row_ascending {
-                            origin_square + 16
-                        } else {
-                            origin_square - 16
-                        };
-                        if !board.is_square_occupied(target_square) {
-                            moves.push(ChessMove::new(
-                                pawn,
-                                origin_square,
-                                target_square,
-                                MoveData::EnableEnPassant,
-                            ));
-                        }
-                    }
-                }
-            }
-
-            fn diagonal_capture(
-                board: &Board,
-                moves: &mut Vec<ChessMove>,
-                pawn: Piece,
-                origin_square: Square,
-                diagonal_target_square: Square,
-                active_player: Color,
-                promotes: bool,
-            ) {
-                // Regular capture
-                if board.is_square_occupied(diagonal_target_square) {
-                    if let Some(_) = board
-                        .get_colored_piece_at(diagonal_target_square, active_player.toggle_color())
-                    {
-                        if promotes {
-                            for promotion_piece in active_player.get_promotion_pieces().iter() {
-                                moves.push(ChessMove::new(
-                                    *promotion_piece,
-                                    origin_square,
-                                    diagonal_target_square,
-                                    MoveData::CapturePromotion,
-                                ));
-                            }
-                        } else {
-                            moves.push(ChessMove::new(
-                                pawn,
-                                origin_square,
-                                diagonal_target_square,
-                                MoveData::Capture,
-                            ));
-                        }
-                    }
-                // en passant
-                } else if board.en_passant_square == Some(diagonal_target_square) {
-                    moves.push(ChessMove::new(
-                        pawn,
-                        origin_square,
-                        diagonal_target_square,
-                        MoveData::EnPassant,
-                    ));
-                }
-            }
-            //Diagonal left Captures
-            if col > 0 {
-                let target_square: Square = target_square - 1;
-                diagonal_capture(
-                    &board,
-                    moves,
-                    pawn,
-                    origin_square,
-                    target_square,
-                    active_player,
-                    promotes,
-                );
-            }
-
-            //Diagonal right Captures
-            if col < 7 {
-                let target_square: Square = target_square + 1;
-                diagonal_capture(
-                    &board,
-                    moves,
-                    pawn,
-                    origin_square,
-                    target_square,
-                    active_player,
-                    promotes,
-                );
-            }
-        }
-    }
-    fn add_rook_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let rook = active_player.get_rook();
-        let mut bitboard = board.get_bitboard(rook);
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            bitboard &= !(1 << origin_square);
-            for i in 0..4 {
-                let ascending = i / 2 == 0;
-                let result = if i % 2 == 0 {
-                    board.clear_n_capture_down_file(
-                        origin_square,
-                        ascending,
-                        active_player.toggle_color(),
-                    )
-                } else {
-                    board.clear_n_capture_down_rank(
-                        origin_square,
-                        ascending,
-                        active_player.toggle_color(),
-                    )
-                };
-                let mut movable_squares = result.0;
-                let capture_square = result.1;
-                while movable_squares != 0 {
-                    let target_square = movable_squares.trailing_zeros() as Square;
-                    movable_squares &= !(1 << target_square);
-                    moves.push(ChessMove::new(
-                        rook,
-                        origin_square,
-                        target_square,
-                        MoveData::Normal,
-                    ));
-                }
-                if let Some(target_square) = capture_square {
-                    moves.push(ChessMove::new(
-                        rook,
-                        origin_square,
-                        target_square,
-                        MoveData::Capture,
-                    ));
-                }
-            }
-        }
-    }
-    fn add_knight_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let opponent = active_player.toggle_color();
-        let knight = active_player.get_knight();
-        let mut bitboard = board.get_bitboard(knight);
-
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            bitboard &= !(1 << origin_square);
-            for target_square in origin_square.iter_knight_offsets() {
-                ChessMove::add_if_valid(
-                    board,
-                    moves,
-                    knight,
-                    origin_square,
-                    target_square,
-                    opponent,
-                );
-            }
-        }
-    }
-    fn add_bishop_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let bishop = active_player.get_bishop();
-        let mut bitboard = board.get_bitboard(bishop);
-
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            bitboard &= !(1 << origin_square);
-
-            for i in 0..4 {
-                let ascending_row = i / 2 == 0;
-                let ascending_col = i % 2 == 0;
-                let result = board.clear_n_capture_down_diagonal(
-                    origin_square,
-                    ascending_row,
-                    ascending_col,
-                    active_player.toggle_color(),
-                );
-
-                let mut movable_squares = result.0;
-                let capture_square = result.1;
-
-                while movable_squares != 0 {
-                    let target_square = movable_squares.trailing_zeros() as Square;
-                    movable_squares &= !(1 << target_square);
-                    moves.push(ChessMove::new(
-                        bishop,
-                        origin_square,
-                        target_square,
-                        MoveData::Normal,
-                    ));
-                }
-
-                if let Some(target_square) = capture_square {
-                    moves.push(ChessMove::new(
-                        bishop,
-                        origin_square,
-                        target_square,
-                        MoveData::Capture,
-                    ));
-                }
-            }
-        }
-    }
-    fn add_queen_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let queen = active_player.get_queen();
-        let mut bitboard = board.get_bitboard(queen);
-
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            bitboard &= !(1 << origin_square);
-
-            for i in 0..4 {
-                let ascending = i / 2 == 0;
-                let ascending_col = i % 2 == 0;
-
-                //Handle straight moves
-                {
-                    let result = if i % 2 == 0 {
-                        board.clear_n_capture_down_rank(
-                            origin_square,
-                            ascending,
-                            active_player.toggle_color(),
-                        )
-                    } else {
-                        board.clear_n_capture_down_file(
-                            origin_square,
-                            ascending,
-                            active_player.toggle_color(),
-                        )
-                    };
-                    let mut movable_squares = result.0;
-                    let capture_square = result.1;
-
-                    while movable_squares != 0 {
-                        let target_square = movable_squares.trailing_zeros() as Square;
-                        movable_squares &= !(1 << target_square);
-                        moves.push(ChessMove::new(
-                            queen,
-                            origin_square,
-                            target_square,
-                            MoveData::Normal,
-                        ));
-                    }
-                    if let Some(target_square) = capture_square {
-                        moves.push(ChessMove::new(
-                            queen,
-                            origin_square,
-                            target_square,
-                            MoveData::Capture,
-                        ));
-                    }
-                }
-
-                //Handle diagonal moves
-                {
-                    let result = board.clear_n_capture_down_diagonal(
-                        origin_square,
-                        ascending,
-                        ascending_col,
-                        active_player.toggle_color(),
-                    );
-                    let mut movable_squares = result.0;
-                    let capture_square = result.1;
-
-                    while movable_squares != 0 {
-                        let target_square = movable_squares.trailing_zeros() as Square;
-                        movable_squares &= !(1 << target_square);
-                        moves.push(ChessMove::new(
-                            queen,
-                            origin_square,
-                            target_square,
-                            MoveData::Normal,
-                        ));
-                    }
-                    if let Some(target_square) = capture_square {
-                        moves.push(ChessMove::new(
-                            queen,
-                            origin_square,
-                            target_square,
-                            MoveData::Capture,
-                        ));
-                    }
-                }
-            }
-        }
-    }
-    fn add_king_moves(board: &Board, moves: &mut Vec<ChessMove>) {
-        let active_player = board.active_player;
-        let opponent = active_player.toggle_color();
-        let king = active_player.get_king();
-        let mut bitboard = board.get_bitboard(king);
-
-        while bitboard != 0 {
-            let origin_square: Square = bitboard.trailing_zeros() as Square;
-            bitboard &= !(1 << origin_square);
-
-            // Calculate regular king moves
-            for target_square in origin_square.iter_king_offsets() {
-                ChessMove::add_if_valid(board, moves, king, origin_square, target_square, opponent);
-            }
-
-            // Calculate castle
-            match active_player {
-                /*
-                 * 0, 7, 56, and 63 are the starting locations of the rooks
-                 * If the flag is enabled, the rooks will be there and the king in its original position
-                 * To go up and down a row you need to subtract or add 8, to go left or right you add
-                 * 1, so +2/-2 describes the kings movement in a Castle to a T.
-                 */
-                Color::White => {
-                    if board.castling_rights.can_castle_white_king_side() {
-                        if board.sees_down_rank(origin_square, true) & (1 << 63) == 1 << 63 {
-                            moves.push(ChessMove::new(
-                                king,
-                                origin_square,
-                                origin_square + 2,
-                                MoveData::Castling,
-                            ));
-                        }
-                    }
-                    if board.castling_rights.can_castle_white_queen_side() {
-                        if board.sees_down_rank(origin_square, false) & (1 << 56) == 1 << 56 {
-                            moves.push(ChessMove::new(
-                                king,
-                                origin_square,
-                                origin_square - 2,
-                                MoveData::Castling,
-                            ));
-                        }
-                    }
-                }
-                Color::Black => {
-                    if board.castling_rights.can_castle_black_king_side() {
-                        if board.sees_down_rank(origin_square, true) & (1 << 7) == 1 << 7 {
-                            moves.push(ChessMove::new(
-                                king,
-                                origin_square,
-                                origin_square + 2,
-                                MoveData::Castling,
-                            ));
-                        }
-                    }
-                    if board.castling_rights.can_castle_black_queen_side() {
-                        if board.sees_down_rank(origin_square, false) & (1 << 0) == 1 << 0 {
-                            moves.push(ChessMove::new(
-                                king,
-                                origin_square,
-                                origin_square - 2,
-                                MoveData::Castling,
-                            ));
-                        }
-                    }
-                }
-            }
-        }
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(board, &mut moves);
+        moves.as_slice().to_vec()
     }
 
     pub fn debug_string(&self) -> String {
@@ -1764,7 +1330,8 @@ impl MoveData {
             | MoveData::PromotionCheck
             | MoveData::CapturePromotionCheck
             | MoveData::EnPassantCheck
-            | MoveData::EnableEnPassantCheck => true,
+            | MoveData::EnableEnPassantCheck
+            | MoveData::CaptureCheck => true,
             _ => false,
         }
     }
@@ -1775,7 +1342,8 @@ impl MoveData {
             | MoveData::PromotionCheckmate
             | MoveData::CapturePromotionCheckmate
             | MoveData::EnPassantCheckmate
-            | MoveData::EnableEnPassantCheckmate => true,
+            | MoveData::EnableEnPassantCheckmate
+            | MoveData::CaptureCheckmate => true,
             _ => false,
         }
     }
@@ -1786,7 +1354,8 @@ impl MoveData {
             | MoveData::PromotionStalemate
             | MoveData::CapturePromotionStalemate
             | MoveData::EnPassantStalemate
-            | MoveData::EnableEnPassantStalemate => true,
+            | MoveData::EnableEnPassantStalemate
+            | MoveData::CaptureStalemate => true,
             _ => false,
         }
     }
@@ -1908,6 +1477,7 @@ impl MoveData {
             MoveData::CapturePromotion => MoveData::CapturePromotionCheck,
             MoveData::EnPassant => MoveData::EnPassantCheck,
             MoveData::EnableEnPassant => MoveData::EnableEnPassantCheck,
+            MoveData::Capture => MoveData::CaptureCheck,
             _ => unimplemented!("Check variant not handled for {:?}", self),
         }
     }
@@ -1919,6 +1489,7 @@ impl MoveData {
             MoveData::CapturePromotion => MoveData::CapturePromotionCheckmate,
             MoveData::EnPassant => MoveData::EnPassantCheckmate,
             MoveData::EnableEnPassant => MoveData::EnableEnPassantCheckmate,
+            MoveData::Capture => MoveData::CaptureCheckmate,
             _ => unimplemented!("Checkmate variant not handled for {:?}", self),
         }
     }
@@ -1930,6 +1501,7 @@ impl MoveData {
             MoveData::CapturePromotion => MoveData::CapturePromotionStalemate,
             MoveData::EnPassant => MoveData::EnPassantStalemate,
             MoveData::EnableEnPassant => MoveData::EnableEnPassantStalemate,
+            MoveData::Capture => MoveData::CaptureStalemate,
             _ => unimplemented!("Stalemate variant not handled for {:?}", self),
         }
     }
@@ -0,0 +1,391 @@
+//! Legal move generation.
+//!
+//! Pins and checkers are computed once per position, which lets the generator emit only legal
+//! moves instead of playing and undoing every candidate to see whether it leaves the king in
+//! check:
+//! - In double check only the king may move.
+//! - In single check every other move must capture the checker or block its line.
+//! - A pinned piece may only move along the line through its king and itself.
+//! - King moves are checked against the attacks that remain once the king leaves its square.
+//! - En passant removes two pieces from a rank at once, so it is verified against the resulting
+//!   occupancy directly.
+use crate::board::attacks;
+use crate::board::pieces::{Color, Piece};
+use crate::board::square::{Square, SquareExt};
+use crate::board::{Bitboard, Board, CastlingRightsExt};
+use crate::chess_moves::{ChessMove, MoveData, MoveList};
+use crate::rules::CastleType;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GenerationType {
+    All,
+    /// Moves that capture a piece, including en passant and capturing promotions.
+    Captures,
+    /// Every move that doesn't capture, including castling and non-capturing promotions.
+    Quiets,
+}
+
+/// Position facts shared by the generation of every piece type.
+struct GenerationContext<'a> {
+    board: &'a Board,
+    active_player: Color,
+    king_square: Square,
+    own_pieces: Bitboard,
+    occupied: Bitboard,
+    /// Squares a move may land on, by generation type.
+    targets: Bitboard,
+    /// Squares non-king moves must land on to resolve a check, every square when not in check.
+    check_mask: Bitboard,
+    pinned: Bitboard,
+    generation_type: GenerationType,
+}
+
+impl GenerationContext<'_> {
+    /// Squares a piece on `origin` may move to without exposing its king.
+    #[inline]
+    fn pin_mask(&self, origin: Square) -> Bitboard {
+        if self.pinned & (1 << origin) != 0 {
+            attacks::line(self.king_square, origin)
+        } else {
+            !0
+        }
+    }
+
+    #[inline]
+    fn move_data(&self, target: Square) -> MoveData {
+        if self.occupied & (1 << target) != 0 {
+            MoveData::Capture
+        } else {
+            MoveData::Normal
+        }
+    }
+}
+
+impl ChessMove {
+    /// Generates every legal move for the active player.
+    ///
+    /// # Arguments
+    /// * `board` - The position to generate moves for.
+    /// * `moves` - The list to append the moves to.
+    pub fn generate_legal_moves(board: &Board, moves: &mut MoveList) {
+        ChessMove::generate(board, moves, GenerationType::All);
+    }
+    /// Generates the legal moves that capture a piece, including en passant and capturing
+    /// promotions.
+    pub fn generate_captures(board: &Board, moves: &mut MoveList) {
+        ChessMove::generate(board, moves, GenerationType::Captures);
+    }
+    /// Generates the legal moves that don't capture, including castling and non-capturing
+    /// promotions. Together with `generate_captures` this covers every legal move exactly once.
+    pub fn generate_quiets(board: &Board, moves: &mut MoveList) {
+        ChessMove::generate(board, moves, GenerationType::Quiets);
+    }
+
+    fn generate(board: &Board, moves: &mut MoveList, generation_type: GenerationType) {
+        let active_player = board.active_player;
+        let opponent = active_player.toggle_color();
+        let king_square = match board.king_square_by_color(active_player) {
+            Some(square) => square,
+            None => return,
+        };
+        let own_pieces = board.get_color_bitboard(active_player);
+        let enemy_pieces = board.get_color_bitboard(opponent);
+        let occupied = own_pieces | enemy_pieces;
+        let checkers = board.attackers_to(king_square, opponent, occupied);
+
+        let context = GenerationContext {
+            board,
+            active_player,
+            king_square,
+            own_pieces,
+            occupied,
+            targets: match generation_type {
+                GenerationType::All => !own_pieces,
+                GenerationType::Captures => enemy_pieces,
+                GenerationType::Quiets => !occupied,
+            },
+            check_mask: match checkers.count_ones() {
+                0 => !0,
+                1 => attacks::between(king_square, checkers.trailing_zeros() as Square) | checkers,
+                _ => 0,
+            },
+            pinned: ChessMove::pinned_pieces(board, king_square, own_pieces, occupied),
+            generation_type,
+        };
+
+        ChessMove::generate_king_moves(&context, moves);
+        // Only the king can escape a double check
+        if context.check_mask == 0 {
+            return;
+        }
+        if checkers == 0 && generation_type != GenerationType::Captures {
+            ChessMove::generate_castles(&context, moves);
+        }
+        ChessMove::generate_pawn_moves(&context, moves);
+        ChessMove::generate_knight_moves(&context, moves);
+        for (piece, piece_attacks) in [
+            (active_player.get_rook(), attacks::rook_attacks as fn(Square, Bitboard) -> Bitboard),
+            (active_player.get_bishop(), attacks::bishop_attacks),
+            (active_player.get_queen(), attacks::queen_attacks),
+        ] {
+            ChessMove::generate_sliding_moves(&context, moves, piece, piece_attacks);
+        }
+    }
+
+    /// Finds the active player's pieces that are the only piece between their king and an enemy
+    /// slider.
+    fn pinned_pieces(board: &Board, king_square: Square, own_pieces: Bitboard, occupied: Bitboard) -> Bitboard {
+        let opponent = board.active_player.toggle_color();
+        let queens = board.get_bitboard(opponent.get_queen());
+        let mut snipers = attacks::rook_attacks(king_square, 0)
+            & (board.get_bitboard(opponent.get_rook()) | queens)
+            | attacks::bishop_attacks(king_square, 0)
+                & (board.get_bitboard(opponent.get_bishop()) | queens);
+
+        let mut pinned = 0;
+        while snipers != 0 {
+            let sniper = snipers.trailing_zeros() as Square;
+            snipers &= !(1 << sniper);
+            let blockers = attacks::between(king_square, sniper) & occupied;
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & own_pieces;
+            }
+        }
+        pinned
+    }
+
+    fn generate_king_moves(context: &GenerationContext, moves: &mut MoveList) {
+        let board = context.board;
+        let king = context.active_player.get_king();
+        let opponent = context.active_player.toggle_color();
+        // The king can't hide from a slider behind its own square
+        let occupied = context.occupied & !(1 << context.king_square);
+
+        let mut target_squares = attacks::king_attacks(context.king_square) & context.targets;
+        while target_squares != 0 {
+            let target_square = target_squares.trailing_zeros() as Square;
+            target_squares &= !(1 << target_square);
+            if board.attackers_to(target_square, opponent, occupied) == 0 {
+                moves.push(ChessMove::new(
+                    king,
+                    context.king_square,
+                    target_square,
+                    context.move_data(target_square),
+                ));
+            }
+        }
+    }
+
+    /// Adds castles, assuming the king is not in check.
+    fn generate_castles(context: &GenerationContext, moves: &mut MoveList) {
+        let board = context.board;
+        let active_player = context.active_player;
+        let opponent = active_player.toggle_color();
+        if context.king_square != active_player.king_starting_square() {
+            return;
+        }
+        for castle_type in [CastleType::KingSide, CastleType::QueenSide] {
+            let kingside = castle_type == CastleType::KingSide;
+            if !board.castling_rights.can_castle(active_player, kingside) {
+                continue;
+            }
+            let rook_col = if kingside { Square::COLS - 1 } else { 0 };
+            let rook_square = Square::new(active_player.get_back_rank_row(), rook_col);
+            if !board.is_piece_at(rook_square, active_player.get_rook())
+                || attacks::between(context.king_square, rook_square) & context.occupied != 0
+            {
+                continue;
+            }
+            // The king may not pass through or land on an attacked square
+            let target_square = active_player.king_castle_target(castle_type);
+            let mut king_path = attacks::between(context.king_square, target_square) | 1 << target_square;
+            let mut attacked = false;
+            while king_path != 0 && !attacked {
+                let square = king_path.trailing_zeros() as Square;
+                king_path &= !(1 << square);
+                attacked = board.attackers_to(square, opponent, context.occupied) != 0;
+            }
+            if !attacked {
+                moves.push(ChessMove::new(
+                    active_player.get_king(),
+                    context.king_square,
+                    target_square,
+                    MoveData::Castling,
+                ));
+            }
+        }
+    }
+
+    fn generate_pawn_moves(context: &GenerationContext, moves: &mut MoveList) {
+        let board = context.board;
+        let active_player = context.active_player;
+        let pawn = active_player.get_pawn();
+        let direction = active_player.get_pawn_direction();
+        let promotion_row = active_player.get_pawn_promotion_row();
+        let enemy_pieces = context.occupied & !context.own_pieces;
+
+        let mut bitboard = board.get_bitboard(pawn);
+        while bitboard != 0 {
+            let origin_square: Square = bitboard.trailing_zeros() as Square;
+            bitboard &= !(1 << origin_square);
+            let allowed = context.check_mask & context.pin_mask(origin_square);
+
+            // Forward moves
+            let target_square = origin_square + direction;
+            if context.generation_type != GenerationType::Captures && !board.is_square_occupied(target_square) {
+                if allowed & (1 << target_square) != 0 {
+                    if target_square.get_row() == promotion_row {
+                        for promotion_piece in active_player.get_promotion_pieces() {
+                            moves.push(ChessMove::new(promotion_piece, origin_square, target_square, MoveData::Promotion));
+                        }
+                    } else {
+                        moves.push(ChessMove::new(pawn, origin_square, target_square, MoveData::Normal));
+                    }
+                }
+                let double_target_square = target_square + direction;
+                if origin_square.get_row() == active_player.get_pawn_starting_row()
+                    && !board.is_square_occupied(double_target_square)
+                    && allowed & (1 << double_target_square) != 0
+                {
+                    moves.push(ChessMove::new(pawn, origin_square, double_target_square, MoveData::EnableEnPassant));
+                }
+            }
+            if context.generation_type == GenerationType::Quiets {
+                continue;
+            }
+
+            // Captures
+            let mut target_squares = attacks::pawn_attacks(active_player, origin_square) & enemy_pieces & allowed;
+            while target_squares != 0 {
+                let target_square = target_squares.trailing_zeros() as Square;
+                target_squares &= !(1 << target_square);
+                if target_square.get_row() == promotion_row {
+                    for promotion_piece in active_player.get_promotion_pieces() {
+                        moves.push(ChessMove::new(promotion_piece, origin_square, target_square, MoveData::CapturePromotion));
+                    }
+                } else {
+                    moves.push(ChessMove::new(pawn, origin_square, target_square, MoveData::Capture));
+                }
+            }
+
+            // En passant removes the captured pawn from beside the origin, so check the king
+            // against the occupancy left behind rather than the pin and check masks
+            if let Some(en_passant_square) = board.en_passant_square {
+                if attacks::pawn_attacks(active_player, origin_square) & (1 << en_passant_square) != 0 {
+                    let captured_square = Square::new(origin_square.get_row(), en_passant_square.get_col());
+                    let occupied = context.occupied ^ (1 << origin_square) ^ (1 << captured_square) | (1 << en_passant_square);
+                    let attackers = board.attackers_to(context.king_square, active_player.toggle_color(), occupied)
+                        & !(1 << captured_square);
+                    if attackers == 0 {
+                        moves.push(ChessMove::new(pawn, origin_square, en_passant_square, MoveData::EnPassant));
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate_knight_moves(context: &GenerationContext, moves: &mut MoveList) {
+        let knight = context.active_player.get_knight();
+        // A pinned knight can never stay on the pin line
+        let mut bitboard = context.board.get_bitboard(knight) & !context.pinned;
+        while bitboard != 0 {
+            let origin_square: Square = bitboard.trailing_zeros() as Square;
+            bitboard &= !(1 << origin_square);
+            let target_squares = attacks::knight_attacks(origin_square) & context.targets & context.check_mask;
+            ChessMove::push_targets(context, moves, knight, origin_square, target_squares);
+        }
+    }
+
+    fn generate_sliding_moves(
+        context: &GenerationContext,
+        moves: &mut MoveList,
+        piece: Piece,
+        piece_attacks: fn(Square, Bitboard) -> Bitboard,
+    ) {
+        let mut bitboard = context.board.get_bitboard(piece);
+        while bitboard != 0 {
+            let origin_square: Square = bitboard.trailing_zeros() as Square;
+            bitboard &= !(1 << origin_square);
+            let target_squares = piece_attacks(origin_square, context.occupied)
+                & context.targets
+                & context.check_mask
+                & context.pin_mask(origin_square);
+            ChessMove::push_targets(context, moves, piece, origin_square, target_squares);
+        }
+    }
+
+    #[inline]
+    fn push_targets(
+        context: &GenerationContext,
+        moves: &mut MoveList,
+        piece: Piece,
+        origin_square: Square,
+        mut target_squares: Bitboard,
+    ) {
+        while target_squares != 0 {
+            let target_square = target_squares.trailing_zeros() as Square;
+            target_squares &= !(1 << target_square);
+            moves.push(ChessMove::new(piece, origin_square, target_square, context.move_data(target_square)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::REFERENCE_POSITIONS;
+
+    fn legal_moves(fen: &str) -> Vec<String> {
+        let board = Board::from_fen(fen).unwrap();
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(&board, &mut moves);
+        let mut moves: Vec<String> = moves.iter().map(|chess_move| chess_move.to_coordinate_notation()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn captures_and_quiets_partition_all_moves() {
+        for position in REFERENCE_POSITIONS.iter() {
+            let board = Board::from_fen(position.fen).unwrap();
+            let (mut all, mut captures, mut quiets) = (MoveList::new(), MoveList::new(), MoveList::new());
+            ChessMove::generate_legal_moves(&board, &mut all);
+            ChessMove::generate_captures(&board, &mut captures);
+            ChessMove::generate_quiets(&board, &mut quiets);
+
+            assert_eq!(captures.len() + quiets.len(), all.len(), "{}", position.name);
+            assert!(captures.iter().all(|chess_move| chess_move.meta_data.is_capture()));
+            assert!(quiets.iter().all(|chess_move| !chess_move.meta_data.is_capture()));
+        }
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        // The e2 rook is pinned by the e8 queen and the d2 knight by the a5 bishop
+        let moves = legal_moves("4q2k/8/8/b7/8/8/3NR3/4K3 w - - 0 1");
+        assert!(moves.iter().all(|chess_move| !chess_move.starts_with("d2")));
+        let rook_moves: Vec<&String> = moves.iter().filter(|chess_move| chess_move.starts_with("e2")).collect();
+        assert_eq!(rook_moves, vec!["e2e3", "e2e4", "e2e5", "e2e6", "e2e7", "e2e8"]);
+    }
+
+    #[test]
+    fn double_check_allows_only_king_moves() {
+        let moves = legal_moves("4k3/8/8/8/1b6/5n2/R7/4K3 w - - 0 1");
+        assert!(moves.iter().all(|chess_move| chess_move.starts_with("e1")));
+        assert!(!moves.is_empty());
+    }
+
+    #[test]
+    fn en_passant_may_not_expose_the_king_along_the_rank() {
+        let moves = legal_moves("8/8/8/KPp4r/8/8/8/7k w - c6 0 2");
+        assert!(!moves.contains(&"b5c6".to_string()));
+        let moves = legal_moves("8/8/8/1Pp4r/8/K7/8/7k w - c6 0 2");
+        assert!(moves.contains(&"b5c6".to_string()));
+    }
+
+    #[test]
+    fn en_passant_can_capture_a_checking_pawn() {
+        let moves = legal_moves("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+        assert!(moves.contains(&"e4d3".to_string()));
+    }
+}
@@ -0,0 +1,133 @@
+//! Fixed-capacity move buffer.
+//!
+//! Move generation runs at every node of a search, so the generated moves are written into a
+//! stack allocated list instead of a fresh `Vec`.
+use std::ops::{Index, IndexMut};
+
+use crate::board::pieces::Piece;
+use crate::chess_moves::{ChessMove, MoveData};
+
+/// No legal chess position has more than 218 moves.
+pub const MAX_MOVES: usize = 256;
+
+const EMPTY_MOVE: ChessMove = ChessMove {
+    piece: Piece::WhitePawn,
+    origin: 0,
+    target: 0,
+    meta_data: MoveData::Normal,
+};
+
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [ChessMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [EMPTY_MOVE; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// Appends a move.
+    ///
+    /// # Panics
+    ///
+    /// If the list already holds `MAX_MOVES` moves.
+    #[inline]
+    pub fn push(&mut self, chess_move: ChessMove) {
+        self.moves[self.len] = chess_move;
+        self.len += 1;
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[ChessMove] {
+        &self.moves[..self.len]
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [ChessMove] {
+        &mut self.moves[..self.len]
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, ChessMove> {
+        self.as_slice().iter()
+    }
+
+    /// Keeps only the moves for which `keep` returns true, preserving their order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&ChessMove) -> bool) {
+        let mut kept = 0;
+        for index in 0..self.len {
+            if keep(&self.moves[index]) {
+                self.moves[kept] = self.moves[index];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Index<usize> for MoveList {
+    type Output = ChessMove;
+    fn index(&self, index: usize) -> &ChessMove {
+        &self.as_slice()[index]
+    }
+}
+
+impl IndexMut<usize> for MoveList {
+    fn index_mut(&mut self, index: usize) -> &mut ChessMove {
+        &mut self.as_mut_slice()[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a ChessMove;
+    type IntoIter = std::slice::Iter<'a, ChessMove>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_retain_and_iterate() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+        for target in 0..10 {
+            moves.push(ChessMove::new(Piece::WhiteQueen, 0, target, MoveData::Normal));
+        }
+        assert_eq!(moves.len(), 10);
+        moves.retain(|chess_move| chess_move.target % 2 == 0);
+        assert_eq!(moves.iter().map(|chess_move| chess_move.target).collect::<Vec<_>>(), vec![0, 2, 4, 6, 8]);
+        assert_eq!(moves[1].target, 2);
+        moves.clear();
+        assert!(moves.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! Compact 16-bit move encoding.
+//!
+//! A `ChessMove` carries its piece and fully classified `MoveData`, which is convenient when
+//! playing moves but wasteful when storing millions of them. `PackedMove` keeps only what is
+//! needed to identify a move within its position:
+//!
+//! | Bits  | Field                                                         |
+//! |-------|---------------------------------------------------------------|
+//! | 0-5   | Origin square                                                 |
+//! | 6-11  | Target square                                                 |
+//! | 12-13 | Promotion piece, in `Color::get_promotion_pieces` order       |
+//! | 14-15 | Special flag: normal, promotion, en passant or castling       |
+//!
+//! The moving piece, captures and double pawn pushes follow from the position, so unpacking
+//! needs the board the move was packed on.
+use std::fmt;
+
+use crate::board::square::{Square, SquareExt};
+use crate::board::Board;
+use crate::chess_moves::{ChessMove, MoveData};
+
+const SQUARE_MASK: u16 = 0b11_1111;
+const TARGET_SHIFT: u16 = 6;
+const PROMOTION_SHIFT: u16 = 12;
+const FLAG_SHIFT: u16 = 14;
+
+const FLAG_NORMAL: u16 = 0;
+const FLAG_PROMOTION: u16 = 1;
+const FLAG_EN_PASSANT: u16 = 2;
+const FLAG_CASTLING: u16 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    /// The all zero encoding, a8 to a8, which no legal move uses. Used to mark empty slots.
+    pub const NONE: PackedMove = PackedMove(0);
+
+    pub const fn from_u16(value: u16) -> PackedMove {
+        PackedMove(value)
+    }
+    pub const fn to_u16(self) -> u16 {
+        self.0
+    }
+
+    pub const fn is_none(self) -> bool {
+        self.0 == 0
+    }
+    pub const fn origin(self) -> Square {
+        (self.0 & SQUARE_MASK) as Square
+    }
+    pub const fn target(self) -> Square {
+        (self.0 >> TARGET_SHIFT & SQUARE_MASK) as Square
+    }
+    pub const fn is_promotion(self) -> bool {
+        self.flag() == FLAG_PROMOTION
+    }
+    pub const fn is_en_passant(self) -> bool {
+        self.flag() == FLAG_EN_PASSANT
+    }
+    pub const fn is_castle(self) -> bool {
+        self.flag() == FLAG_CASTLING
+    }
+
+    const fn flag(self) -> u16 {
+        self.0 >> FLAG_SHIFT
+    }
+    const fn promotion_index(self) -> usize {
+        (self.0 >> PROMOTION_SHIFT & 0b11) as usize
+    }
+
+    /// Rebuilds the full move in the position it was packed in.
+    ///
+    /// Check, checkmate and stalemate results aren't stored, so the returned move carries the
+    /// unclassified `MoveData` the move generator produces.
+    ///
+    /// # Arguments
+    /// * `board` - The position before the move is played.
+    ///
+    /// # Returns
+    /// `None` if there is no piece on the origin square, otherwise the move. The move is not
+    /// checked for legality, so a move packed on a different position may come back illegal.
+    pub fn to_chess_move(self, board: &Board) -> Option<ChessMove> {
+        let origin = self.origin();
+        let target = self.target();
+        let piece = board.get_piece_at(origin)?;
+        let is_capture = board.is_square_occupied(target);
+
+        let (piece, meta_data) = match self.flag() {
+            FLAG_CASTLING => (piece, MoveData::Castling),
+            FLAG_EN_PASSANT => (piece, MoveData::EnPassant),
+            FLAG_PROMOTION => {
+                let promotion_piece = piece.get_color().get_promotion_pieces()[self.promotion_index()];
+                let meta_data = if is_capture { MoveData::CapturePromotion } else { MoveData::Promotion };
+                (promotion_piece, meta_data)
+            }
+            _ if is_capture => (piece, MoveData::Capture),
+            _ if piece.is_pawn() && origin.get_row().abs_diff(target.get_row()) == 2 => {
+                (piece, MoveData::EnableEnPassant)
+            }
+            _ => (piece, MoveData::Normal),
+        };
+        Some(ChessMove::new(piece, origin, target, meta_data))
+    }
+}
+
+impl From<ChessMove> for PackedMove {
+    fn from(chess_move: ChessMove) -> PackedMove {
+        let meta_data = chess_move.meta_data;
+        let (flag, promotion_index) = if meta_data.is_promotion() {
+            let promotion_index = chess_move
+                .piece
+                .get_color()
+                .get_promotion_pieces()
+                .iter()
+                .position(|&piece| piece == chess_move.piece)
+                .unwrap_or(0);
+            (FLAG_PROMOTION, promotion_index as u16)
+        } else if meta_data.is_en_passant() {
+            (FLAG_EN_PASSANT, 0)
+        } else if meta_data.is_castle() {
+            (FLAG_CASTLING, 0)
+        } else {
+            (FLAG_NORMAL, 0)
+        };
+        PackedMove(
+            chess_move.origin as u16
+                | (chess_move.target as u16) << TARGET_SHIFT
+                | promotion_index << PROMOTION_SHIFT
+                | flag << FLAG_SHIFT,
+        )
+    }
+}
+
+impl ChessMove {
+    pub fn pack(self) -> PackedMove {
+        PackedMove::from(self)
+    }
+}
+
+/// Formats the move in coordinate notation, e.g. `e2e4`, with the promotion piece as `q`, `n`,
+/// `b` or `r`.
+impl fmt::Display for PackedMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.origin().to_square_string(), self.target().to_square_string())?;
+        if self.is_promotion() {
+            const PROMOTION_CHARS: [char; 4] = ['q', 'n', 'b', 'r'];
+            write!(f, "{}", PROMOTION_CHARS[self.promotion_index()])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::pieces::{Color, Piece};
+    use crate::chess_moves::MoveList;
+    use crate::perft::REFERENCE_POSITIONS;
+
+    #[test]
+    fn round_trips_every_generated_move() {
+        for position in REFERENCE_POSITIONS.iter() {
+            let board = Board::from_fen(position.fen).unwrap();
+            let mut moves = MoveList::new();
+            ChessMove::generate_legal_moves(&board, &mut moves);
+            for &chess_move in &moves {
+                let packed = chess_move.pack();
+                assert_eq!(packed.to_string(), chess_move.to_coordinate_notation());
+                let unpacked = packed.to_chess_move(&board).unwrap();
+                assert_eq!(format!("{:?}", unpacked), format!("{:?}", chess_move), "in {}", position.name);
+                assert_eq!(PackedMove::from_u16(packed.to_u16()), packed);
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_fields_in_documented_bits() {
+        // Black pawn g2 to h1 capturing and promoting to a knight
+        let board = Board::from_fen("4k3/8/8/8/8/8/6p1/4K2R b - - 0 1").unwrap();
+        let chess_move = ChessMove::new(Piece::BlackKnight, Square::G2, Square::H1, MoveData::CapturePromotion);
+        let packed = chess_move.pack();
+        assert_eq!(packed.origin(), Square::G2);
+        assert_eq!(packed.target(), Square::H1);
+        assert!(packed.is_promotion());
+        assert_eq!(packed.to_u16(), Square::G2 as u16 | (Square::H1 as u16) << 6 | 1 << 12 | 1 << 14);
+        assert_eq!(packed.to_string(), "g2h1n");
+        assert!(packed.to_chess_move(&board).unwrap().meta_data.is_capture());
+
+        for color in [Color::White, Color::Black] {
+            let chars: String = color.get_promotion_pieces().iter().map(|piece| piece.to_char().to_ascii_lowercase()).collect();
+            assert_eq!(chars, "qnbr");
+        }
+        assert!(PackedMove::NONE.is_none());
+        assert!(PackedMove::NONE.to_chess_move(&Board::empty_new()).is_none());
+    }
+}
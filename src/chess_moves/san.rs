@@ -0,0 +1,186 @@
+//! Standard Algebraic Notation export.
+//!
+//! SAN names only the moving piece and its target, adding the origin's file, rank or square
+//! just when another piece of the same type could reach the same target. That makes the
+//! notation depend on the position the move is played from.
+use crate::board::square::SquareExt;
+use crate::board::Board;
+use crate::chess_moves::{ChessMove, Disambiguity, MoveList};
+use crate::rules::{CastleType, MoveResult};
+
+impl ChessMove {
+    /// Formats the move in Standard Algebraic Notation, e.g. `Nbd7`, `exd6`, `e8=Q+` or `O-O-O#`.
+    ///
+    /// # Arguments
+    /// * `board` - The position the move is played from.
+    ///
+    /// # Returns
+    /// The move as `chess_notation_parser::from_simplified_algebraic_notation` reads it back.
+    pub fn to_san(self, board: &Board) -> String {
+        let chess_move = self.with_result_data(board);
+        let mut san = if chess_move.meta_data.is_castle() {
+            if chess_move.target.get_col() > chess_move.origin.get_col() {
+                CastleType::KingSide.to_string()
+            } else {
+                CastleType::QueenSide.to_string()
+            }
+        } else if chess_move.meta_data.is_promotion() || chess_move.piece.is_pawn() {
+            chess_move.pawn_san()
+        } else {
+            chess_move.piece_san(board)
+        };
+
+        match chess_move.meta_data.move_result() {
+            MoveResult::Check => san.push('+'),
+            MoveResult::Checkmate => san.push('#'),
+            // SAN has no stalemate suffix
+            MoveResult::Stalemate | MoveResult::None => {}
+        }
+        san
+    }
+
+    /// Returns the move with the check, checkmate or stalemate it delivers recorded in its
+    /// `MoveData`. Moves straight from the generator don't carry their result.
+    ///
+    /// # Arguments
+    /// * `board` - The position the move is played from.
+    pub fn with_result_data(&self, board: &Board) -> ChessMove {
+        let mut chess_move = *self;
+        if let MoveResult::None = chess_move.meta_data.move_result() {
+            let mut after = board.clone();
+            chess_move.make_move(&mut after);
+            let mut replies = MoveList::new();
+            ChessMove::generate_legal_moves(&after, &mut replies);
+            chess_move.meta_data.add_result_data(after.is_in_check(after.active_player), !replies.is_empty());
+        }
+        chess_move
+    }
+
+    /// Pawns are never named, a capture gives the origin file instead.
+    fn pawn_san(&self) -> String {
+        let mut san = String::with_capacity(6);
+        if self.meta_data.is_capture() {
+            san.push(self.origin.get_file());
+            san.push('x');
+        }
+        san.push_str(&self.target.to_square_string());
+        if self.meta_data.is_promotion() {
+            san.push('=');
+            san.push(self.piece.to_char().to_ascii_uppercase());
+        }
+        san
+    }
+
+    fn piece_san(&self, board: &Board) -> String {
+        let mut san = String::with_capacity(7);
+        san.push(self.piece.to_char().to_ascii_uppercase());
+
+        // This move first, then every other piece of the same type that reaches the target
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(board, &mut moves);
+        let mut candidates = vec![*self];
+        candidates.extend(moves.iter().filter(|other| {
+            other.piece == self.piece && other.target == self.target && other.origin != self.origin
+        }));
+        match ChessMove::disambiguate_from_moves(&candidates) {
+            Disambiguity::None => {}
+            Disambiguity::File(_) => san.push(self.origin.get_file()),
+            Disambiguity::Rank(_) => san.push_str(&self.origin.get_rank().to_string()),
+            Disambiguity::Square(_) => san.push_str(&self.origin.to_square_string()),
+        }
+
+        if self.meta_data.is_capture() {
+            san.push('x');
+        }
+        san.push_str(&self.target.to_square_string());
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::pieces::Color;
+    use crate::move_parser::chess_notation_parser::from_simplified_algebraic_notation;
+    use crate::perft::REFERENCE_POSITIONS;
+
+    fn san_of(board: &Board, coordinates: &str) -> String {
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(board, &mut moves);
+        moves
+            .iter()
+            .find(|chess_move| chess_move.to_coordinate_notation() == coordinates)
+            .unwrap()
+            .to_san(board)
+    }
+
+    /// Checks every move below `board` parses back from its SAN to the same move.
+    fn assert_round_trips(board: &mut Board, depth: u8) {
+        if depth == 0 {
+            return;
+        }
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(board, &mut moves);
+        for &chess_move in &moves {
+            let san = chess_move.to_san(board);
+            let proto_move = from_simplified_algebraic_notation(&san, board.active_player)
+                .unwrap_or_else(|error| panic!("{} in {}: {:?}", san, board.to_fen(), error));
+            let parsed = ChessMove::new_from_proto(board, proto_move)
+                .unwrap_or_else(|error| panic!("{} in {}: {:?}", san, board.to_fen(), error));
+            assert_eq!(parsed.to_coordinate_notation(), chess_move.to_coordinate_notation(), "{}", san);
+
+            let undo = chess_move.make_reversible_move(board);
+            assert_round_trips(board, depth - 1);
+            let _ = chess_move.undo_move(board, undo);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_parser() {
+        for position in REFERENCE_POSITIONS.iter() {
+            let mut board = Board::from_fen(position.fen).unwrap();
+            assert_round_trips(&mut board, 2);
+        }
+    }
+
+    #[test]
+    fn formats_special_moves() {
+        let kiwipete = Board::from_fen(REFERENCE_POSITIONS[1].fen).unwrap();
+        assert_eq!(san_of(&kiwipete, "e1g1"), "O-O");
+        assert_eq!(san_of(&kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san_of(&kiwipete, "e5f7"), "Nxf7");
+        assert_eq!(san_of(&kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san_of(&kiwipete, "e2a6"), "Bxa6");
+
+        let board = Board::from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        assert_eq!(san_of(&board, "e4d3"), "exd3");
+
+        // Both promotions check, the queen's mates
+        let board = Board::from_fen("k7/2P5/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(san_of(&board, "c7c8q"), "c8=Q#");
+        assert_eq!(san_of(&board, "c7c8r"), "c8=R#");
+        assert_eq!(san_of(&board, "c7c8n"), "c8=N");
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K6R w - - 0 1").unwrap();
+        assert_eq!(san_of(&board, "h1h7"), "Rh7+");
+    }
+
+    #[test]
+    fn disambiguates_minimally() {
+        // Knights on b1 and f1 reach d2 from different files
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(san_of(&board, "b1d2"), "Nbd2");
+        // Rooks on a1 and a5 share a file
+        let board = Board::from_fen("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(san_of(&board, "a1a3"), "R1a3");
+        assert_eq!(san_of(&board, "a5a3"), "R5a3");
+        // Queens on a1, a5 and e1 all reach e5, only the a1 queen needs its whole square
+        let board = Board::from_fen("6k1/8/8/Q7/8/8/8/Q3Q2K w - - 0 1").unwrap();
+        assert_eq!(san_of(&board, "a1e5"), "Qa1e5");
+        assert_eq!(san_of(&board, "a5e5"), "Q5e5");
+        assert_eq!(san_of(&board, "e1e5"), "Qee5");
+        // Black pieces are written in upper case too
+        let board = Board::from_fen("r6r/4k3/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.active_player, Color::Black);
+        assert_eq!(san_of(&board, "a8d8"), "Rad8");
+    }
+}
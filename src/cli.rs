@@ -0,0 +1,289 @@
+//! Command line subcommands.
+//!
+//! Running the binary without arguments starts the game as usual. The subcommands here are
+//! developer tools that run to completion and exit.
+use std::time::Instant;
+
+use crate::ai::book::{BookBuilder, BookFilter, OpeningBook};
+use crate::ai::eval::{EvalWeights, Evaluator, StandardEvaluator};
+use crate::ai::syzygy::Tablebases;
+use crate::board::fen::STARTING_FEN;
+use crate::board::Board;
+use crate::engine::{uci, xboard};
+use crate::perft;
+use crate::pgn::reader::read_games;
+use crate::pgn::writer::write_games;
+use crate::pgn::{GameRecord, PgnError, PgnErrorKind};
+
+const USAGE: &str = "Usage:
+  rusty_chess                         Start the game
+  rusty_chess perft <depth> [<fen>]   Count leaf nodes per root move
+  rusty_chess perft suite [<depth>]   Check the reference positions up to <depth>
+  rusty_chess eval [--trace] [--weights <file>] [<fen>]
+                                      Evaluate a position, per term with --trace
+  rusty_chess book build [--min-games <n>] [--min-win-rate <rate>] [--max-ply <n>] <pgn> <book>
+                                      Make a Polyglot book from the games of a PGN file
+  rusty_chess book probe <book> [<fen>]
+                                      List the book moves of a position
+  rusty_chess pgn list <pgn>          List the games of a PGN file with their results
+  rusty_chess pgn export <pgn>        Rewrite the games of a PGN file in the export format
+  rusty_chess tb probe [--path <dirs>] [<fen>]
+                                      Look a position up in the Syzygy tablebases found in
+                                      <dirs>, by default $SYZYGY_PATH
+  rusty_chess uci                     Run as a UCI engine on stdin and stdout
+  rusty_chess xboard                  Run as a CECP (XBoard) engine on stdin and stdout";
+
+/// Runs the subcommand named by `args`, the command line without the program name.
+///
+/// # Returns
+///
+/// `None` if `args` names no subcommand and the game should start, otherwise the subcommand's
+/// result, with an error message to print on failure.
+pub fn run(args: &[String]) -> Option<Result<(), String>> {
+    let (command, rest) = args.split_first()?;
+    Some(match command.as_str() {
+        "perft" => run_perft(rest),
+        "eval" => run_eval(rest),
+        "book" => run_book(rest),
+        "pgn" => run_pgn(rest),
+        "tb" => run_tb(rest),
+        "uci" => {
+            uci::run(std::io::stdin().lock(), std::io::stdout());
+            Ok(())
+        }
+        "xboard" => {
+            xboard::run(std::io::BufReader::new(std::io::stdin()), std::io::stdout());
+            Ok(())
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command '{}'\n{}", command, USAGE)),
+    })
+}
+
+fn parse_depth(depth: &str) -> Result<u8, String> {
+    depth.parse::<u8>().map_err(|_| format!("Invalid depth '{}'", depth))
+}
+
+fn run_perft(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("suite") => {
+            let max_depth = match args.get(1) {
+                Some(depth) => parse_depth(depth)?,
+                None => 4,
+            };
+            run_perft_suite(max_depth)
+        }
+        Some(depth) => {
+            let depth = parse_depth(depth)?;
+            let fen = if args.len() > 1 { args[1..].join(" ") } else { STARTING_FEN.to_string() };
+            run_perft_divide(&fen, depth)
+        }
+        None => Err(USAGE.to_string()),
+    }
+}
+
+fn run_perft_divide(fen: &str, depth: u8) -> Result<(), String> {
+    let mut board = Board::from_fen(fen).map_err(|error| error.to_string())?;
+    let start = Instant::now();
+    let mut divide = perft::perft_divide(&mut board, depth);
+    let elapsed = start.elapsed();
+
+    divide.sort_by_key(|(chess_move, _)| chess_move.to_coordinate_notation());
+    for (chess_move, nodes) in &divide {
+        println!("{}: {}", chess_move.to_coordinate_notation(), nodes);
+    }
+    let nodes: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Moves: {}", divide.len());
+    println!("Nodes: {}", nodes);
+    println!("Time:  {} ms ({:.0} nps)", elapsed.as_millis(), nodes as f64 / elapsed.as_secs_f64().max(1e-9));
+    Ok(())
+}
+
+fn run_perft_suite(max_depth: u8) -> Result<(), String> {
+    let mut failures = 0;
+    for result in perft::run_reference_suite(max_depth, u64::MAX) {
+        println!(
+            "{:<20} depth {} {:>12} {:>12} {:>8} ms {}",
+            result.name,
+            result.depth,
+            result.nodes,
+            result.expected,
+            result.elapsed.as_millis(),
+            if result.passed() { "ok" } else { "FAILED" }
+        );
+        if !result.passed() {
+            failures += 1;
+        }
+    }
+    match failures {
+        0 => Ok(()),
+        _ => Err(format!("{} perft result(s) did not match", failures)),
+    }
+}
+
+fn run_eval(args: &[String]) -> Result<(), String> {
+    let mut trace = false;
+    let mut weights = EvalWeights::default();
+    let mut fen_words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--weights" => {
+                let path = args.next().ok_or("Missing weights file")?;
+                weights = EvalWeights::load(path)?;
+            }
+            word => fen_words.push(word),
+        }
+    }
+    let fen = if fen_words.is_empty() { STARTING_FEN.to_string() } else { fen_words.join(" ") };
+    let board = Board::from_fen(&fen).map_err(|error| error.to_string())?;
+
+    let evaluator = StandardEvaluator::new(weights);
+    if trace {
+        println!("{}", evaluator.trace(&board));
+    } else {
+        println!("{}", evaluator.evaluate(&board));
+    }
+    Ok(())
+}
+
+fn run_book(args: &[String]) -> Result<(), String> {
+    match args.split_first() {
+        Some((command, rest)) if command == "build" => run_book_build(rest),
+        Some((command, rest)) if command == "probe" => run_book_probe(rest),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn run_book_build(args: &[String]) -> Result<(), String> {
+    let mut filter = BookFilter::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--min-games" => filter.min_games = value()?.parse().map_err(|_| "Invalid game count")?,
+            "--min-win-rate" => filter.min_win_rate = value()?.parse().map_err(|_| "Invalid win rate")?,
+            "--max-ply" => filter.max_ply = value()?.parse().map_err(|_| "Invalid ply count")?,
+            path => paths.push(path),
+        }
+    }
+    let [pgn_path, book_path] = paths[..] else {
+        return Err(USAGE.to_string());
+    };
+    let text = std::fs::read_to_string(pgn_path).map_err(|error| format!("{}: {}", pgn_path, error))?;
+    let mut builder = BookBuilder::new(filter);
+    let games = builder.add_pgn(&text);
+    let book = builder.build();
+    book.save(book_path)?;
+    println!("{} games, {} book entries written to {}", games, book.len(), book_path);
+    Ok(())
+}
+
+fn run_book_probe(args: &[String]) -> Result<(), String> {
+    let (path, fen_words) = args.split_first().ok_or(USAGE)?;
+    let fen = if fen_words.is_empty() { STARTING_FEN.to_string() } else { fen_words.join(" ") };
+    let board = Board::from_fen(&fen).map_err(|error| error.to_string())?;
+    let book = OpeningBook::load(path)?;
+    let moves = book.moves(&board);
+    let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+    for (chess_move, weight) in moves {
+        let share = weight as f64 * 100.0 / total.max(1) as f64;
+        println!("{} {:>6} {:>5.1}%", chess_move.to_coordinate_notation(), weight, share);
+    }
+    Ok(())
+}
+
+fn run_pgn(args: &[String]) -> Result<(), String> {
+    let [command, path] = args else {
+        return Err(USAGE.to_string());
+    };
+    if !matches!(command.as_str(), "list" | "export") {
+        return Err(USAGE.to_string());
+    }
+    let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let games = read_games(&text);
+    if games.is_empty() {
+        let error = PgnError { line: 1, column: 1, kind: PgnErrorKind::NoGame };
+        return Err(format!("{}: {}", path, error));
+    }
+    // Games that don't read are reported and skipped, so one bad game doesn't lose the rest
+    let game_count = games.len();
+    let mut records = Vec::new();
+    for (index, game) in games.into_iter().enumerate() {
+        match game {
+            Ok(record) => records.push((index + 1, record)),
+            Err(error) => eprintln!("{}: game {}: {}", path, index + 1, error),
+        }
+    }
+    if records.is_empty() {
+        return Err(format!("{}: no game could be read, {} failed", path, game_count));
+    }
+    if command == "list" {
+        for (number, record) in &records {
+            println!("{}", describe_game(*number, record));
+        }
+    } else {
+        let records: Vec<GameRecord> = records.into_iter().map(|(_, record)| record).collect();
+        print!("{}", write_games(&records));
+    }
+    Ok(())
+}
+
+/// One line about a game: its players, result and length, and the position it ends in.
+fn describe_game(number: usize, record: &GameRecord) -> String {
+    let player = |tag| record.tag(tag).unwrap_or("?");
+    let termination = record
+        .outcome()
+        .map_or(String::new(), |outcome| format!(" by {}", outcome.termination.to_pgn_tag()));
+    format!(
+        "{}. {} - {} {}{}, {} plies, ending in {}",
+        number,
+        player("White"),
+        player("Black"),
+        record.result.to_pgn(),
+        termination,
+        record.mainline_moves().len(),
+        record.final_position().to_fen()
+    )
+}
+
+fn run_tb(args: &[String]) -> Result<(), String> {
+    let Some((command, mut args)) = args.split_first() else {
+        return Err(USAGE.to_string());
+    };
+    if command != "probe" {
+        return Err(USAGE.to_string());
+    }
+    let paths = match args {
+        [flag, paths, rest @ ..] if flag == "--path" => {
+            args = rest;
+            paths.clone()
+        }
+        _ => std::env::var("SYZYGY_PATH").map_err(|_| "No tablebases, set --path or SYZYGY_PATH")?,
+    };
+    let fen = if args.is_empty() { STARTING_FEN.to_string() } else { args.join(" ") };
+    let board = Board::from_fen(&fen).map_err(|error| error.to_string())?;
+    let tablebases = Tablebases::open(&paths)?;
+    if !tablebases.covers(&board) {
+        return Err(format!("The {} tablebases found don't hold this position", tablebases.len()));
+    }
+    let missing = || String::from("A table this position needs is missing");
+    println!("WDL: {:?}", tablebases.probe_wdl(&board).ok_or_else(missing)?);
+    match tablebases.probe_dtz(&board) {
+        Some(dtz) => println!("DTZ: {}", dtz),
+        None => println!("DTZ: no table"),
+    }
+    let mut moves = tablebases.rank_root_moves(&board, false).ok_or_else(missing)?;
+    moves.sort_by_key(|root_move| -root_move.rank);
+    for root_move in moves {
+        let dtz = root_move.dtz.map_or(String::new(), |dtz| format!(" dtz {}", dtz));
+        println!("{} {:?}{}", root_move.chess_move.to_coordinate_notation(), root_move.wdl, dtz);
+    }
+    Ok(())
+}
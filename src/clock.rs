@@ -9,6 +9,9 @@ pub struct ChessClock{
     white_time_left: Duration,
     black_total_time: Duration,
     black_time_left: Duration,
+    /// Added to a player's time after each of their moves.
+    white_increment: Duration,
+    black_increment: Duration,
     instant: Instant,
 }
 
@@ -21,6 +24,8 @@ impl ChessClock{
             white_time_left: Duration::from_secs(0),
             black_total_time: Duration::from_secs(0),
             black_time_left: Duration::from_secs(0),
+            white_increment: Duration::ZERO,
+            black_increment: Duration::ZERO,
             instant: Instant::now()
         }
     }
@@ -41,6 +46,8 @@ impl Timer for ChessClock{
                 white_time_left: white_time,
                 black_total_time: black_time,
                 black_time_left: black_time,
+                white_increment: time_controls.time_per_move_white,
+                black_increment: time_controls.time_per_move_black,
                 instant: Instant::now()
             })
         }
@@ -56,8 +63,8 @@ impl Timer for ChessClock{
     fn stop(&mut self) {
         if self.running{
             match self.active_player {
-                Color::White => self.white_time_left -= self.instant.elapsed(),
-                Color::Black => self.black_time_left -= self.instant.elapsed(),
+                Color::White => self.white_time_left = self.white_time_left.saturating_sub(self.instant.elapsed()),
+                Color::Black => self.black_time_left = self.black_time_left.saturating_sub(self.instant.elapsed()),
             }
         }
         self.running = false;       
@@ -65,9 +72,14 @@ impl Timer for ChessClock{
 
     fn switch_clock(&mut self) {
         if self.running {
-            match self.active_player {
-                Color::White => self.white_time_left -= self.instant.elapsed(),
-                Color::Black => self.black_time_left -= self.instant.elapsed(),
+            let (time_left, increment) = match self.active_player {
+                Color::White => (&mut self.white_time_left, self.white_increment),
+                Color::Black => (&mut self.black_time_left, self.black_increment),
+            };
+            *time_left = time_left.saturating_sub(self.instant.elapsed());
+            // A fallen flag stays down
+            if !time_left.is_zero() {
+                *time_left += increment;
             }
             self.instant = Instant::now();
         }
@@ -109,7 +121,8 @@ impl Timer for ChessClock{
         };
         
         if self.running && self.active_player == color{
-            time_left - self.instant.elapsed()
+            // A flag that has fallen shows no time left
+            time_left.saturating_sub(self.instant.elapsed())
         }else{
             time_left
         }
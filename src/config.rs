@@ -1,4 +1,7 @@
+use crate::ai::book::{BookSelection, BookSettings};
+use crate::ai::skill::SkillLevel;
 use crate::log::LogOutput;
+use crate::rules::TimeControls;
 use crate::ui::UIType;
 
 use std::fs::File;
@@ -17,12 +20,32 @@ pub enum ConfigResult {
 pub struct Config {
     pub ui_type: UIType,
     pub log_output: LogOutput,
+    /// Strength of the computer opponent, `ai_skill: 5` or `ai_skill: 1500 elo`.
+    pub ai_skill: SkillLevel,
+    /// Whether the computer opponent thinks on the player's time, `ponder: false` on a shared machine.
+    pub ponder: bool,
+    /// Clock for games against the computer, in seconds plus increment like `time_control: 300+2`.
+    /// Games are untimed without it.
+    pub time_control: Option<TimeControls>,
+    /// PGN file the games against the computer are appended to, `pgn_file: games.pgn`.
+    pub pgn_file: Option<String>,
+    /// Polyglot book the computer opponent opens from, `book_file: book.bin`.
+    pub book_file: Option<String>,
+    /// How the book is played, `book_best_move: true` for its best move only and `book_depth: 10`
+    /// to leave it after 10 plies.
+    pub book_settings: BookSettings,
 }
 
 fn default() -> Config {
     Config {
         ui_type: UIType::Terminal,
         log_output: LogOutput::InitStdout,
+        ai_skill: SkillLevel::MAX,
+        ponder: true,
+        time_control: None,
+        pgn_file: None,
+        book_file: None,
+        book_settings: BookSettings::default(),
     }
 }
 pub fn parse_config() -> (Config, ConfigResult) {
@@ -117,6 +140,34 @@ fn parse_line<'a>( config: &mut Config, variable: &'a str, value: &'a str, out_p
             "0" | "false" => config.ui_type = UIType::GUI,
             _ => {}
         },
+        "ai_skill" => config.ai_skill = value.parse()?,
+        "ponder" => match value.to_ascii_lowercase().as_str() {
+            "1" | "true" => config.ponder = true,
+            "0" | "false" => config.ponder = false,
+            _ => return Err(format!("Invalid ponder value '{}', expected true or false", value)),
+        },
+        "time_control" => match value.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => config.time_control = None,
+            _ => config.time_control = Some(value.parse()?),
+        },
+        "pgn_file" => match value.trim() {
+            "" => config.pgn_file = None,
+            path => config.pgn_file = Some(path.to_string()),
+        },
+        "book_file" => match value.trim() {
+            "" => config.book_file = None,
+            path => config.book_file = Some(path.to_string()),
+        },
+        "book_best_move" => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" => config.book_settings.selection = BookSelection::Best,
+            "0" | "false" => config.book_settings.selection = BookSelection::WeightedRandom,
+            _ => return Err(format!("Invalid book_best_move value '{}', expected true or false", value)),
+        },
+        "book_depth" => match value.trim().parse::<u32>() {
+            // 0 plays from the book for as long as it knows the position
+            Ok(plies) => config.book_settings.max_depth = (plies > 0).then_some(plies),
+            Err(_) => return Err(format!("Invalid book_depth '{}', expected a number of plies", value)),
+        },
         "test" => match value.to_ascii_lowercase().as_str() {
             "true" | "1" => return Ok(()),
             _ => {
@@ -207,6 +258,74 @@ mod tests {
         matches!(cfg.ui_type, UIType::Terminal);
     }
 
+    #[test]
+    fn parse_line_sets_ai_skill() {
+        let mut cfg = default();
+        let mut base_path = DEFAULT_LOG_PATH;
+        let mut path_ref = &mut base_path;
+        assert_eq!(cfg.ai_skill, SkillLevel::MAX);
+        parse_line(&mut cfg, "ai_skill", " 5", &mut path_ref).unwrap();
+        assert_eq!(cfg.ai_skill.level(), 5);
+        parse_line(&mut cfg, "AI_Skill", "1600 elo", &mut path_ref).unwrap();
+        assert_eq!(cfg.ai_skill.level(), 10);
+        assert!(parse_line(&mut cfg, "ai_skill", "strong", &mut path_ref).is_err());
+    }
+
+    #[test]
+    fn parse_line_sets_ponder() {
+        let mut cfg = default();
+        let mut base_path = DEFAULT_LOG_PATH;
+        let mut path_ref = &mut base_path;
+        assert!(cfg.ponder);
+        parse_line(&mut cfg, "ponder", "false", &mut path_ref).unwrap();
+        assert!(!cfg.ponder);
+        parse_line(&mut cfg, "ponder", "1", &mut path_ref).unwrap();
+        assert!(cfg.ponder);
+        assert!(parse_line(&mut cfg, "ponder", "sometimes", &mut path_ref).is_err());
+    }
+
+    #[test]
+    fn parse_line_sets_time_control() {
+        let mut cfg = default();
+        let mut base_path = DEFAULT_LOG_PATH;
+        let mut path_ref = &mut base_path;
+        assert!(cfg.time_control.is_none());
+        parse_line(&mut cfg, "time_control", " 300+2", &mut path_ref).unwrap();
+        assert_eq!(cfg.time_control.unwrap().to_pgn_tag(), "300+2");
+        parse_line(&mut cfg, "time_control", "none", &mut path_ref).unwrap();
+        assert!(cfg.time_control.is_none());
+        assert!(parse_line(&mut cfg, "time_control", "5 minutes", &mut path_ref).is_err());
+    }
+
+    #[test]
+    fn parse_line_sets_pgn_file() {
+        let mut cfg = default();
+        let mut base_path = DEFAULT_LOG_PATH;
+        let mut path_ref = &mut base_path;
+        assert!(cfg.pgn_file.is_none());
+        parse_line(&mut cfg, "pgn_file", " games.pgn", &mut path_ref).unwrap();
+        assert_eq!(cfg.pgn_file.as_deref(), Some("games.pgn"));
+        parse_line(&mut cfg, "pgn_file", "", &mut path_ref).unwrap();
+        assert!(cfg.pgn_file.is_none());
+    }
+
+    #[test]
+    fn parse_line_sets_the_book() {
+        let mut cfg = default();
+        let mut base_path = DEFAULT_LOG_PATH;
+        let mut path_ref = &mut base_path;
+        assert_eq!(cfg.book_settings, BookSettings::default());
+        parse_line(&mut cfg, "book_file", " book.bin", &mut path_ref).unwrap();
+        parse_line(&mut cfg, "book_best_move", " true", &mut path_ref).unwrap();
+        parse_line(&mut cfg, "book_depth", " 12", &mut path_ref).unwrap();
+        assert_eq!(cfg.book_file.as_deref(), Some("book.bin"));
+        assert_eq!(cfg.book_settings, BookSettings { selection: BookSelection::Best, max_depth: Some(12) });
+        parse_line(&mut cfg, "book_depth", "0", &mut path_ref).unwrap();
+        assert_eq!(cfg.book_settings.max_depth, None);
+        assert!(parse_line(&mut cfg, "book_best_move", "often", &mut path_ref).is_err());
+        assert!(parse_line(&mut cfg, "book_depth", "-1", &mut path_ref).is_err());
+    }
+
     #[test]
     fn parse_line_test_false_returns_error() {
         let mut cfg = default();
@@ -0,0 +1,260 @@
+//! Engine front-ends.
+//!
+//! Chess GUIs and tournament managers run engines as child processes and talk to them over
+//! stdin and stdout. This module holds what the protocols share: the limits a search is started
+//! with, the progress it reports, and a search running on its own thread so the protocol can
+//! keep answering commands and stop it at any time.
+pub mod uci;
+pub mod xboard;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::ai::ChessAI;
+use crate::board::pieces::Color;
+use crate::board::square::{Square, SquareExt};
+use crate::board::Board;
+use crate::chess_moves::ChessMove;
+use crate::move_parser::chess_notation_parser::from_long_algebraic_notation;
+use crate::rules::draw::PositionHistory;
+use crate::rules::{TimeControls, Timer};
+
+/// Moves left in the game assumed when the time control doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// Time kept in reserve on every move for latency, unless the front-end sets its own.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+/// The hard limit allows this many times the soft limit, if the clock has room.
+const HARD_LIMIT_FACTOR: u32 = 4;
+
+/// What a search may spend. Every limit that is set applies, the first one reached ends it.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Exactly this long per move.
+    pub move_time: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Duration,
+    pub black_increment: Duration,
+    /// Moves until the next time control, `None` if the rest of the game must be played in
+    /// the time left.
+    pub moves_to_go: Option<u32>,
+    /// Search until stopped.
+    pub infinite: bool,
+    /// Time kept in reserve on every move for GUI and process latency, taken off the clock
+    /// before the budget is worked out.
+    pub move_overhead: Duration,
+}
+
+/// How long a search may think.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBudget {
+    /// No new iteration starts after this, stretched or shrunk by how the search is going.
+    pub soft: Duration,
+    /// The search is stopped here wherever it is.
+    pub hard: Duration,
+}
+
+impl SearchLimits {
+    /// Limits for thinking on a game clock: the time each player has left on `clock`, and the
+    /// increment from `time_controls`. Any fixed move time is dropped for the clock.
+    pub fn with_clock(self, clock: &impl Timer, time_controls: TimeControls) -> SearchLimits {
+        SearchLimits {
+            move_time: None,
+            white_time: Some(clock.get_player_time_left(Color::White)),
+            black_time: Some(clock.get_player_time_left(Color::Black)),
+            white_increment: time_controls.time_per_move_white,
+            black_increment: time_controls.time_per_move_black,
+            moves_to_go: None,
+            ..self
+        }
+    }
+
+    /// How long `color` should think on this move, `None` when no time limit applies.
+    ///
+    /// A fixed move time is both limits. On a clock the soft limit is an even share of the
+    /// time left over the moves to go plus most of the increment, at most half the time left,
+    /// and the hard limit a few times that, at most three quarters of the time left.
+    pub fn time_budget(&self, color: Color) -> Option<TimeBudget> {
+        let at_least = Duration::from_millis(1);
+        if let Some(move_time) = self.move_time {
+            let move_time = move_time.saturating_sub(self.move_overhead).max(at_least);
+            return Some(TimeBudget { soft: move_time, hard: move_time });
+        }
+        let (time_left, increment) = match color {
+            Color::White => (self.white_time?, self.white_increment),
+            Color::Black => (self.black_time?, self.black_increment),
+        };
+        let available = time_left.saturating_sub(self.move_overhead).max(at_least);
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let soft = (available / moves_to_go + increment * 3 / 4).min(available / 2).max(at_least);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(available * 3 / 4).max(soft);
+        Some(TimeBudget { soft, hard })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Advantage of the side to move, in hundredths of a pawn.
+    Centipawns(i32),
+    /// Mate in this many moves, negative when the side to move is getting mated.
+    Mate(i32),
+}
+
+/// Progress of a search, reported after each completed iteration.
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: u8,
+    /// Rank of the root move this line starts with, 1 for the best.
+    pub multi_pv: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// How full the transposition table is, in permille.
+    pub hashfull: u32,
+    /// The principal variation, the line the search expects to be played.
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchInfo {
+    pub fn nodes_per_second(&self) -> u64 {
+        (self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-3)) as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchResult {
+    /// `None` only when the position has no legal move.
+    pub best_move: Option<ChessMove>,
+    /// The reply expected to the best move, to think about on the opponent's time.
+    pub ponder_move: Option<ChessMove>,
+}
+
+/// Flags a running search shares with the thread that started it.
+#[derive(Debug, Default)]
+pub struct SearchSignals {
+    /// Set to end the search as soon as possible.
+    pub stop: AtomicBool,
+    /// Set while searching on the opponent's time. Time limits only apply once it's cleared.
+    pub pondering: AtomicBool,
+}
+
+pub enum SearchEvent {
+    Info(SearchInfo),
+    /// The search has ended. Sent exactly once, and last.
+    Finished(SearchResult),
+}
+
+/// A search running on its own thread.
+pub struct BackgroundSearch {
+    signals: Arc<SearchSignals>,
+    handle: JoinHandle<()>,
+}
+
+impl BackgroundSearch {
+    /// Starts searching `board`.
+    ///
+    /// Infinite and ponder searches hold their result back until stopped, or until the ponder
+    /// move is played, as both protocols require.
+    ///
+    /// # Arguments
+    /// * `ai` - The player searching.
+    /// * `board` - The position to search.
+    /// * `history` - The positions of the game leading to `board`.
+    /// * `limits` - When to stop.
+    /// * `ponder` - Whether this search runs on the opponent's time.
+    /// * `report` - Receives progress and the result, on the search thread.
+    pub fn start(
+        ai: ChessAI,
+        board: Board,
+        history: PositionHistory,
+        limits: SearchLimits,
+        ponder: bool,
+        mut report: impl FnMut(SearchEvent) + Send + 'static,
+    ) -> BackgroundSearch {
+        let signals = Arc::new(SearchSignals::default());
+        signals.pondering.store(ponder, Ordering::SeqCst);
+        let thread_signals = Arc::clone(&signals);
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let outcome = ai.search(&board, &history, &limits, &thread_signals, &mut |info| {
+                report(SearchEvent::Info(info))
+            });
+            // A weakened AI takes its time over a move it has to play, within its time budget
+            if !ponder && !limits.infinite {
+                let max_wait = limits.time_budget(board.active_player).map_or(Duration::MAX, |budget| budget.soft);
+                ai.pause(start, max_wait, &thread_signals);
+            }
+            let result = SearchResult {
+                best_move: outcome.best_move,
+                ponder_move: outcome.pv.get(1).copied(),
+            };
+            while (limits.infinite || thread_signals.pondering.load(Ordering::SeqCst))
+                && !thread_signals.stop.load(Ordering::SeqCst)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+            report(SearchEvent::Finished(result));
+        });
+        BackgroundSearch { signals, handle }
+    }
+
+    /// The opponent played the expected move, from now on the search is on our own time.
+    pub fn ponder_hit(&self) {
+        self.signals.pondering.store(false, Ordering::SeqCst);
+    }
+
+    /// Stops the search and waits for it to report its result.
+    pub fn stop(self) {
+        self.signals.stop.store(true, Ordering::SeqCst);
+        self.wait();
+    }
+
+    /// Waits for the search to end by itself.
+    pub fn wait(self) {
+        let _ = self.handle.join();
+    }
+}
+
+/// Parses a FEN the GUI sent, refusing positions [`Board::validate`] finds illegal.
+///
+/// Move generation and the search assume a reachable position, so a board with a missing king
+/// or a pawn on the back rank must never reach them.
+pub fn parse_legal_fen(fen: &str) -> Result<Board, String> {
+    let board = Board::from_fen(fen).map_err(|error| error.to_string())?;
+    let errors = board.validate();
+    if !errors.is_empty() {
+        let reasons: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        return Err(reasons.join(", "));
+    }
+    Ok(board)
+}
+
+pub fn parse_square(name: &str) -> Option<Square> {
+    Square::SQUARES.iter().position(|&square| square == name).map(|index| index as Square)
+}
+
+/// Parses a move in the coordinate notation both protocols use against `board`.
+///
+/// Coordinate notation leaves out the piece and writes castling as the king's move, so the
+/// piece is looked up on the board and the move rewritten in long algebraic notation, e.g.
+/// `e7e8q` as `Pe7e8=Q` and `e1g1` as `Ke1g1`.
+pub fn parse_coordinate_move(board: &mut Board, notation: &str) -> Result<ChessMove, String> {
+    let invalid = || format!("Invalid move '{}'", notation);
+    if !notation.is_ascii() || !(4..=5).contains(&notation.len()) {
+        return Err(invalid());
+    }
+    let origin = parse_square(&notation[0..2]).ok_or_else(invalid)?;
+    let piece = board.get_piece_at(origin).ok_or_else(invalid)?;
+    let mut long_algebraic = format!("{}{}", piece.to_char().to_ascii_uppercase(), &notation[0..4]);
+    if let Some(promotion) = notation.chars().nth(4) {
+        long_algebraic.push('=');
+        long_algebraic.push(promotion.to_ascii_uppercase());
+    }
+
+    let proto_move = from_long_algebraic_notation(&long_algebraic, board.active_player).map_err(|_| invalid())?;
+    ChessMove::new_from_proto(board, proto_move).map_err(|error| format!("Illegal move '{}': {:?}", notation, error))
+}
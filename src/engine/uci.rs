@@ -0,0 +1,559 @@
+//! Universal Chess Interface (UCI).
+//!
+//! The GUI sends one command per line and the engine answers on stdout. The engine keeps no
+//! game of its own: every search is preceded by a `position` command giving the start position
+//! and all moves played since, in coordinate notation such as `e2e4` or `e7e8q`.
+//!
+//! Searches run in the background so `isready`, `stop` and `ponderhit` are answered while
+//! thinking. Progress is streamed as `info` lines and every `go` is answered by exactly one
+//! `bestmove`.
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::ai::book::{BookSelection, BookSettings};
+use crate::ai::eval::{EvalWeights, StandardEvaluator};
+use crate::ai::skill::SkillLevel;
+use crate::ai::syzygy::Tablebases;
+use crate::ai::tt::MAX_HASH_MB;
+use crate::ai::ChessAI;
+use crate::board::fen::STARTING_FEN;
+use crate::board::Board;
+use crate::engine::{
+    parse_coordinate_move, parse_legal_fen, parse_square, BackgroundSearch, Score, SearchEvent, SearchInfo, SearchLimits, SearchResult,
+    DEFAULT_MOVE_OVERHEAD,
+};
+use crate::rules::draw::PositionHistory;
+
+const ENGINE_NAME: &str = "Rusty Chess";
+const ENGINE_AUTHOR: &str = "the Rusty Chess developers";
+
+/// Longest `Move Overhead` accepted, in milliseconds.
+const MAX_MOVE_OVERHEAD: u64 = 5000;
+
+/// Options the engine announces in answer to `uci`.
+const OPTIONS: [&str; 11] = [
+    "option name Hash type spin default 16 min 1 max 4096",
+    "option name Threads type spin default 1 min 1 max 256",
+    "option name MultiPV type spin default 1 min 1 max 256",
+    "option name Ponder type check default false",
+    "option name Move Overhead type spin default 30 min 0 max 5000",
+    "option name EvalWeights type string default <empty>",
+    "option name BookFile type string default <empty>",
+    "option name BookBestMove type check default false",
+    "option name BookDepth type spin default 0 min 0 max 1000",
+    "option name SyzygyPath type string default <empty>",
+    "option name Skill Level type spin default 20 min 0 max 20",
+];
+
+/// The state of a UCI session.
+pub struct UciEngine<W: Write + Send + 'static> {
+    board: Board,
+    history: PositionHistory,
+    output: Arc<Mutex<W>>,
+    ai: ChessAI,
+    search: Option<BackgroundSearch>,
+    move_overhead: Duration,
+    /// The `BookFile` loaded, reloaded when the book settings change.
+    book_file: String,
+    book_settings: BookSettings,
+}
+
+/// Writes one line of output. Searches write from their own thread, so each line is written
+/// and flushed under the lock.
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    if let Ok(mut output) = output.lock() {
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+}
+
+/// Formats a search report as an `info` line.
+fn info_line(info: &SearchInfo) -> String {
+    let score = match info.score {
+        Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+        Score::Mate(moves) => format!("mate {}", moves),
+    };
+    let mut line = format!(
+        "info depth {} multipv {} score {} nodes {} nps {} hashfull {} time {}",
+        info.depth,
+        info.multi_pv,
+        score,
+        info.nodes,
+        info.nodes_per_second(),
+        info.hashfull,
+        info.elapsed.as_millis()
+    );
+    if !info.pv.is_empty() {
+        line.push_str(" pv");
+        for chess_move in &info.pv {
+            line.push(' ');
+            line.push_str(&chess_move.to_coordinate_notation());
+        }
+    }
+    line
+}
+
+fn bestmove_line(result: &SearchResult) -> String {
+    match (result.best_move, result.ponder_move) {
+        (Some(best_move), Some(ponder_move)) => format!(
+            "bestmove {} ponder {}",
+            best_move.to_coordinate_notation(),
+            ponder_move.to_coordinate_notation()
+        ),
+        (Some(best_move), None) => format!("bestmove {}", best_move.to_coordinate_notation()),
+        // No legal move, the GUI expects a null move
+        (None, _) => String::from("bestmove 0000"),
+    }
+}
+
+fn parse_millis(value: Option<&str>) -> Result<Duration, String> {
+    let value = value.ok_or("Missing time value")?;
+    // Some GUIs send negative times once the clock has run out
+    let millis: i64 = value.parse().map_err(|_| format!("Invalid time '{}'", value))?;
+    Ok(Duration::from_millis(millis.max(0) as u64))
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&str>) -> Result<T, String> {
+    let value = value.ok_or("Missing number")?;
+    value.parse().map_err(|_| format!("Invalid number '{}'", value))
+}
+
+impl<W: Write + Send + 'static> UciEngine<W> {
+    pub fn new(output: W) -> UciEngine<W> {
+        let board = Board::std_new();
+        UciEngine {
+            history: PositionHistory::new(&board),
+            board,
+            output: Arc::new(Mutex::new(output)),
+            ai: ChessAI::default(),
+            search: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            book_file: String::new(),
+            book_settings: BookSettings::default(),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Handles one line of input.
+    ///
+    /// # Returns
+    /// `false` once the GUI has sent `quit`.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else { return true };
+        let arguments: Vec<&str> = words.collect();
+        let result = match command {
+            "uci" => {
+                self.send(&format!("id name {}", ENGINE_NAME));
+                self.send(&format!("id author {}", ENGINE_AUTHOR));
+                for option in OPTIONS {
+                    self.send(option);
+                }
+                self.send("uciok");
+                Ok(())
+            }
+            "debug" => Ok(()),
+            "isready" => {
+                self.send("readyok");
+                Ok(())
+            }
+            "ucinewgame" => {
+                self.stop_search();
+                self.ai.clear_hash();
+                self.set_position(Board::std_new(), &[])
+            }
+            "position" => self.position(&arguments),
+            "go" => self.go(&arguments),
+            "stop" => {
+                self.stop_search();
+                Ok(())
+            }
+            "ponderhit" => {
+                if let Some(search) = &self.search {
+                    search.ponder_hit();
+                }
+                Ok(())
+            }
+            "setoption" => self.set_option(&arguments),
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => Err(format!("Unknown command '{}'", command)),
+        };
+        if let Err(message) = result {
+            self.send(&format!("info string {}", message));
+        }
+        true
+    }
+
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop();
+        }
+    }
+
+    fn set_position(&mut self, start: Board, moves: &[&str]) -> Result<(), String> {
+        let mut board = start;
+        let mut history = PositionHistory::new(&board);
+        for notation in moves {
+            let chess_move = parse_coordinate_move(&mut board, notation)?;
+            chess_move.make_move(&mut board);
+            history.push(&board);
+        }
+        self.board = board;
+        self.history = history;
+        Ok(())
+    }
+
+    /// `position [startpos | fen <fen>] [moves <move>...]`
+    fn position(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let moves_index = arguments.iter().position(|&word| word == "moves").unwrap_or(arguments.len());
+        let moves = arguments.get(moves_index + 1..).unwrap_or(&[]);
+        let start = match arguments.first() {
+            Some(&"startpos") => STARTING_FEN.to_string(),
+            Some(&"fen") => arguments[1..moves_index].join(" "),
+            _ => return Err(String::from("Expected 'startpos' or 'fen'")),
+        };
+        let start = parse_legal_fen(&start).map_err(|error| format!("Illegal position: {}", error))?;
+        self.set_position(start, moves)
+    }
+
+    /// `go [ponder] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>] [movestogo <n>] [depth <n>]
+    /// [nodes <n>] [mate <n>] [movetime <ms>] [infinite]`
+    fn go(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let mut limits = SearchLimits {
+            move_overhead: self.move_overhead,
+            ..SearchLimits::default()
+        };
+        let mut ponder = false;
+        let mut words = arguments.iter().copied();
+        while let Some(word) = words.next() {
+            match word {
+                "ponder" => ponder = true,
+                "wtime" => limits.white_time = Some(parse_millis(words.next())?),
+                "btime" => limits.black_time = Some(parse_millis(words.next())?),
+                "winc" => limits.white_increment = parse_millis(words.next())?,
+                "binc" => limits.black_increment = parse_millis(words.next())?,
+                "movestogo" => limits.moves_to_go = Some(parse_number(words.next())?),
+                "depth" => limits.depth = Some(parse_number(words.next())?),
+                "nodes" => limits.nodes = Some(parse_number(words.next())?),
+                // A mate in n is found within 2n - 1 plies
+                "mate" => limits.depth = Some(parse_number::<u8>(words.next())?.saturating_mul(2)),
+                "movetime" => limits.move_time = Some(parse_millis(words.next())?),
+                "infinite" => limits.infinite = true,
+                // Restricting the root moves isn't supported, skip them
+                "searchmoves" => {}
+                _ if word.len() >= 4 && word.get(0..2).and_then(parse_square).is_some() => {}
+                _ => return Err(format!("Unknown go parameter '{}'", word)),
+            }
+        }
+
+        self.stop_search();
+        // Analysis and pondering search even in book
+        if !ponder && !limits.infinite {
+            if let Some(book_move) = self.ai.book_move(&self.board) {
+                send(&self.output, &format!("bestmove {}", book_move.to_coordinate_notation()));
+                return Ok(());
+            }
+        }
+        let output = Arc::clone(&self.output);
+        self.search = Some(BackgroundSearch::start(
+            self.ai.clone(),
+            self.board.clone(),
+            self.history.clone(),
+            limits,
+            ponder,
+            move |event| match event {
+                SearchEvent::Info(info) => send(&output, &info_line(&info)),
+                SearchEvent::Finished(result) => send(&output, &bestmove_line(&result)),
+            },
+        ));
+        Ok(())
+    }
+
+    /// Plays from the book at `path` with `settings`. On an error the current book and
+    /// settings are kept.
+    fn load_book(&mut self, path: String, settings: BookSettings) -> Result<(), String> {
+        let book = settings.load(&path)?;
+        self.stop_search();
+        self.ai.set_book(book);
+        self.book_file = path;
+        self.book_settings = settings;
+        Ok(())
+    }
+
+    /// `setoption name <name> [value <value>]`, names may contain spaces.
+    fn set_option(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let value_index = arguments.iter().position(|&word| word == "value").unwrap_or(arguments.len());
+        if arguments.first() != Some(&"name") {
+            return Err(String::from("Expected 'name'"));
+        }
+        let name = arguments[1..value_index].join(" ");
+        let value = arguments.get(value_index + 1..).unwrap_or(&[]).join(" ");
+        match name.to_ascii_lowercase().as_str() {
+            "hash" => {
+                let megabytes: usize = parse_number(Some(&value))?;
+                self.stop_search();
+                self.ai.set_hash_size(megabytes.clamp(1, MAX_HASH_MB));
+                Ok(())
+            }
+            "threads" => {
+                let threads: usize = parse_number(Some(&value))?;
+                self.stop_search();
+                self.ai.set_threads(threads);
+                Ok(())
+            }
+            // Pondering is driven by the GUI, the option only tells it we support it
+            "ponder" => Ok(()),
+            "move overhead" => {
+                let millis: u64 = parse_number(Some(&value))?;
+                self.move_overhead = Duration::from_millis(millis.min(MAX_MOVE_OVERHEAD));
+                Ok(())
+            }
+            "evalweights" => {
+                let weights = match value.as_str() {
+                    "" | "<empty>" => EvalWeights::default(),
+                    path => EvalWeights::load(path)?,
+                };
+                self.ai.set_evaluator(Arc::new(StandardEvaluator::new(weights)));
+                Ok(())
+            }
+            "bookfile" => self.load_book(value, self.book_settings),
+            "bookbestmove" => {
+                let selection = match value.as_str() {
+                    "true" => BookSelection::Best,
+                    "false" => BookSelection::WeightedRandom,
+                    _ => return Err(format!("Invalid BookBestMove value '{}', expected true or false", value)),
+                };
+                self.load_book(self.book_file.clone(), BookSettings { selection, ..self.book_settings })
+            }
+            "bookdepth" => {
+                // 0 plays from the book for as long as it knows the position
+                let plies: u32 = parse_number(Some(&value))?;
+                let max_depth = (plies > 0).then_some(plies);
+                self.load_book(self.book_file.clone(), BookSettings { max_depth, ..self.book_settings })
+            }
+            "multipv" => {
+                let lines: usize = parse_number(Some(&value))?;
+                self.stop_search();
+                self.ai.set_multi_pv(lines);
+                Ok(())
+            }
+            "skill level" => {
+                // Levels out of range are refused rather than clamped, keeping the current level
+                let level = parse_number(Some(&value))?;
+                let skill = SkillLevel::new(level)
+                    .ok_or_else(|| format!("Skill Level must be from 0 to {}, not {}", SkillLevel::MAX.level(), level))?;
+                self.stop_search();
+                self.ai.set_skill(skill);
+                Ok(())
+            }
+            "syzygypath" => {
+                let tablebases = match value.as_str() {
+                    "" | "<empty>" => None,
+                    paths => Some(Tablebases::open(paths)?),
+                };
+                if let Some(tablebases) = &tablebases {
+                    self.send(&format!("info string Found {} tablebases", tablebases.len()));
+                }
+                self.stop_search();
+                self.ai.set_tablebases(tablebases);
+                Ok(())
+            }
+            _ => Err(format!("No such option: {}", name)),
+        }
+    }
+}
+
+/// Runs a UCI session until `quit` or the end of `input`.
+pub fn run<W: Write + Send + 'static>(input: impl BufRead, output: W) {
+    let mut engine = UciEngine::new(output);
+    for line in input.lines() {
+        let Ok(line) = line else { break };
+        if !engine.handle_command(&line) {
+            return;
+        }
+    }
+    engine.stop_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output shared with the test, which reads it back once the session is over.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `commands`, waiting for each search to finish by itself, and returns the output.
+    fn transcript(commands: &[&str]) -> Vec<String> {
+        let output = SharedOutput::default();
+        let mut engine = UciEngine::new(output.clone());
+        for command in commands {
+            engine.handle_command(command);
+            if !command.contains("infinite") && !command.contains("ponder") {
+                if let Some(search) = engine.search.take() {
+                    search.wait();
+                }
+            }
+        }
+        engine.stop_search();
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let lines = transcript(&["uci", "isready"]);
+        assert_eq!(lines[0], "id name Rusty Chess");
+        assert!(lines.contains(&String::from("uciok")));
+        assert_eq!(lines.last().unwrap(), "readyok");
+    }
+
+    #[test]
+    fn searches_positions_with_moves() {
+        // 1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 leaves Qxf7 mate
+        let lines = transcript(&[
+            "ucinewgame",
+            "position startpos moves e2e4 e7e5 d1h5 b8c6 f1c4 g8f6",
+            "go depth 2",
+        ]);
+        assert!(lines.iter().any(|line| line.starts_with("info depth 1 multipv 1 score mate 1 ")), "{:?}", lines);
+        assert!(lines.iter().filter(|line| line.starts_with("info depth")).all(|line| line.contains(" hashfull ")));
+        assert_eq!(lines.last().unwrap(), "bestmove h5f7");
+
+        let lines = transcript(&["position fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q e8d7", "go nodes 500"]);
+        assert!(lines.last().unwrap().starts_with("bestmove "), "{:?}", lines);
+
+        let lines = transcript(&["setoption name MultiPV value 2", "position startpos", "go depth 2"]);
+        assert!(lines.iter().any(|line| line.starts_with("info depth 2 multipv 2 score ")), "{:?}", lines);
+        assert!(lines.last().unwrap().starts_with("bestmove "), "{:?}", lines);
+
+        let lines = transcript(&["position fen 7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", "go depth 1"]);
+        assert_eq!(lines, vec!["bestmove 0000"]);
+
+        // An illegal position is refused and the previous one kept
+        let lines = transcript(&[
+            "position fen 7k/6Q1/6K1/8/8/8/8/8 b - - 0 1",
+            "position fen 8/8/8/8/8/8/8/8 w - - 0 1",
+            "go depth 1",
+        ]);
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("info string Illegal position: "), "{:?}", lines);
+        assert_eq!(lines[1], "bestmove 0000");
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        let lines = transcript(&[
+            "position startpos moves e2e5",
+            "go wtime x",
+            "go aé2e4",
+            "position fen P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "setoption name Contempt value 1",
+            "xyzzy",
+        ]);
+        assert_eq!(lines.len(), 6);
+        assert!(lines.iter().all(|line| line.starts_with("info string ")), "{:?}", lines);
+        assert_eq!(lines[3], "info string Illegal position: Pawn on back rank square a8");
+
+        let lines = transcript(&[
+            "setoption name Move Overhead value 100",
+            "setoption name Hash value 1",
+            "setoption name Threads value 2",
+            "setoption name Skill Level value 3",
+        ]);
+        assert!(lines.is_empty());
+
+        let lines = transcript(&["setoption name Skill Level value 25"]);
+        assert_eq!(lines, vec!["info string Skill Level must be from 0 to 20, not 25"]);
+
+        let lines = transcript(&[
+            "setoption name EvalWeights value /nonexistent/weights.txt",
+            "setoption name EvalWeights value <empty>",
+            "setoption name BookFile value /nonexistent/book.bin",
+            "setoption name BookFile value <empty>",
+            "setoption name SyzygyPath value /nonexistent/syzygy",
+            "setoption name SyzygyPath value <empty>",
+        ]);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("info string /nonexistent/weights.txt: "), "{:?}", lines);
+        assert!(lines[1].starts_with("info string /nonexistent/book.bin: "), "{:?}", lines);
+        assert!(lines[2].starts_with("info string /nonexistent/syzygy: "), "{:?}", lines);
+    }
+
+    #[test]
+    fn plays_from_the_book_with_its_options() {
+        use crate::ai::book::{polyglot_key, polyglot_move, BookEntry, OpeningBook};
+        let entry = |moves: &str, notation: &str, weight| {
+            let mut board = Board::std_new();
+            for notation in moves.split_whitespace() {
+                parse_coordinate_move(&mut board, notation).unwrap().make_move(&mut board);
+            }
+            let chess_move = parse_coordinate_move(&mut board.clone(), notation).unwrap();
+            BookEntry { key: polyglot_key(&board), raw_move: polyglot_move(chess_move), weight, learn: 0 }
+        };
+        let book = OpeningBook::new(vec![entry("", "e2e4", 30), entry("", "d2d4", 10), entry("e2e4", "a7a6", 1)]);
+        let path = std::env::temp_dir().join(format!("rusty_chess_uci_book_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        book.save(&path).unwrap();
+
+        // The best move every time, and no search for it
+        let mut commands = vec![format!("setoption name BookFile value {}", path), String::from("setoption name BookBestMove value true")];
+        commands.extend((0..10).map(|_| String::from("go depth 1")));
+        let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+        let lines = transcript(&commands);
+        assert_eq!(lines, vec!["bestmove e2e4"; 10]);
+
+        // Settings apply whichever comes first, and past the book's depth the engine searches
+        let lines = transcript(&[
+            "setoption name BookDepth value 1",
+            &format!("setoption name BookFile value {}", path),
+            "position startpos moves e2e4",
+            "go depth 1",
+            "setoption name BookDepth value 0",
+            "go depth 1",
+            "setoption name BookBestMove value maybe",
+        ]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(lines[0].starts_with("info depth 1 "), "{:?}", lines);
+        assert_eq!(lines[lines.len() - 2], "bestmove a7a6");
+        assert_eq!(lines[lines.len() - 1], "info string Invalid BookBestMove value 'maybe', expected true or false");
+    }
+
+    #[test]
+    fn infinite_and_ponder_searches_wait_for_the_gui() {
+        let output = SharedOutput::default();
+        let mut engine = UciEngine::new(output.clone());
+        engine.handle_command("position startpos");
+        engine.handle_command("go infinite");
+        std::thread::sleep(Duration::from_millis(50));
+        let bestmoves = || String::from_utf8(output.0.lock().unwrap().clone()).unwrap().matches("bestmove").count();
+        assert_eq!(bestmoves(), 0);
+        engine.handle_command("stop");
+        assert_eq!(bestmoves(), 1);
+
+        engine.handle_command("go ponder wtime 1000 btime 1000");
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(bestmoves(), 1);
+        engine.handle_command("ponderhit");
+        engine.search.take().unwrap().wait();
+        assert_eq!(bestmoves(), 2);
+        assert!(engine.handle_command("isready"));
+        assert!(!engine.handle_command("quit"));
+    }
+}
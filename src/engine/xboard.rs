@@ -0,0 +1,656 @@
+//! Chess Engine Communication Protocol version 2, spoken by XBoard and WinBoard.
+//!
+//! Unlike UCI the engine keeps the game itself: the GUI sends each move as it is played and
+//! tells the engine which side to play with `new`, `go`, `playother` and `force`. Whenever it
+//! is the engine's side to move it starts thinking, answers with `move`, and plays that move on
+//! its own board. Moves can be taken back with `undo` and `remove`.
+//!
+//! The game is kept in a [`Game`], so game ends are detected by the same rules as local play
+//! and announced with the result and a reason, e.g. `1-0 {White mates}`.
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::ai::book::{BookSelection, BookSettings};
+use crate::ai::syzygy::Tablebases;
+use crate::ai::tt::MAX_HASH_MB;
+use crate::ai::ChessAI;
+use crate::board::pieces::Color;
+use crate::board::Board;
+use crate::chess_moves::ChessMove;
+use crate::engine::{
+    parse_coordinate_move, parse_legal_fen, BackgroundSearch, Score, SearchEvent, SearchInfo, SearchLimits,
+    DEFAULT_MOVE_OVERHEAD,
+};
+use crate::game::{Game, PlayerType};
+use crate::rules::{GameOutcome, Termination};
+
+const FEATURES: &str = "feature myname=\"Rusty Chess\" setboard=1 usermove=1 ping=1 playother=1 memory=1 smp=1 \
+    egt=\"syzygy\" colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 option=\"BookFile -file \" \
+    option=\"BookBestMove -check 0\" option=\"BookDepth -spin 0 0 1000\" done=1";
+
+/// Thinking output reports mate in n as this plus n, or minus it for being mated.
+const MATE_SCORE: i32 = 100_000;
+/// How often the session checks for a finished search while waiting for input.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The clock settings from `level`, `st` and `sd`.
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeControl {
+    /// Moves per time control, 0 when the whole game must be played in `base`.
+    moves_per_session: u32,
+    base: Duration,
+    increment: Duration,
+    /// A fixed time per move, set by `st`.
+    move_time: Option<Duration>,
+    /// A fixed depth, set by `sd`.
+    depth: Option<u8>,
+}
+
+/// The state of a CECP session.
+pub struct XBoardEngine<W: Write + Send + 'static> {
+    game: Game,
+    output: Arc<Mutex<W>>,
+    ai: ChessAI,
+    search: Option<BackgroundSearch>,
+    /// Set before stopping a search whose move must not be played, e.g. on `force`.
+    search_cancelled: Arc<AtomicBool>,
+    engine_moves: (Sender<ChessMove>, Receiver<ChessMove>),
+    /// The side the engine plays, `None` in force mode.
+    engine_color: Option<Color>,
+    /// Whether to send thinking output.
+    post: Arc<AtomicBool>,
+    time_control: TimeControl,
+    engine_time: Option<Duration>,
+    opponent_time: Option<Duration>,
+    /// The `BookFile` option loaded, reloaded when the book settings change.
+    book_file: String,
+    book_settings: BookSettings,
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    if let Ok(mut output) = output.lock() {
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+}
+
+/// Formats a search report as thinking output: depth, score, time in centiseconds, nodes and
+/// the principal variation in SAN.
+fn thinking_line(board: &Board, info: &SearchInfo) -> String {
+    let score = match info.score {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves > 0 => MATE_SCORE + moves,
+        Score::Mate(moves) => -MATE_SCORE + moves,
+    };
+    let mut board = board.clone();
+    let pv: Vec<String> = info
+        .pv
+        .iter()
+        .map(|chess_move| {
+            let san = chess_move.to_san(&board);
+            chess_move.make_move(&mut board);
+            san
+        })
+        .collect();
+    format!("{} {} {} {} {}", info.depth, score, info.elapsed.as_millis() / 10, info.nodes, pv.join(" "))
+}
+
+/// The result line announcing the end of a game.
+fn result_line(outcome: GameOutcome) -> String {
+    let reason = match (outcome.termination, outcome.winner) {
+        (Termination::Checkmate, Some(Color::White)) => "White mates",
+        (Termination::Checkmate, _) => "Black mates",
+        (Termination::Stalemate, _) => "Stalemate",
+        (Termination::Repetition, _) => "Draw by repetition",
+        (Termination::FiftyMoveRule, _) => "Draw by fifty move rule",
+        (Termination::InsufficientMaterial, _) => "Draw by insufficient material",
+        (Termination::Resignation, Some(Color::White)) => "Black resigns",
+        (Termination::Resignation, _) => "White resigns",
+        (Termination::Timeout, _) => "Time forfeit",
+        (Termination::Agreement, _) => "Draw agreed",
+        (Termination::Abandonment, _) => "Game abandoned",
+        (Termination::Adjudication, _) => "Adjudication",
+    };
+    format!("{} {{{}}}", outcome.to_pgn_result(), reason)
+}
+
+/// Parses a `level` base time, minutes or `minutes:seconds`.
+fn parse_base_time(base: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid time '{}'", base);
+    let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+    let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+    let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+    Ok(Duration::from_secs(minutes * 60 + seconds))
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&&str>) -> Result<T, String> {
+    let value = value.ok_or("Missing number")?;
+    value.parse().map_err(|_| format!("Invalid number '{}'", value))
+}
+
+/// Parses a `time` or `otim` value, in centiseconds.
+fn parse_centiseconds(value: Option<&&str>) -> Result<Duration, String> {
+    let centiseconds: i64 = parse_number(value)?;
+    Ok(Duration::from_millis(centiseconds.max(0) as u64 * 10))
+}
+
+impl<W: Write + Send + 'static> XBoardEngine<W> {
+    pub fn new(output: W) -> XBoardEngine<W> {
+        XBoardEngine {
+            game: Game::new(PlayerType::RemoteHuman, PlayerType::LocalHuman),
+            output: Arc::new(Mutex::new(output)),
+            ai: ChessAI::default(),
+            search: None,
+            search_cancelled: Arc::new(AtomicBool::new(false)),
+            engine_moves: mpsc::channel(),
+            engine_color: Some(Color::Black),
+            post: Arc::new(AtomicBool::new(false)),
+            time_control: TimeControl::default(),
+            engine_time: None,
+            opponent_time: None,
+            book_file: String::new(),
+            book_settings: BookSettings::default(),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Handles one line of input.
+    ///
+    /// # Returns
+    /// `false` once the GUI has sent `quit`.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        // The engine's last move is sent before the GUI can answer it, play it first
+        self.play_engine_moves();
+
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else { return true };
+        let arguments: Vec<&str> = words.collect();
+        let result = match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating"
+            | "draw" | "ics" | "white" | "black" => Ok(()),
+            "protover" => {
+                self.send(FEATURES);
+                Ok(())
+            }
+            "new" => {
+                self.cancel_search();
+                self.game = Game::new(PlayerType::RemoteHuman, PlayerType::LocalHuman);
+                self.engine_color = Some(Color::Black);
+                self.time_control.depth = None;
+                self.ai.clear_hash();
+                Ok(())
+            }
+            "memory" => parse_number::<usize>(arguments.first()).map(|megabytes| {
+                self.cancel_search();
+                self.ai.set_hash_size(megabytes.clamp(1, MAX_HASH_MB));
+            }),
+            "cores" => parse_number::<usize>(arguments.first()).map(|cores| {
+                self.cancel_search();
+                self.ai.set_threads(cores);
+            }),
+            "egtpath" => match arguments.split_first() {
+                Some((&"syzygy", paths)) => Tablebases::open(&paths.join(" ")).map(|tablebases| {
+                    self.cancel_search();
+                    self.ai.set_tablebases(Some(tablebases));
+                }),
+                _ => Err(format!("Unsupported tablebases: {}", arguments.join(" "))),
+            },
+            "variant" => match arguments.first() {
+                Some(&"normal") => Ok(()),
+                _ => Err(format!("Unsupported variant: {}", arguments.join(" "))),
+            },
+            "force" => {
+                self.cancel_search();
+                self.engine_color = None;
+                Ok(())
+            }
+            "go" => {
+                self.engine_color = Some(self.game.board().active_player);
+                self.think();
+                Ok(())
+            }
+            "playother" => {
+                self.cancel_search();
+                self.engine_color = Some(self.game.board().active_player.toggle_color());
+                Ok(())
+            }
+            "level" => self.level(&arguments),
+            "st" => parse_number::<u64>(arguments.first()).map(|seconds| {
+                self.time_control.move_time = Some(Duration::from_secs(seconds));
+            }),
+            "sd" => parse_number(arguments.first()).map(|depth| self.time_control.depth = Some(depth)),
+            "time" => parse_centiseconds(arguments.first()).map(|time| self.engine_time = Some(time)),
+            "otim" => parse_centiseconds(arguments.first()).map(|time| self.opponent_time = Some(time)),
+            "usermove" => match arguments.first() {
+                Some(notation) => self.user_move(notation),
+                None => Err(String::from("Missing move")),
+            },
+            "?" => {
+                // Move now, the search plays what it has
+                if let Some(search) = self.search.take() {
+                    search.stop();
+                }
+                self.play_engine_moves();
+                Ok(())
+            }
+            "ping" => {
+                self.send(&format!("pong {}", arguments.first().unwrap_or(&"")));
+                Ok(())
+            }
+            "result" => {
+                self.cancel_search();
+                self.engine_color = None;
+                Ok(())
+            }
+            "setboard" => self.set_board(&arguments.join(" ")),
+            "option" => self.set_option(&arguments.join(" ")),
+            "undo" => {
+                self.cancel_search();
+                self.game.undo_move();
+                Ok(())
+            }
+            "remove" => {
+                self.cancel_search();
+                self.game.undo_move();
+                self.game.undo_move();
+                Ok(())
+            }
+            "post" => {
+                self.post.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            "nopost" => {
+                self.post.store(false, Ordering::SeqCst);
+                Ok(())
+            }
+            "quit" => {
+                self.cancel_search();
+                return false;
+            }
+            // Version 1 GUIs send moves without `usermove`
+            _ if parse_coordinate_move(&mut self.game.board().clone(), command).is_ok() => self.user_move(command),
+            _ => {
+                self.send(&format!("Error (unknown command): {}", command));
+                Ok(())
+            }
+        };
+        if let Err(message) = result {
+            self.send(&format!("Error ({}): {}", message, line));
+        }
+        true
+    }
+
+    /// Stops the search without playing its move.
+    fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.search_cancelled.store(true, Ordering::SeqCst);
+            search.stop();
+        }
+    }
+
+    /// Plays the moves the search has sent, and announces the end of the game if one of them
+    /// ended it.
+    fn play_engine_moves(&mut self) {
+        while let Ok(chess_move) = self.engine_moves.1.try_recv() {
+            self.search = None;
+            self.game.play_move(chess_move);
+            // The engine claims draws as soon as it can
+            self.game.claim_draw();
+            if let Some(outcome) = self.game.outcome() {
+                self.send(&result_line(outcome));
+            }
+        }
+    }
+
+    /// `level <moves per session> <base> <increment>`
+    fn level(&mut self, arguments: &[&str]) -> Result<(), String> {
+        let [moves_per_session, base, increment] = arguments else {
+            return Err(String::from("Expected 3 arguments"));
+        };
+        self.time_control.moves_per_session = parse_number(Some(moves_per_session))?;
+        self.time_control.base = parse_base_time(base)?;
+        let increment: f64 = parse_number(Some(increment))?;
+        self.time_control.increment = Duration::from_secs_f64(increment.max(0.0));
+        self.time_control.move_time = None;
+        Ok(())
+    }
+
+    fn user_move(&mut self, notation: &str) -> Result<(), String> {
+        if self.game.outcome().is_some() {
+            self.send(&format!("Illegal move (game over): {}", notation));
+            return Ok(());
+        }
+        let Ok(chess_move) = parse_coordinate_move(&mut self.game.board().clone(), notation) else {
+            self.send(&format!("Illegal move: {}", notation));
+            return Ok(());
+        };
+        self.game.play_move(chess_move);
+        match self.game.outcome() {
+            Some(outcome) => self.send(&result_line(outcome)),
+            None => {
+                if self.engine_color == Some(self.game.board().active_player) {
+                    self.think();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn set_board(&mut self, fen: &str) -> Result<(), String> {
+        self.cancel_search();
+        match parse_legal_fen(fen) {
+            Ok(board) => {
+                self.game = Game::new_from_position(PlayerType::RemoteHuman, PlayerType::LocalHuman, board);
+                Ok(())
+            }
+            Err(error) => {
+                self.send(&format!("tellusererror Illegal position: {}", error));
+                Ok(())
+            }
+        }
+    }
+
+    /// `option <name>=<value>`, for the options announced in [`FEATURES`].
+    fn set_option(&mut self, option: &str) -> Result<(), String> {
+        let (name, value) = option.split_once('=').ok_or("Expected <name>=<value>")?;
+        match name {
+            "BookFile" => self.load_book(value.to_string(), self.book_settings),
+            "BookBestMove" => {
+                let selection = match value {
+                    "1" => BookSelection::Best,
+                    "0" => BookSelection::WeightedRandom,
+                    _ => return Err(format!("Invalid BookBestMove value '{}'", value)),
+                };
+                self.load_book(self.book_file.clone(), BookSettings { selection, ..self.book_settings })
+            }
+            "BookDepth" => {
+                // 0 plays from the book for as long as it knows the position
+                let plies: u32 = parse_number(Some(&value))?;
+                let max_depth = (plies > 0).then_some(plies);
+                self.load_book(self.book_file.clone(), BookSettings { max_depth, ..self.book_settings })
+            }
+            _ => Err(format!("Unknown option {}", name)),
+        }
+    }
+
+    /// Plays from the book at `path` with `settings`. On an error the current book and
+    /// settings are kept.
+    fn load_book(&mut self, path: String, settings: BookSettings) -> Result<(), String> {
+        let book = settings.load(&path)?;
+        self.cancel_search();
+        self.ai.set_book(book);
+        self.book_file = path;
+        self.book_settings = settings;
+        Ok(())
+    }
+
+    fn search_limits(&self) -> SearchLimits {
+        let time_control = self.time_control;
+        let mut limits = SearchLimits {
+            depth: time_control.depth,
+            move_time: time_control.move_time,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            ..SearchLimits::default()
+        };
+        if let (None, Some(engine_time)) = (time_control.move_time, self.engine_time) {
+            let board = self.game.board();
+            let opponent_time = self.opponent_time.unwrap_or(engine_time);
+            let (white_time, black_time) = match board.active_player {
+                Color::White => (engine_time, opponent_time),
+                Color::Black => (opponent_time, engine_time),
+            };
+            limits.white_time = Some(white_time);
+            limits.black_time = Some(black_time);
+            limits.white_increment = time_control.increment;
+            limits.black_increment = time_control.increment;
+            if time_control.moves_per_session > 0 {
+                let moves_played = board.full_move_number.saturating_sub(1) as u32 % time_control.moves_per_session;
+                limits.moves_to_go = Some(time_control.moves_per_session - moves_played);
+            }
+        }
+        limits
+    }
+
+    /// Plays a book move if the book knows the position, and otherwise starts searching for
+    /// the engine's move, unless the game is over.
+    fn think(&mut self) {
+        self.cancel_search();
+        if self.game.outcome().is_some() {
+            return;
+        }
+        if let Some(book_move) = self.ai.book_move(self.game.board()) {
+            let _ = self.engine_moves.0.send(book_move);
+            self.send(&format!("move {}", book_move.to_coordinate_notation()));
+            return;
+        }
+        self.search_cancelled = Arc::new(AtomicBool::new(false));
+
+        let output = Arc::clone(&self.output);
+        let board = self.game.board().clone();
+        let post = Arc::clone(&self.post);
+        let cancelled = Arc::clone(&self.search_cancelled);
+        let engine_moves = self.engine_moves.0.clone();
+        self.search = Some(BackgroundSearch::start(
+            self.ai.clone(),
+            board.clone(),
+            self.game.position_history().clone(),
+            self.search_limits(),
+            false,
+            move |event| match event {
+                SearchEvent::Info(info) => {
+                    if post.load(Ordering::SeqCst) {
+                        send(&output, &thinking_line(&board, &info));
+                    }
+                }
+                SearchEvent::Finished(result) => {
+                    if let (Some(best_move), false) = (result.best_move, cancelled.load(Ordering::SeqCst)) {
+                        // Queue the move before the GUI can see and answer it
+                        let _ = engine_moves.send(best_move);
+                        send(&output, &format!("move {}", best_move.to_coordinate_notation()));
+                    }
+                }
+            },
+        ));
+    }
+}
+
+/// Runs a CECP session until `quit` or the end of `input`.
+///
+/// Input is read on its own thread, so the engine's moves are played while waiting for the
+/// GUI and a game the engine ends is announced without the GUI having to send anything.
+pub fn run<W: Write + Send + 'static>(input: impl BufRead + Send + 'static, output: W) {
+    let mut engine = XBoardEngine::new(output);
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    loop {
+        match lines.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => {
+                if !engine.handle_command(&line) {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => engine.play_engine_moves(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    engine.cancel_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `commands`, letting every search finish before the next command, and returns the
+    /// output with the engine.
+    fn transcript(commands: &[&str]) -> (Vec<String>, XBoardEngine<SharedOutput>) {
+        let output = SharedOutput::default();
+        let mut engine = XBoardEngine::new(output.clone());
+        for command in commands {
+            engine.handle_command(command);
+            if let Some(search) = engine.search.take() {
+                search.wait();
+            }
+        }
+        engine.play_engine_moves();
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        (text.lines().map(String::from).collect(), engine)
+    }
+
+    #[test]
+    fn plays_the_side_it_is_given() {
+        let (lines, engine) = transcript(&["xboard", "protover 2", "new", "sd 2", "post", "usermove e2e4"]);
+        assert!(lines[0].starts_with("feature ") && lines[0].ends_with("done=1"));
+        assert!(lines.iter().any(|line| line.starts_with("1 ")), "{:?}", lines);
+        assert!(lines.last().unwrap().starts_with("move "), "{:?}", lines);
+        // The engine's reply is on its board
+        assert_eq!(engine.game.board().active_player, Color::White);
+        assert_eq!(engine.game.board().full_move_number, 2);
+
+        let (lines, engine) = transcript(&["new", "force", "e2e4", "usermove e7e5", "ping 7"]);
+        assert_eq!(lines, vec!["pong 7"]);
+        assert_eq!(engine.game.board().full_move_number, 2);
+    }
+
+    #[test]
+    fn announces_game_ends() {
+        let (lines, _) = transcript(&[
+            "new",
+            "force",
+            "setboard r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            "sd 2",
+            "go",
+        ]);
+        assert_eq!(lines, vec!["move h5f7", "1-0 {White mates}"]);
+
+        let (lines, _) = transcript(&[
+            "new",
+            "force",
+            "usermove f2f3",
+            "usermove e7e5",
+            "usermove g2g4",
+            "usermove d8h4",
+            "usermove a2a3",
+        ]);
+        assert!(lines.contains(&String::from("0-1 {Black mates}")), "{:?}", lines);
+        assert_eq!(lines.last().unwrap(), "Illegal move (game over): a2a3");
+    }
+
+    #[test]
+    fn announces_its_own_mate_without_further_input() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let output = SharedOutput::default();
+        let session_output = output.clone();
+        let session = thread::spawn(move || run(std::io::BufReader::new(reader), session_output));
+        writeln!(writer, "new\nforce\nsetboard 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\nsd 3\ngo").unwrap();
+
+        let text = || String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        for _ in 0..500 {
+            if text().contains("1-0") {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(text(), "move a1a8\n1-0 {White mates}\n");
+        writeln!(writer, "quit").unwrap();
+        session.join().unwrap();
+    }
+
+    #[test]
+    fn takes_moves_back() {
+        let (_, engine) = transcript(&["new", "force", "usermove e2e4", "usermove e7e5", "usermove g1f3", "remove"]);
+        assert_eq!(engine.game.board().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        let (_, engine) = transcript(&["new", "force", "usermove e2e4", "undo", "undo"]);
+        assert_eq!(engine.game.board().to_fen(), Board::std_new().to_fen());
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        let (lines, _) = transcript(&["new", "usermove e2e5", "level 40 x 0", "setboard 8/8", "xyzzy", "variant x", "egtpath gaviota /tb"]);
+        assert_eq!(lines[0], "Illegal move: e2e5");
+        assert!(lines[1].starts_with("Error (Invalid time 'x'): level"));
+        assert!(lines[2].starts_with("tellusererror Illegal position"));
+        assert_eq!(lines[3], "Error (unknown command): xyzzy");
+        assert!(lines[4].starts_with("Error (Unsupported variant"));
+        assert!(lines[5].starts_with("Error (Unsupported tablebases"));
+
+        let (lines, engine) = transcript(&["new", "setboard P3k3/8/8/8/8/8/8/4K3 w - - 0 1", "setboard 8/8/8/8/8/8/8/8 w - - 0 1"]);
+        assert_eq!(lines[0], "tellusererror Illegal position: Pawn on back rank square a8");
+        assert!(lines[1].starts_with("tellusererror Illegal position: "));
+        assert_eq!(engine.game.board().to_fen(), Board::std_new().to_fen());
+    }
+
+    #[test]
+    fn plays_from_the_book_it_is_given() {
+        use crate::ai::book::{polyglot_key, polyglot_move, BookEntry, OpeningBook};
+        let board = Board::std_new();
+        let entry = |notation, weight| {
+            let chess_move = parse_coordinate_move(&mut board.clone(), notation).unwrap();
+            BookEntry { key: polyglot_key(&board), raw_move: polyglot_move(chess_move), weight, learn: 0 }
+        };
+        let book = OpeningBook::new(vec![entry("e2e4", 30), entry("d2d4", 10)]);
+        let path = std::env::temp_dir().join(format!("rusty_chess_xboard_book_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        book.save(&path).unwrap();
+
+        let book_file = format!("option BookFile={}", path);
+        let (lines, engine) = transcript(&["new", "option BookBestMove=1", &book_file, "post", "go"]);
+        // A book move comes without thinking output, and is played on the engine's board
+        assert_eq!(lines, vec!["move e2e4"]);
+        assert_eq!(engine.game.board().active_player, Color::Black);
+
+        let (lines, _) = transcript(&[&book_file, "option BookDepth=1", "force", "usermove e2e4", "sd 1", "post", "go"]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(lines[0].starts_with("1 "), "{:?}", lines);
+
+        let (lines, _) = transcript(&["option BookFile=/nonexistent/book.bin", "option BookBestMove=2", "option Contempt=1"]);
+        assert!(lines[0].starts_with("Error (/nonexistent/book.bin: "), "{:?}", lines);
+        assert_eq!(lines[1], "Error (Invalid BookBestMove value '2'): option BookBestMove=2");
+        assert_eq!(lines[2], "Error (Unknown option Contempt): option Contempt=1");
+    }
+
+    #[test]
+    fn converts_clocks_into_limits() {
+        let (_, mut engine) = transcript(&["new", "level 40 5 0", "time 30000", "otim 20000", "force"]);
+        let limits = engine.search_limits();
+        // The engine's time goes to the side to move, which is the side it thinks for
+        assert_eq!(limits.white_time, Some(Duration::from_secs(300)));
+        assert_eq!(limits.black_time, Some(Duration::from_secs(200)));
+        assert_eq!(limits.moves_to_go, Some(40));
+
+        engine.handle_command("level 0 2:30 1.5");
+        engine.handle_command("time 15000");
+        let limits = engine.search_limits();
+        assert_eq!(engine.time_control.base, Duration::from_secs(150));
+        assert_eq!(limits.white_increment, Duration::from_millis(1500));
+        assert_eq!(limits.moves_to_go, None);
+
+        engine.handle_command("st 3");
+        assert_eq!(engine.search_limits().move_time, Some(Duration::from_secs(3)));
+    }
+}
@@ -1,15 +1,26 @@
 use crate::rules;
+use crate::board::pieces::Color;
 use crate::board::Board;
-use crate::chess_moves::{ChessMove, MoveError};
-use crate::rules::{GameState, TimeControls, Timer};
+use crate::chess_moves::{ChessMove, MoveError, MoveList, MoveUndo};
+use crate::rules::draw::{self, DrawStatus, PositionHistory};
+use crate::rules::{GameOutcome, GameState, Termination, TimeControls, Timer};
 use crate::clock::ChessClock;
 use crate::common::{ThreadIdHash, ThreadIdHashExt, ThreadIdentifier};
-// use crate::ai::ChessAI;
+use crate::pgn::GameRecord;
+use crate::time::time_format::{DateMillis, DisplayMode};
+use crate::ai::skill::SkillLevel;
+use crate::ai::ChessAI;
+use crate::engine::{parse_coordinate_move, BackgroundSearch, SearchEvent, SearchLimits, SearchResult};
+use crate::move_parser::chess_notation_parser::from_simplified_algebraic_notation;
+use crate::ui::UiMessage;
 use crate::player_agent;
 
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
 use std::thread;
-use std::sync::mpsc::{Receiver,Sender};
-use std::time::Instant;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
 use crate::handler::PlayerHandler;
 // pub trait AIBehavior {
 //     fn make_move(&self, board: &Board) -> Result<ChessMove, AIError>;
@@ -27,7 +38,7 @@ use crate::handler::PlayerHandler;
 ///     A human player playing on the local system.
 /// - `RemoteHuman`
 ///     A human player playing through a network connection.
-/// - `LocalAI(ChessAI)`
+/// - `LocalAI(Box<ChessAI>)`
 ///     An AI player running locally on the system. The `ChessAI` parameter
 ///     provides additional details or configuration about the AI.
 /// - `RemoteAI(ChessAI)`
@@ -38,23 +49,38 @@ use crate::handler::PlayerHandler;
 ///     The `ChessBot` parameter provides additional details or configuration
 ///     about the bot.
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum PlayerType{
     /// Human player playing locally
     LocalHuman,
     /// Human player playing through a network connection
     RemoteHuman,
-    // /// AI player playing locally
-    // LocalAI(ChessAI),
+    /// AI player playing locally
+    LocalAI(Box<ChessAI>),
     // /// AI player playing through a network connection
     // RemoteAI(ChessAI),
     // /// Bot player/API
     // RemoteBot(ChessBot),
 }
 
+impl PlayerType {
+    /// The skill of an AI player, `None` for humans.
+    pub fn skill(&self) -> Option<SkillLevel> {
+        match self {
+            PlayerType::LocalAI(ai) => Some(ai.skill()),
+            PlayerType::LocalHuman | PlayerType::RemoteHuman => None,
+        }
+    }
+}
+
 pub struct GameMetadata {
     game_id: Option<String>,
     start_time: Instant,
+    start_date: Option<DateMillis>,
+    event: Option<String>,
+    site: Option<String>,
+    white_name: Option<String>,
+    black_name: Option<String>,
     white_rating: Option<u32>,
     black_rating: Option<u32>,
 }
@@ -65,10 +91,18 @@ pub struct Game{
     black: PlayerType,
     game_state: GameState,
     clock: Option<ChessClock>,
+    time_controls: Option<TimeControls>,
+    starting_position: Board,
+    position_history: PositionHistory,
+    /// Moves played, with what is needed to take each back.
+    move_stack: Vec<(ChessMove, MoveUndo)>,
 }
 
 pub type FullMoveNumber = rules::FullMoveNumber;
 
+/// How often the clock of a human thinking is checked for a fallen flag.
+const FLAG_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct GameThread{
     game : Game,
     game_metadata: Option<GameMetadata>,
@@ -77,6 +111,23 @@ pub struct GameThread{
     player_2_handler: Option<PlayerHandler>,
     ui_in : Receiver<GameController>,
     move_history: Vec<ChessMove>,
+    /// Time left to the mover after each move of `move_history`, if the game is timed.
+    clock_history: Vec<Option<Duration>>,
+    /// Whether a local AI thinks on its human opponent's time, off on a machine other programs need.
+    pondering: bool,
+    ponder: Option<Ponder>,
+    /// Receives the moves played and the result, if a UI follows the game.
+    ui_out: Option<Sender<UiMessage>>,
+}
+
+/// A local AI's search of the position after the reply it expects, run while its human
+/// opponent thinks. The AI's clock isn't running meanwhile, and the search only starts timing
+/// itself once the reply is played.
+struct Ponder {
+    expected_reply: ChessMove,
+    search: BackgroundSearch,
+    /// Receives the search's result once it ends.
+    result: Receiver<SearchResult>,
 }
 
 pub enum GameMessage {
@@ -87,7 +138,7 @@ pub enum GameMessage {
     SetPremove(ChessMove, FullMoveNumber),
 }
 pub enum GameResponse {
-    Sync(Game),
+    Sync(Box<Game>),
     SyncClock(ChessClock),
     SyncMoveHistory(Vec<ChessMove>),
     IllegalMove(MoveError),
@@ -97,29 +148,63 @@ pub enum GameController {
     StartGame,
     StopGame,
     AbortThread,
+    /// A move of the local human, in coordinate notation like `e2e4` or in SAN.
+    MakeMove(String),
 }
 
 impl Game{
     pub fn new(white: PlayerType, black: PlayerType) -> Game {
+        Game::new_from_position(white, black, Board::std_new())
+    }
+    /// Starts a game of the local human, playing `human`, against `ai`, e.g. one set up with
+    /// the skill and book from the config. With `time_controls` the game is played on the
+    /// clock, which starts with the game.
+    ///
+    /// # Returns
+    ///
+    /// `None` if no clock can be set to `time_controls`.
+    pub fn against_ai(human: Color, ai: ChessAI, time_controls: Option<TimeControls>) -> Option<Game> {
+        let ai = PlayerType::LocalAI(Box::new(ai));
+        let (white, black) = match human {
+            Color::White => (PlayerType::LocalHuman, ai),
+            Color::Black => (ai, PlayerType::LocalHuman),
+        };
+        match time_controls {
+            Some(time_controls) => Game::new_with_time_controls(white, black, time_controls),
+            None => Some(Game::new(white, black)),
+        }
+    }
+    /// Starts an untimed game from `board`, e.g. a position set up from FEN.
+    pub fn new_from_position(white: PlayerType, black: PlayerType, board: Board) -> Game {
         Game{
-            board: Board::std_new(),
+            position_history: PositionHistory::new(&board),
+            starting_position: board.clone(),
+            board,
             white,
             black,
             game_state: GameState::Running,
             clock: None,
+            time_controls: None,
+            move_stack: Vec::new(),
         }
     }
+    /// Starts a game on the clock. The clock runs from when the game thread starts the game.
     pub fn new_with_time_controls(white: PlayerType, black: PlayerType, time_controls: TimeControls) -> Option<Game> {
         let clock = match ChessClock::new_from_time_controls(time_controls) {
             Some(clock) => Some(clock),
             None => return None,
         };
+        let board = Board::std_new();
         Some(Game{
-            board: Board::std_new(),
+            position_history: PositionHistory::new(&board),
+            starting_position: board.clone(),
+            board,
             white,
             black,
-            game_state: GameState::Running,
+            game_state: GameState::Start,
             clock,
+            time_controls: Some(time_controls),
+            move_stack: Vec::new(),
         })
     }
     fn start_game(&mut self) {
@@ -130,6 +215,213 @@ impl Game{
             self.game_state = GameState::Running;
         }
     }
+    /// Plays an already validated move and ends the game if it leaves the opponent without a
+    /// legal move or leads to an automatic draw.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the mover's flag had fallen, which ends the game on time instead.
+    pub fn play_move(&mut self, chess_move: ChessMove) -> bool {
+        if self.check_flag() {
+            return false;
+        }
+        let undo = chess_move.make_reversible_move(&mut self.board);
+        self.move_stack.push((chess_move, undo));
+        self.position_history.push(&self.board);
+        if let Some(clock) = &mut self.clock {
+            clock.switch_clock();
+        }
+
+        let mut moves = MoveList::new();
+        ChessMove::generate_legal_moves(&self.board, &mut moves);
+        let player = self.board.active_player;
+        if moves.is_empty() {
+            if self.board.is_in_check(player) {
+                self.finish(GameOutcome::win(player.toggle_color(), Termination::Checkmate));
+            } else {
+                self.finish(GameOutcome::draw(Termination::Stalemate));
+            }
+        } else if let Some(draw_reason) = self.draw_status().automatic {
+            self.finish(GameOutcome::draw(draw_reason.into()));
+        }
+        true
+    }
+    /// Takes back the last move, reopening the game if that move ended it.
+    ///
+    /// # Returns
+    ///
+    /// The move taken back, `None` if no move has been played.
+    pub fn undo_move(&mut self) -> Option<ChessMove> {
+        let &(chess_move, undo) = self.move_stack.last()?;
+        // The stack holds each move with the undo made when it was played on this board, which
+        // undo_move always accepts. An error would come too late anyway, the board already changed
+        chess_move
+            .undo_move(&mut self.board, undo)
+            .expect("the last move played is always undone");
+        self.move_stack.pop();
+        self.position_history.pop();
+        if let Some(clock) = &mut self.clock {
+            clock.switch_clock();
+        }
+        self.game_state = GameState::Running;
+        Some(chess_move)
+    }
+    /// The AI playing `color` and the limits it chooses its moves within, on the clock if the
+    /// game is timed.
+    ///
+    /// # Returns
+    ///
+    /// `None` if `color` isn't played by a local AI.
+    fn ai_limits(&self, color: Color) -> Option<(ChessAI, SearchLimits)> {
+        let player = match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        };
+        let PlayerType::LocalAI(ai) = player else { return None };
+        let limits = match (&self.clock, self.time_controls) {
+            (Some(clock), Some(time_controls)) => ai.limits().clone().with_clock(clock, time_controls),
+            _ => ai.limits().clone(),
+        };
+        Some((ChessAI::clone(ai), limits))
+    }
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+    pub fn position_history(&self) -> &PositionHistory {
+        &self.position_history
+    }
+    /// Claims a draw for the player to move.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a draw could be claimed and the game ended.
+    pub fn claim_draw(&mut self) -> bool {
+        match self.draw_status().claimable.first() {
+            Some(&draw_reason) => self.finish(GameOutcome::draw(draw_reason.into())),
+            None => false,
+        }
+    }
+    pub fn agree_draw(&mut self) -> bool {
+        self.finish(GameOutcome::draw(Termination::Agreement))
+    }
+    pub fn resign(&mut self, player: Color) -> bool {
+        self.finish(GameOutcome::win(player.toggle_color(), Termination::Resignation))
+    }
+    pub fn abandon(&mut self, player: Color) -> bool {
+        self.finish(GameOutcome::win(player.toggle_color(), Termination::Abandonment))
+    }
+    pub fn adjudicate(&mut self, winner: Option<Color>) -> bool {
+        self.finish(GameOutcome { winner, termination: Termination::Adjudication })
+    }
+    /// Ends the game if the player to move has run out of time. The game is drawn instead of
+    /// lost when the opponent has only a king left.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the player's flag fell and the game ended.
+    pub fn check_flag(&mut self) -> bool {
+        let player = self.board.active_player;
+        let flagged = match &self.clock {
+            Some(clock) => clock.get_player_time_left(player).is_zero(),
+            None => false,
+        };
+        if !flagged {
+            return false;
+        }
+        let opponent = player.toggle_color();
+        if draw::has_bare_king(&self.board, opponent) {
+            self.finish(GameOutcome::draw(Termination::Timeout))
+        } else {
+            self.finish(GameOutcome::win(opponent, Termination::Timeout))
+        }
+    }
+    /// Ends the game with `outcome`, unless it already ended.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the game was still in play.
+    fn finish(&mut self, outcome: GameOutcome) -> bool {
+        if let GameState::Finished(_) = self.game_state {
+            return false;
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+        }
+        self.game_state = GameState::Finished(outcome);
+        true
+    }
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        match self.game_state {
+            GameState::Finished(outcome) => Some(outcome),
+            GameState::Start | GameState::Running => None,
+        }
+    }
+    /// Time left on `player`'s clock, `None` for untimed games.
+    pub fn time_left(&self, player: Color) -> Option<Duration> {
+        self.clock.as_ref().map(|clock| clock.get_player_time_left(player))
+    }
+    /// The draws that apply to the current position, see [draw::draw_status].
+    pub fn draw_status(&self) -> DrawStatus {
+        draw::draw_status(&self.board, &self.position_history)
+    }
+}
+
+impl GameMetadata {
+    /// Metadata for a game starting now. Names and ratings are unknown until set.
+    pub fn new(game_id: Option<String>) -> GameMetadata {
+        GameMetadata {
+            game_id,
+            start_time: Instant::now(),
+            start_date: DateMillis::new().ok(),
+            event: None,
+            site: None,
+            white_name: None,
+            black_name: None,
+            white_rating: None,
+            black_rating: None,
+        }
+    }
+    pub fn set_event(&mut self, event: &str, site: &str) {
+        self.event = Some(event.to_string());
+        self.site = Some(site.to_string());
+    }
+    pub fn set_player(&mut self, color: Color, name: &str, rating: Option<u32>) {
+        match color {
+            Color::White => {
+                self.white_name = Some(name.to_string());
+                self.white_rating = rating;
+            }
+            Color::Black => {
+                self.black_name = Some(name.to_string());
+                self.black_rating = rating;
+            }
+        }
+    }
+    /// Writes the known metadata into `record` as PGN tags.
+    fn add_pgn_tags(&self, record: &mut GameRecord) {
+        let text_tags = [
+            ("Event", &self.event),
+            ("Site", &self.site),
+            ("White", &self.white_name),
+            ("Black", &self.black_name),
+        ];
+        for (name, value) in text_tags {
+            if let Some(value) = value {
+                record.set_tag(name, value.as_str());
+            }
+        }
+        if let Some(start_date) = &self.start_date {
+            let mut date = start_date.clone();
+            date.set_display_mode(DisplayMode::Pgn);
+            record.set_tag("Date", date.to_string());
+        }
+        if let Some(rating) = self.white_rating {
+            record.set_tag("WhiteElo", rating.to_string());
+        }
+        if let Some(rating) = self.black_rating {
+            record.set_tag("BlackElo", rating.to_string());
+        }
+    }
 }
 
 impl GameThread {
@@ -139,7 +431,7 @@ impl GameThread {
     ///
     ///
     ///
-    pub fn new(game: Game) -> (GameThread, Sender<GameController>) {
+    pub fn new(game: Game, pondering: bool) -> (GameThread, Sender<GameController>) {
         let thread_identifier = ThreadIdentifier::Game(ThreadIdHash::new());
         // let (player_1_in_sender, player_1_in_receiver) = std::sync::mpsc::channel();
         // let (player_2_in_sender, player_2_in_receiver) = std::sync::mpsc::channel();
@@ -159,6 +451,10 @@ impl GameThread {
                 player_2_handler: None,
                 ui_in,
                 move_history: Vec::new(),
+                clock_history: Vec::new(),
+                pondering,
+                ponder: None,
+                ui_out: None,
             }
             , ui_out
         )
@@ -176,9 +472,547 @@ impl GameThread {
 
         self
     }
+    /// Plays the game out: local AIs move as soon as it is their turn, and local humans' moves
+    /// come from the UI. Their clock is watched while they think, so a flag falls on time.
     fn run(&mut self) {
-        loop {
+        while self.game.outcome().is_none() {
+            if self.play_ai_move().is_some() {
+                continue;
+            }
+            match self.ui_in.recv_timeout(FLAG_POLL_INTERVAL) {
+                Ok(GameController::MakeMove(notation)) => {
+                    if let Err(reason) = self.play_human_move(&notation) {
+                        self.send_ui(UiMessage::IllegalMove(reason));
+                    }
+                }
+                Ok(GameController::StartGame) => {}
+                Ok(GameController::StopGame | GameController::AbortThread) | Err(RecvTimeoutError::Disconnected) => {
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.game.check_flag();
+                }
+            }
+        }
+        self.stop_pondering();
+        if let Some(outcome) = self.game.outcome() {
+            self.send_ui(UiMessage::GameOver(outcome));
+        }
+        // Lets the UI see the game has stopped
+        self.ui_out = None;
+    }
+    /// Sends the moves played and the result to `ui_out` from now on.
+    pub fn set_ui(&mut self, ui_out: Sender<UiMessage>) {
+        self.ui_out = Some(ui_out);
+    }
+    fn send_ui(&mut self, message: UiMessage) {
+        if let Some(ui_out) = &self.ui_out {
+            if ui_out.send(message).is_err() {
+                self.ui_out = None;
+            }
+        }
+    }
+    /// Plays a move a local human entered, in coordinate notation or SAN.
+    fn play_human_move(&mut self, notation: &str) -> Result<ChessMove, String> {
+        if self.game.ai_limits(self.game.board.active_player).is_some() {
+            return Err(String::from("It isn't your move"));
+        }
+        let mut board = self.game.board.clone();
+        let chess_move = parse_coordinate_move(&mut board, notation).or_else(|_| {
+            let proto_move = from_simplified_algebraic_notation(notation, board.active_player)
+                .map_err(|_| format!("Invalid move '{}'", notation))?;
+            ChessMove::new_from_proto(&mut board, proto_move).map_err(|_| format!("Illegal move '{}'", notation))
+        })?;
+        self.play_move(chess_move);
+        Ok(chess_move)
+    }
+    pub fn set_metadata(&mut self, game_metadata: GameMetadata) {
+        self.game_metadata = Some(game_metadata);
+    }
+    /// Plays an already validated move and adds it, with the mover's time left, to the history.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the mover's flag had fallen, which ends the game on time instead.
+    pub fn play_move(&mut self, chess_move: ChessMove) -> bool {
+        let mover = self.game.board.active_player;
+        let san = chess_move.to_san(&self.game.board);
+        let played = self.game.play_move(chess_move);
+        if played {
+            self.move_history.push(chess_move);
+            self.clock_history.push(self.game.time_left(mover));
+            self.send_ui(UiMessage::MovePlayed(san));
+            self.send_ui(UiMessage::BoardUpdate(self.game.board.clone()));
+            if let (Some(white), Some(black)) = (self.game.time_left(Color::White), self.game.time_left(Color::Black)) {
+                self.send_ui(UiMessage::TimeLeft(white, black));
+            }
+        }
+        if self.game.outcome().is_some() {
+            self.stop_pondering();
+        }
+        played
+    }
+    /// Lets the local AI to move choose its move and plays it.
+    ///
+    /// If the AI was pondering on the move just played it finishes that search, now on its own
+    /// clock, and otherwise it searches afresh. With a human to move next, it then ponders on
+    /// the reply it expects.
+    ///
+    /// # Returns
+    ///
+    /// The move played, `None` if the game is over, the player to move isn't a local AI or its
+    /// flag fell while it thought.
+    pub fn play_ai_move(&mut self) -> Option<ChessMove> {
+        if self.game.outcome().is_some() {
+            return None;
+        }
+        let (ai, limits) = self.game.ai_limits(self.game.board.active_player)?;
+        let last_move = self.move_history.last().map(|chess_move| chess_move.pack());
+        let pondered = match self.ponder.take() {
+            Some(ponder) if last_move == Some(ponder.expected_reply.pack()) => {
+                ponder.search.ponder_hit();
+                let result = ponder.result.recv().ok();
+                ponder.search.wait();
+                result
+            }
+            Some(ponder) => {
+                ponder.search.stop();
+                None
+            }
+            None => None,
+        };
+        let result = match pondered.filter(|result| result.best_move.is_some()) {
+            Some(result) => result,
+            None => ai.choose_move_within(&self.game.board, &self.game.position_history, &limits),
+        };
+        let chess_move = result.best_move?;
+        if !self.play_move(chess_move) {
+            return None;
+        }
+        if let Some(reply) = result.ponder_move {
+            self.start_pondering(ai, reply);
+        }
+        Some(chess_move)
+    }
+    /// Stops pondering, throwing the search away.
+    pub fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.search.stop();
+        }
+    }
+    /// Starts `ai` searching the position after `reply`, if a human is to move.
+    fn start_pondering(&mut self, ai: ChessAI, reply: ChessMove) {
+        let human = self.game.board.active_player;
+        let human_to_move = self.game.ai_limits(human).is_none();
+        if !self.pondering || !human_to_move || self.game.outcome().is_some() {
+            return;
+        }
+        // The AI's clock stands still while the human thinks, so its limits are the same now
+        // as once the reply is played
+        let Some((_, limits)) = self.game.ai_limits(human.toggle_color()) else { return };
+        let mut board = self.game.board.clone();
+        reply.make_move(&mut board);
+        let mut history = self.game.position_history.clone();
+        history.push(&board);
+        let (sender, result) = mpsc::channel();
+        let search = BackgroundSearch::start(ai, board, history, limits, true, move |event| {
+            if let SearchEvent::Finished(result) = event {
+                let _ = sender.send(result);
+            }
+        });
+        self.ponder = Some(Ponder { expected_reply: reply, search, result });
+    }
+    /// Builds the PGN record of the game so far, with its metadata, time control, clock times
+    /// and, once finished, its result and termination.
+    pub fn to_game_record(&self) -> GameRecord {
+        let mut record = GameRecord::from_moves(self.game.starting_position.clone(), &self.move_history);
+        for (node, clock) in record.mainline.moves.iter_mut().zip(&self.clock_history) {
+            node.clock = *clock;
+        }
+        if let Some(game_metadata) = &self.game_metadata {
+            game_metadata.add_pgn_tags(&mut record);
+        }
+        if let Some(time_controls) = self.game.time_controls {
+            record.set_tag("TimeControl", time_controls.to_pgn_tag());
+        }
+        let outcome = self.game.outcome();
+        if let Some(outcome) = outcome {
+            record.set_tag("Termination", outcome.termination.to_pgn_tag());
+        }
+        record.result = outcome.into();
+        record
+    }
+    /// Appends the game to the PGN file at `path`, creating the file if it doesn't exist. A game
+    /// stopped before its end is saved with the result `*`.
+    pub fn save_game(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Games in a PGN file are separated by a blank line
+        if file.metadata()?.len() > 0 {
+            file.write_all(b"\n")?;
+        }
+        file.write_all(self.to_game_record().to_pgn().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::reader::read_games;
+
+    fn play(game: &mut Game, notations: &[&str]) {
+        for notation in notations {
+            let chess_move = ChessMove::get_valid_moves(&mut game.board)
+                .into_iter()
+                .find(|chess_move| chess_move.to_coordinate_notation() == *notation)
+                .unwrap();
+            game.play_move(chess_move);
+        }
+    }
+
+    #[test]
+    fn checkmate_ends_the_game() {
+        let mut game = Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman);
+        play(&mut game, &["f2f3", "e7e5", "g2g4"]);
+        assert_eq!(game.outcome(), None);
+        play(&mut game, &["d8h4"]);
+        assert_eq!(game.outcome(), Some(GameOutcome::win(Color::Black, Termination::Checkmate)));
+        assert_eq!(game.game_state.to_pgn_result(), "0-1");
+        // A finished game can't end again
+        assert!(!game.resign(Color::Black));
+    }
+
+    #[test]
+    fn exports_the_game_as_pgn() {
+        let time_controls = TimeControls {
+            initial_time_white: Duration::from_secs(300),
+            initial_time_black: Duration::from_secs(300),
+            time_per_move_white: Duration::from_secs(2),
+            time_per_move_black: Duration::from_secs(2),
+        };
+        let game = Game::new_with_time_controls(PlayerType::LocalHuman, PlayerType::LocalHuman, time_controls).unwrap();
+        let (mut game_thread, _ui_out) = GameThread::new(game, true);
+        let mut game_metadata = GameMetadata::new(None);
+        game_metadata.set_event("Club Championship", "Home");
+        game_metadata.set_player(Color::White, "White, Player", Some(1500));
+        game_metadata.set_player(Color::Black, "Black, Player", None);
+        game_thread.set_metadata(game_metadata);
+
+        for notation in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let chess_move = ChessMove::get_valid_moves(&mut game_thread.game.board)
+                .into_iter()
+                .find(|chess_move| chess_move.to_coordinate_notation() == notation)
+                .unwrap();
+            game_thread.play_move(chess_move);
+        }
+        let pgn = game_thread.to_game_record().to_pgn();
+        let record = GameRecord::from_pgn(&pgn).unwrap();
+        assert_eq!(record.tag("Event"), Some("Club Championship"));
+        assert_eq!(record.tag("White"), Some("White, Player"));
+        assert_eq!(record.tag("WhiteElo"), Some("1500"));
+        assert_eq!(record.tag("BlackElo"), None);
+        assert_eq!(record.tag("TimeControl"), Some("300+2"));
+        assert_eq!(record.tag("Result"), Some("0-1"));
+        assert_eq!(record.outcome(), game_thread.game.outcome());
+        assert_eq!(record.mainline.moves[3].clock, Some(Duration::from_secs(300)));
+        assert!(record.tag("Date").is_some(), "{}", pgn);
+
+        // The date is written in UTC, around month, year and leap day ends
+        let dates = [
+            (0, "1970.01.01"),
+            (68_169_600_000, "1972.02.29"),
+            (94_694_400_000, "1973.01.01"),
+            (946_684_799_999, "1999.12.31"),
+            (946_684_800_000, "2000.01.01"),
+            (951_782_400_000, "2000.02.29"),
+            (1_677_628_799_999, "2023.02.28"),
+            (1_677_628_800_000, "2023.03.01"),
+            (1_704_067_199_999, "2023.12.31"),
+            (1_704_067_200_000, "2024.01.01"),
+            (1_709_164_799_999, "2024.02.28"),
+            (1_709_164_800_000, "2024.02.29"),
+            (1_709_251_200_000, "2024.03.01"),
+            (1_735_689_599_999, "2024.12.31"),
+            (1_735_689_600_000, "2025.01.01"),
+            (1_792_195_200_000, "2026.10.17"),
+        ];
+        for (millis, date) in dates {
+            let mut game_metadata = GameMetadata::new(None);
+            game_metadata.start_date = Some(DateMillis::from_unix_millis(millis));
+            game_thread.set_metadata(game_metadata);
+            assert_eq!(game_thread.to_game_record().tag("Date"), Some(date), "{}", millis);
+        }
+        assert!(pgn.ends_with("1. f3 {[%clk 0:05:00]} 1... e5 {[%clk 0:05:00]} 2. g4 {[%clk 0:05:00]} 2... Qh4#\n{[%clk 0:05:00]} 0-1\n"), "{}", pgn);
+    }
 
+    #[test]
+    fn undoes_moves_and_reopens_the_game() {
+        let mut game = Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman);
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert!(game.outcome().is_some());
+        assert_eq!(game.undo_move().unwrap().to_coordinate_notation(), "d8h4");
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.board().to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2");
+        for _ in 0..3 {
+            game.undo_move();
         }
+        assert_eq!(game.board().to_fen(), Board::std_new().to_fen());
+        assert_eq!(game.position_history().len(), 1);
+        assert!(game.undo_move().is_none());
+    }
+
+    #[test]
+    fn claims_and_resignations() {
+        let mut game = Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman);
+        assert!(!game.claim_draw());
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle);
+        assert!(game.claim_draw());
+        assert_eq!(game.outcome(), Some(GameOutcome::draw(Termination::Repetition)));
+
+        let mut game = Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman);
+        assert!(game.resign(Color::White));
+        let outcome = game.outcome().unwrap();
+        assert_eq!(outcome.winner, Some(Color::Black));
+        assert_eq!(outcome.termination.to_pgn_tag(), "resignation");
+    }
+
+    #[test]
+    fn saves_each_game_to_the_pgn_file() {
+        let path = std::env::temp_dir().join(format!("rusty_chess_saved_games_{}.pgn", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for (white, moves) in [("First", ["f2f3", "e7e5", "g2g4", "d8h4"]), ("Second", ["e2e4", "e7e5", "g1f3", "b8c6"])] {
+            let game = Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman);
+            let (mut game_thread, _ui_out) = GameThread::new(game, false);
+            let mut game_metadata = GameMetadata::new(None);
+            game_metadata.set_player(Color::White, white, None);
+            game_thread.set_metadata(game_metadata);
+            for notation in moves {
+                play_thread(&mut game_thread, notation);
+            }
+            game_thread.save_game(&path).unwrap();
+        }
+
+        let pgn = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let games: Vec<GameRecord> = read_games(&pgn).into_iter().map(Result::unwrap).collect();
+        assert_eq!(games.len(), 2, "{}", pgn);
+        assert_eq!(games[0].tag("White"), Some("First"));
+        assert_eq!(games[0].outcome(), Some(GameOutcome::win(Color::Black, Termination::Checkmate)));
+        assert_eq!(games[1].tag("White"), Some("Second"));
+        assert_eq!(games[1].tag("Result"), Some("*"));
+        assert_eq!(games[1].mainline_moves().len(), 4);
+    }
+
+    #[test]
+    fn a_fallen_flag_ends_the_game_on_the_next_move() {
+        let time_controls = TimeControls {
+            initial_time_white: Duration::from_secs(60),
+            initial_time_black: Duration::from_secs(60),
+            time_per_move_white: Duration::ZERO,
+            time_per_move_black: Duration::ZERO,
+        };
+        let timed_game = |fen: &str, flagged: Color| {
+            let mut game = Game::new_with_time_controls(PlayerType::LocalHuman, PlayerType::LocalHuman, time_controls).unwrap();
+            game.board = Board::from_fen(fen).unwrap();
+            game.position_history = PositionHistory::new(&game.board);
+            game.clock.as_mut().unwrap().set_player_time_left(flagged, Duration::ZERO);
+            game
+        };
+        let first_move = |game: &mut Game| ChessMove::get_valid_moves(&mut game.board)[0];
+
+        // Black runs out of time against a rook, the move isn't played
+        let mut game = timed_game("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", Color::Black);
+        let chess_move = first_move(&mut game);
+        assert!(!game.play_move(chess_move));
+        assert_eq!(game.outcome(), Some(GameOutcome::win(Color::White, Termination::Timeout)));
+        assert_eq!(game.board.to_fen(), "4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+
+        // White runs out of time, but a bare king can't mate
+        let mut game = timed_game("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Color::White);
+        let chess_move = first_move(&mut game);
+        assert!(!game.play_move(chess_move));
+        assert_eq!(game.outcome(), Some(GameOutcome::draw(Termination::Timeout)));
+
+        // With time left the move is played
+        let mut game = timed_game("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", Color::Black);
+        let chess_move = first_move(&mut game);
+        assert!(game.play_move(chess_move));
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn local_ai_plays_its_own_moves() {
+        let ai = ChessAI::new(crate::engine::SearchLimits {
+            depth: Some(2),
+            ..Default::default()
+        });
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let game = Game::new_from_position(PlayerType::LocalAI(Box::new(ai)), PlayerType::LocalHuman, Board::from_fen(fen).unwrap());
+        let (mut game_thread, _ui_out) = GameThread::new(game, false);
+        assert_eq!(game_thread.play_ai_move().unwrap().to_coordinate_notation(), "h5f7");
+        assert_eq!(game_thread.game.outcome(), Some(GameOutcome::win(Color::White, Termination::Checkmate)));
+        assert!(game_thread.play_ai_move().is_none());
+
+        let (mut game_thread, _ui_out) = GameThread::new(Game::new(PlayerType::LocalHuman, PlayerType::LocalHuman), false);
+        assert!(game_thread.play_ai_move().is_none());
+    }
+
+    fn ai_at(level: u8) -> ChessAI {
+        let mut ai = ChessAI::default();
+        ai.set_skill(SkillLevel::new(level).unwrap());
+        ai
+    }
+
+    #[test]
+    fn players_have_their_own_skill() {
+        let game = Game::new(PlayerType::LocalAI(Box::new(ai_at(0))), PlayerType::LocalAI(Box::new(ai_at(20))));
+        assert_eq!(game.white.skill(), SkillLevel::new(0));
+        assert_eq!(game.black.skill(), Some(SkillLevel::MAX));
+        assert_eq!(PlayerType::LocalHuman.skill(), None);
+
+        let game = Game::against_ai(Color::Black, ai_at(5), None).unwrap();
+        assert_eq!(game.white.skill(), SkillLevel::new(5));
+        assert_eq!(game.black.skill(), None);
+    }
+
+    #[test]
+    fn the_ai_plays_on_the_clock() {
+        let time_controls: TimeControls = "60+1".parse().unwrap();
+        let game = Game::against_ai(Color::White, ai_at(0), Some(time_controls)).unwrap();
+        let (game_thread, game_controller) = GameThread::new(game, false);
+        let (ui_out, ui_in) = mpsc::channel();
+        let mut game_thread = game_thread;
+        game_thread.set_ui(ui_out);
+        let game_handle = thread::spawn(move || game_thread.start());
+
+        game_controller.send(GameController::MakeMove(String::from("e4"))).unwrap();
+        let mut moves = ui_in.iter().filter(|message| matches!(message, UiMessage::MovePlayed(_)));
+        moves.next().unwrap();
+        moves.next().unwrap();
+        game_controller.send(GameController::StopGame).unwrap();
+        let game_thread = game_handle.join().unwrap();
+
+        // The clock started with the game, and each move earned its increment
+        for (color, clock) in [Color::White, Color::Black].into_iter().zip(&game_thread.clock_history) {
+            let clock = clock.unwrap();
+            assert!(clock < Duration::from_secs(61), "{:?}", clock);
+            assert!(clock > Duration::from_secs(55), "{:?}", clock);
+            assert!(game_thread.game.time_left(color).unwrap() < Duration::from_secs(61));
+        }
+        // The AI thinks within the time it has left
+        let (_, limits) = game_thread.game.ai_limits(Color::Black).unwrap();
+        assert_eq!(limits.black_time, game_thread.game.time_left(Color::Black));
+        assert_eq!(limits.black_increment, Duration::from_secs(1));
+        assert!(limits.time_budget(Color::Black).unwrap().soft < Duration::from_secs(5));
+    }
+
+    fn play_thread(game_thread: &mut GameThread, notation: &str) {
+        let chess_move = ChessMove::get_valid_moves(&mut game_thread.game.board)
+            .into_iter()
+            .find(|chess_move| chess_move.to_coordinate_notation() == notation)
+            .unwrap();
+        game_thread.play_move(chess_move);
+    }
+
+    fn expected_reply(game_thread: &GameThread) -> String {
+        game_thread.ponder.as_ref().unwrap().expected_reply.to_coordinate_notation()
+    }
+
+    #[test]
+    fn ponders_on_the_human_s_time() {
+        let ai = ChessAI::new(SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        });
+        let (mut game_thread, _ui_out) = GameThread::new(Game::new(PlayerType::LocalHuman, PlayerType::LocalAI(Box::new(ai.clone()))), true);
+        play_thread(&mut game_thread, "e2e4");
+        assert!(game_thread.play_ai_move().is_some());
+
+        // A hit carries on with the pondering search, and the AI ponders on again
+        let reply = expected_reply(&game_thread);
+        play_thread(&mut game_thread, &reply);
+        assert!(game_thread.play_ai_move().is_some());
+        assert!(game_thread.ponder.is_some());
+
+        // A miss throws it away and searches afresh
+        let reply = expected_reply(&game_thread);
+        let other = ChessMove::get_valid_moves(&mut game_thread.game.board)
+            .into_iter()
+            .find(|chess_move| chess_move.to_coordinate_notation() != reply)
+            .unwrap();
+        game_thread.play_move(other);
+        assert!(game_thread.ponder.is_some());
+        assert!(game_thread.play_ai_move().is_some());
+
+        game_thread.stop_pondering();
+        assert!(game_thread.ponder.is_none());
+
+        // A thread made with pondering off never starts
+        let (mut game_thread, _ui_out) = GameThread::new(Game::new(PlayerType::LocalHuman, PlayerType::LocalAI(Box::new(ai))), false);
+        play_thread(&mut game_thread, "e2e4");
+        assert!(game_thread.play_ai_move().is_some());
+        assert!(game_thread.ponder.is_none());
+    }
+
+    #[test]
+    fn runs_the_game_for_the_ui() {
+        let ai = ChessAI::new(SearchLimits {
+            depth: Some(1),
+            ..Default::default()
+        });
+        let game = Game::new(PlayerType::LocalHuman, PlayerType::LocalAI(Box::new(ai)));
+        let (mut game_thread, game_controller) = GameThread::new(game, true);
+        let (ui_out, ui_in) = mpsc::channel();
+        game_thread.set_ui(ui_out);
+        let game_handle = thread::spawn(move || game_thread.start());
+
+        let mut next_move = || loop {
+            match ui_in.recv().unwrap() {
+                UiMessage::MovePlayed(san) => return Ok(san),
+                UiMessage::IllegalMove(reason) => return Err(reason),
+                _ => {}
+            }
+        };
+        game_controller.send(GameController::MakeMove(String::from("e2e4"))).unwrap();
+        assert_eq!(next_move(), Ok(String::from("e4")));
+        assert!(next_move().is_ok());
+        game_controller.send(GameController::MakeMove(String::from("e2e4"))).unwrap();
+        assert_eq!(next_move(), Err(String::from("Illegal move 'e2e4'")));
+        game_controller.send(GameController::MakeMove(String::from("Nf3"))).unwrap();
+        assert_eq!(next_move(), Ok(String::from("Nf3")));
+        assert!(next_move().is_ok());
+
+        game_controller.send(GameController::StopGame).unwrap();
+        let game_thread = game_handle.join().unwrap();
+        assert_eq!(game_thread.move_history.len(), 4);
+        assert!(game_thread.ponder.is_none());
+        assert!(ui_in.iter().all(|message| matches!(message, UiMessage::BoardUpdate(_))));
+    }
+
+    #[test]
+    fn pondering_is_not_billed_to_the_ai() {
+        let time_controls = TimeControls {
+            initial_time_white: Duration::from_secs(60),
+            initial_time_black: Duration::from_secs(60),
+            time_per_move_white: Duration::ZERO,
+            time_per_move_black: Duration::ZERO,
+        };
+        let ai = ChessAI::new(SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        });
+        let game = Game::new_with_time_controls(PlayerType::LocalAI(Box::new(ai)), PlayerType::LocalHuman, time_controls).unwrap();
+        let (mut game_thread, _ui_out) = GameThread::new(game, true);
+        game_thread.game.clock.as_mut().unwrap().start();
+        game_thread.play_ai_move().unwrap();
+        assert!(game_thread.ponder.is_some());
+
+        let ai_time = game_thread.game.time_left(Color::White);
+        let human_time = game_thread.game.time_left(Color::Black);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(game_thread.game.time_left(Color::White), ai_time);
+        assert!(game_thread.game.time_left(Color::Black) < human_time);
+        game_thread.stop_pondering();
     }
 }
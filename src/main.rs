@@ -2,30 +2,47 @@ mod ai;
 mod board;
 mod chess_bot;
 mod chess_moves;
+mod cli;
 mod clock;
 mod common;
+mod engine;
 mod config;
 mod game;
 mod handler;
 mod log;
 mod move_parser;
+mod perft;
+mod pgn;
 mod player_agent;
 mod rules;
 mod time;
 mod ui;
 
+use std::path::Path;
+use std::sync::mpsc;
 use std::thread;
 
-use crate::board::pieces::Piece;
+use crate::ai::ChessAI;
+use crate::board::pieces::{Color, Piece};
 use crate::board::Board;
 use crate::chess_moves::ChessMove;
 use crate::common::{ThreadIdentifier};
 use crate::common::common_lib;
 use crate::config::{parse_config, Config, ConfigResult};
+use crate::game::{Game, GameMetadata, GameThread};
 use crate::log::{LogLevel, LogMessage, LogOutput};
 use crate::ui::{UIManager,UIType};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args) {
+        if let Err(message) = result {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut b = Board::std_new();
     let mut e = Board::empty_new();
     println!("{}", b.to_string());
@@ -89,10 +106,47 @@ fn main() {
     }
 
     // Initialize ui
+    let in_terminal = matches!(ui_type, UIType::Terminal);
     let mut ui_thread = UIManager::new(Some(log_channel.clone()));
     ui_thread.set_ui_type(ui_type);
     let ui_handle = ui_thread.start();
 
+    // Play against the AI at the configured strength, pondering unless the config turns it off
+    if in_terminal {
+        println!("You play White against the AI at level {}, about {} Elo.", config.ai_skill, config.ai_skill.elo());
+        let mut ai = ChessAI::default();
+        ai.set_skill(config.ai_skill);
+        if let Some(book_file) = &config.book_file {
+            match config.book_settings.load(book_file) {
+                Ok(book) => ai.set_book(book),
+                Err(error) => println!("Could not load the opening book, {}", error),
+            }
+        }
+        match Game::against_ai(Color::White, ai, config.time_control) {
+            Some(game) => {
+                let (mut game_thread, game_controller) = GameThread::new(game, config.ponder);
+                let mut game_metadata = GameMetadata::new(None);
+                game_metadata.set_event("Casual game", "rusty_chess");
+                game_metadata.set_player(Color::White, "Player", None);
+                game_metadata.set_player(Color::Black, &format!("rusty_chess level {}", config.ai_skill), Some(config.ai_skill.elo()));
+                game_thread.set_metadata(game_metadata);
+                let (game_ui_out, game_ui_in) = mpsc::channel();
+                game_thread.set_ui(game_ui_out);
+                let game_handle = thread::spawn(move || game_thread.start());
+                ui::play_in_terminal(game_controller, game_ui_in);
+                match (game_handle.join(), &config.pgn_file) {
+                    (Err(_), _) => println!("Game panicked"),
+                    (Ok(game_thread), Some(pgn_file)) => match game_thread.save_game(Path::new(pgn_file)) {
+                        Ok(()) => println!("Game saved to {}", pgn_file),
+                        Err(error) => println!("Could not save the game to {}: {}", pgn_file, error),
+                    },
+                    (Ok(_), None) => {}
+                }
+            }
+            None => println!("The clock can't be set to the configured time control"),
+        }
+    }
+
     let test = log_channel.send(LogMessage::Message(
         main_id,
         LogLevel::Info,
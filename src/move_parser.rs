@@ -1,7 +1,7 @@
 use crate::board::pieces::{Color, Piece};
 use crate::board::{Board, Square, SquareExt};
 use crate::board::square::{Col, ColExt, Row, RowExt};
-use crate::chess_moves::{ChessMove,MoveError,Disambiguity};
+use crate::chess_moves::{ChessMove, MoveError, Disambiguity, MoveList};
 use crate::rules::{MoveResult, MoveType, CastleType};
 
 pub const PIECE_MAP: &[(&str, char)] = &[
@@ -34,7 +34,7 @@ pub const PIECE_MAP: &[(&str, char)] = &[
     ("🏰", 'R'), ("🏯", 'R'),
     ("飛", 'R'), ("車", 'R'), ("俥", 'R'), // Shogi & Xiangqi Rooks
 ];
-pub const SORTED_PIECE_MAP: &[(char, char)] = &[('+', '+'), ('-', '-'), ('0', '0'), ('1', '1'), ('2', '2'), ('3', '3'), ('4', '4'), ('5', '5'), ('6', '6'), ('7', '7'), ('8', '8'), ('=', '='), ('>', '>'), ('B', 'B'), ('K', 'K'), ('N', 'N'), ('O', 'O'), ('P', 'P'), ('Q', 'Q'), ('R', 'R'), ('a', 'a'), ('b', 'b'), ('c', 'c'), ('d', 'd'), ('e', 'e'), ('f', 'f'), ('g', 'g'), ('h', 'h'), ('x', 'x'), ('ᴋ', 'K'), ('ᴎ', 'N'), ('⒝', 'B'), ('⒦', 'K'), ('⒩', 'N'), ('⒫', 'P'), ('⒬', 'Q'), ('⒭', 'R'), ('Ⓚ', 'K'), ('Ⓝ', 'N'), ('Ⓟ', 'P'), ('Ⓠ', 'Q'), ('Ⓡ', 'R'), ('ⓑ', 'B'), ('♔', 'K'), ('♕', 'Q'), ('♖', 'R'), ('♗', 'B'), ('♘', 'N'), ('♙', 'P'), ('♚', 'K'), ('♛', 'Q'), ('♜', 'R'), ('♝', 'B'), ('♞', 'N'), ('♟', 'P'), ('㋛', 'Q'), ('㋜', 'R'), ('㋝', 'B'), ('㋞', 'K'), ('俥', 'R'), ('傌', 'N'), ('兵', 'P'), ('卒', 'P'), ('将', 'K'), ('帅', 'K'), ('桂', 'N'), ('歩', 'P'), ('王', 'K'), ('角', 'B'), ('象', 'B'), ('車', 'R'), ('飛', 'R'), ('馬', 'N'), ('🄌', 'Q'), ('🄍', 'R'), ('🄑', 'B'), ('🄚', 'K'), ('🄝', 'N'), ('🄟', 'P'), ('🄺', 'K'), ('🅀', 'Q'), ('🅁', 'R'), ('🅑', 'B'), ('🅚', 'K'), ('🅝', 'N'), ('🅟', 'P'), ('🅺', 'K'), ('🏃', 'P'), ('🏇', 'N'), ('🏯', 'R'), ('🏰', 'R'), ('🐴', 'N'), ('👑', 'K'), ('👑', 'Q'), ('👸', 'Q'), ('🚶', 'P'), ('🤴', 'K'), ('🦄', 'N')];
+pub const SORTED_PIECE_MAP: &[(char, char)] = &[('#', '#'), ('+', '+'), ('-', '-'), ('0', '0'), ('1', '1'), ('2', '2'), ('3', '3'), ('4', '4'), ('5', '5'), ('6', '6'), ('7', '7'), ('8', '8'), ('=', '='), ('>', '>'), ('B', 'B'), ('K', 'K'), ('N', 'N'), ('O', 'O'), ('P', 'P'), ('Q', 'Q'), ('R', 'R'), ('a', 'a'), ('b', 'b'), ('c', 'c'), ('d', 'd'), ('e', 'e'), ('f', 'f'), ('g', 'g'), ('h', 'h'), ('x', 'x'), ('ᴋ', 'K'), ('ᴎ', 'N'), ('‡', '‡'), ('⒝', 'B'), ('⒦', 'K'), ('⒩', 'N'), ('⒫', 'P'), ('⒬', 'Q'), ('⒭', 'R'), ('Ⓚ', 'K'), ('Ⓝ', 'N'), ('Ⓟ', 'P'), ('Ⓠ', 'Q'), ('Ⓡ', 'R'), ('ⓑ', 'B'), ('♔', 'K'), ('♕', 'Q'), ('♖', 'R'), ('♗', 'B'), ('♘', 'N'), ('♙', 'P'), ('♚', 'K'), ('♛', 'Q'), ('♜', 'R'), ('♝', 'B'), ('♞', 'N'), ('♟', 'P'), ('㋛', 'Q'), ('㋜', 'R'), ('㋝', 'B'), ('㋞', 'K'), ('俥', 'R'), ('傌', 'N'), ('兵', 'P'), ('卒', 'P'), ('将', 'K'), ('帅', 'K'), ('桂', 'N'), ('歩', 'P'), ('王', 'K'), ('角', 'B'), ('象', 'B'), ('車', 'R'), ('飛', 'R'), ('馬', 'N'), ('🄌', 'Q'), ('🄍', 'R'), ('🄑', 'B'), ('🄚', 'K'), ('🄝', 'N'), ('🄟', 'P'), ('🄺', 'K'), ('🅀', 'Q'), ('🅁', 'R'), ('🅑', 'B'), ('🅚', 'K'), ('🅝', 'N'), ('🅟', 'P'), ('🅺', 'K'), ('🏃', 'P'), ('🏇', 'N'), ('🏯', 'R'), ('🏰', 'R'), ('🐴', 'N'), ('👑', 'K'), ('👑', 'Q'), ('👸', 'Q'), ('🚶', 'P'), ('🤴', 'K'), ('🦄', 'N')];
 #[derive(Debug)]
 pub enum ParseError{
     MissingPiece,
@@ -515,57 +515,74 @@ pub(crate) mod chess_notation_parser {
     }
     
     impl ChessMove {
+        /// Resolves a parsed move against the legal moves of `board`.
+        ///
+        /// # Returns
+        /// - `Ok(ChessMove)`: The only legal move matching every part of `proto_move`.
+        /// - `Err(ParseError::IllegalMoveError)`: No legal move matches, or the notation fits
+        ///   several moves and needs the returned disambiguation.
         pub fn new_from_proto(board: &mut Board, proto_move: ProtoMove) -> Result<ChessMove, ParseError> {
+            let mut legal_moves = MoveList::new();
+            ChessMove::generate_legal_moves(board, &mut legal_moves);
+
             // Castle
             match proto_move.move_type {
                 MoveType::Castling => {
-                    return match proto_move.castle_type {
-                        Some(castle_type) => { match ChessMove::new_castle(board, castle_type) {
-                                Ok(chess_move) => Ok(chess_move),
-                                Err(e) => Err(ParseError::IllegalMoveError(e))
-                        }}
-                        None => Err(ParseError::MissingCastleType)
-                    }
+                    let castle_type = proto_move.castle_type.ok_or(ParseError::MissingCastleType)?;
+                    let king_target = board.active_player.king_castle_target(castle_type);
+                    return legal_moves
+                        .iter()
+                        .find(|chess_move| chess_move.get_meta_data().is_castle() && chess_move.get_target() == king_target)
+                        .copied()
+                        .ok_or(ParseError::IllegalMoveError(MoveError::CastleNotPermmited));
                 }
                 MoveType::Regular | MoveType::Promotion | MoveType::EnPassant => {}
             }
 
-            let target_square: Square;
             // Get rid of bad variants
             if proto_move.piece.is_none() && proto_move.origin == Disambiguity::None{
                 return Err(ParseError::MissingPiece)
             }
-            if let Some(target) = proto_move.target {
-                target_square = target;
-            } else {
-                return Err(ParseError::MissingTargetError)
-            }
-
-            // If promotion, pass promotion piece
-            if let Some(promotion_piece) = proto_move.promotion_piece {
-                return match if let Disambiguity::Square(origin_square) = proto_move.origin {
-                            ChessMove::valid_new(board, promotion_piece, origin_square, target_square, true)
-                        } else {
-                            ChessMove::new_with_disambiguation(board, promotion_piece, proto_move.origin, target_square, true)
-                        } {
-                    Ok(chess_move) => Ok(chess_move),
-                    Err(e) => Err(ParseError::IllegalMoveError(e))
-                };
+            let target_square = proto_move.target.ok_or(ParseError::MissingTargetError)?;
+            if let (None, Disambiguity::Square(origin_square)) = (proto_move.piece, &proto_move.origin) {
+                if !board.is_square_occupied(*origin_square) {
+                    return Err(ParseError::IllegalMoveError(MoveError::PieceNotFound(*origin_square)));
+                }
             }
 
-            let result: Result<ChessMove, MoveError> = match (proto_move.piece, proto_move.origin) {
-                (None, Disambiguity::None) => return Err(ParseError::MissingPiece),
-                (Some(piece), origin) => {
-                    ChessMove::new_with_disambiguation(board, piece, origin, target_square, false)
-                }
-                (_, Disambiguity::Square(origin_square)) => {
-                    ChessMove::new_from_squares(board, origin_square, target_square, false)
+            // Promotions are generated as the piece promoted to
+            let moved_piece = proto_move.promotion_piece.or(proto_move.piece);
+            let candidates: Vec<ChessMove> = legal_moves
+                .iter()
+                .filter(|chess_move| {
+                    let origin = chess_move.get_origin();
+                    chess_move.get_target() == target_square
+                        && chess_move.get_meta_data().is_promotion() == proto_move.promotion_piece.is_some()
+                        && moved_piece.is_none_or(|piece| chess_move.get_piece() == piece)
+                        && match proto_move.origin {
+                            Disambiguity::None => true,
+                            Disambiguity::File(col) => origin.get_col() == col,
+                            Disambiguity::Rank(row) => origin.get_row() == row,
+                            Disambiguity::Square(square) => origin == square,
+                        }
+                })
+                .copied()
+                .collect();
+
+            match candidates.len() {
+                1 => Ok(candidates[0]),
+                0 => {
+                    let promotion_required = proto_move.promotion_piece.is_none()
+                        && legal_moves.iter().any(|chess_move| {
+                            chess_move.get_meta_data().is_promotion() && chess_move.get_target() == target_square
+                        });
+                    Err(ParseError::IllegalMoveError(match (promotion_required, proto_move.piece) {
+                        (true, _) => MoveError::PromotionRequired,
+                        (false, Some(piece)) => MoveError::OriginNotFound(piece),
+                        (false, None) => MoveError::IllegalMove,
+                    }))
                 }
-                (None, _) => return Err(ParseError::MissingPiece)
-            };
-            match result {
-                Ok(chess_move) => Ok(chess_move),
-                Err(e) => Err(ParseError::IllegalMoveError(e))
+                _ => Err(ParseError::DisambiguousMoveError(ChessMove::disambiguate_from_moves(&candidates))),
             }
         }
     }
@@ -0,0 +1,207 @@
+//! Perft (performance test) move path enumeration.
+//!
+//! Counts the leaf nodes of the legal move tree to a fixed depth. Comparing the counts against
+//! published values for well known positions is the standard way of verifying a move generator,
+//! including the make and undo paths every search relies on.
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::chess_moves::{ChessMove, MoveList};
+
+/// A position with its published perft node counts.
+pub struct PerftPosition {
+    pub name: &'static str,
+    pub fen: &'static str,
+    /// Node counts for depth 1, 2, 3, ...
+    pub node_counts: &'static [u64],
+}
+
+/// The standard reference positions, see <https://www.chessprogramming.org/Perft_Results>.
+pub const REFERENCE_POSITIONS: [PerftPosition; 7] = [
+    PerftPosition {
+        name: "Start position",
+        fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        node_counts: &[20, 400, 8_902, 197_281, 4_865_609, 119_060_324],
+    },
+    PerftPosition {
+        name: "Kiwipete",
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        node_counts: &[48, 2_039, 97_862, 4_085_603, 193_690_690],
+    },
+    PerftPosition {
+        name: "Position 3",
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        node_counts: &[14, 191, 2_812, 43_238, 674_624, 11_030_083],
+    },
+    PerftPosition {
+        name: "Position 4",
+        fen: "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        node_counts: &[6, 264, 9_467, 422_333, 15_833_292],
+    },
+    PerftPosition {
+        name: "Position 4 mirrored",
+        fen: "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        node_counts: &[6, 264, 9_467, 422_333, 15_833_292],
+    },
+    PerftPosition {
+        name: "Position 5",
+        fen: "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        node_counts: &[44, 1_486, 62_379, 2_103_487, 89_941_194],
+    },
+    PerftPosition {
+        name: "Position 6",
+        fen: "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        node_counts: &[46, 2_079, 89_890, 3_894_594, 164_075_551],
+    },
+];
+
+/// Counts the leaf nodes of the legal move tree below `board`.
+///
+/// # Arguments
+///
+/// * `board` - The position to search from, left unchanged on return.
+/// * `depth` - The number of plies to play out.
+///
+/// # Returns
+///
+/// The number of positions reached after exactly `depth` plies.
+pub fn perft(board: &mut Board, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = MoveList::new();
+    ChessMove::generate_legal_moves(board, &mut moves);
+    // The generator only emits legal moves, so the last ply needs no make and undo
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for &chess_move in &moves {
+        let undo = chess_move.make_reversible_move(board);
+        nodes += perft(board, depth - 1);
+        let _ = chess_move.undo_move(board, undo);
+    }
+    nodes
+}
+
+/// Runs perft below every legal move of `board` separately.
+///
+/// # Arguments
+///
+/// * `board` - The position to search from, left unchanged on return.
+/// * `depth` - The number of plies to play out, including the root move.
+///
+/// # Returns
+///
+/// Each root move paired with the node count of its subtree.
+pub fn perft_divide(board: &mut Board, depth: u8) -> Vec<(ChessMove, u64)> {
+    let mut divide = Vec::new();
+    if depth == 0 {
+        return divide;
+    }
+    let mut moves = MoveList::new();
+    ChessMove::generate_legal_moves(board, &mut moves);
+    for &chess_move in &moves {
+        let undo = chess_move.make_reversible_move(board);
+        let nodes = perft(board, depth - 1);
+        let _ = chess_move.undo_move(board, undo);
+        divide.push((chess_move, nodes));
+    }
+    divide
+}
+
+/// Outcome of checking a reference position at one depth.
+pub struct PerftResult {
+    pub name: &'static str,
+    pub depth: u8,
+    pub expected: u64,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl PerftResult {
+    pub fn passed(&self) -> bool {
+        self.expected == self.nodes
+    }
+}
+
+/// Runs every reference position up to `max_depth`, skipping depths that would visit more than
+/// `max_nodes` nodes.
+///
+/// # Returns
+///
+/// One `PerftResult` per position and depth searched.
+pub fn run_reference_suite(max_depth: u8, max_nodes: u64) -> Vec<PerftResult> {
+    let mut results = Vec::new();
+    for position in REFERENCE_POSITIONS.iter() {
+        let mut board = Board::from_fen(position.fen).expect("reference FEN is valid");
+        for (index, &expected) in position.node_counts.iter().enumerate() {
+            let depth = index as u8 + 1;
+            if depth > max_depth || expected > max_nodes {
+                break;
+            }
+            let start = Instant::now();
+            let nodes = perft(&mut board, depth);
+            results.push(PerftResult {
+                name: position.name,
+                depth,
+                expected,
+                nodes,
+                elapsed: start.elapsed(),
+            });
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Debug builds are slow, so the test suite stops at this many nodes per position.
+    const TEST_NODE_LIMIT: u64 = 100_000;
+
+    #[test]
+    fn reference_positions_match_published_counts() {
+        let results = run_reference_suite(u8::MAX, TEST_NODE_LIMIT);
+        assert!(results.len() >= REFERENCE_POSITIONS.len() * 2);
+        for result in results {
+            assert_eq!(result.nodes, result.expected, "{} at depth {}", result.name, result.depth);
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let mut board = Board::from_fen(REFERENCE_POSITIONS[1].fen).unwrap();
+        let divide = perft_divide(&mut board, 2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2_039);
+        assert!(divide.iter().any(|(chess_move, _)| chess_move.to_coordinate_notation() == "e1g1"));
+    }
+
+    /// Plays out the whole tree, checking every undo restores the exact position.
+    fn assert_undo_restores(board: &mut Board, depth: u8) {
+        if depth == 0 {
+            return;
+        }
+        let fen = board.to_fen();
+        let key = board.zobrist_key();
+        for chess_move in ChessMove::get_valid_moves(board) {
+            let undo = chess_move.make_reversible_move(board);
+            assert_undo_restores(board, depth - 1);
+            let _ = chess_move.undo_move(board, undo);
+            assert_eq!(board.to_fen(), fen, "after undoing {}", chess_move);
+            assert_eq!(board.zobrist_key(), key);
+        }
+    }
+
+    #[test]
+    fn undo_restores_castling_positions() {
+        // Both sides can castle either way, and castling rights are lost by rook captures
+        let mut board = Board::from_fen(REFERENCE_POSITIONS[1].fen).unwrap();
+        assert_undo_restores(&mut board, 3);
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_undo_restores(&mut board, 3);
+    }
+}
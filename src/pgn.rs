@@ -0,0 +1,232 @@
+//! Portable Game Notation (PGN) support.
+//!
+//! A PGN file holds any number of games, each a tag section of `[Name "value"]` pairs followed
+//! by movetext: SAN moves with optional move numbers, comments, numeric annotation glyphs
+//! (NAGs), recursive variations and a closing result token.
+//!
+//! Games are read into a [`GameRecord`], whose moves are resolved against the position they are
+//! played from and can be replayed directly. Records are written back in the export format, so
+//! anything written reads back to the same record.
+pub mod reader;
+pub mod writer;
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::board::fen::FenError;
+use crate::board::pieces::Color;
+use crate::board::{Board, BoardError};
+use crate::chess_moves::ChessMove;
+use crate::move_parser::ParseError;
+use crate::rules::{GameOutcome, Termination};
+
+/// The seven tags every PGN game is expected to carry, in their required order.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// The result token that closes a game's movetext, also the value of its `Result` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// The game is still in progress, or its result is unknown.
+    Unfinished,
+}
+
+impl GameResult {
+    pub fn from_pgn(token: &str) -> Option<GameResult> {
+        match token {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unfinished),
+            _ => None,
+        }
+    }
+    pub fn to_pgn(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+}
+
+impl From<Option<GameOutcome>> for GameResult {
+    fn from(outcome: Option<GameOutcome>) -> GameResult {
+        match outcome.map(|outcome| outcome.winner) {
+            Some(Some(Color::White)) => GameResult::WhiteWins,
+            Some(Some(Color::Black)) => GameResult::BlackWins,
+            Some(None) => GameResult::Draw,
+            None => GameResult::Unfinished,
+        }
+    }
+}
+
+/// A move of a game record, with the annotations that follow it.
+#[derive(Debug, Clone)]
+pub struct MoveNode {
+    pub chess_move: ChessMove,
+    /// Numeric annotation glyphs, `$1` or `!` is 1. Suffix annotations are stored as their NAG.
+    pub nags: Vec<u8>,
+    /// Comments between this move and the next, without their braces.
+    pub comments: Vec<String>,
+    /// Alternatives to this move, each played from the position before it.
+    pub variations: Vec<Variation>,
+    /// Time left on the mover's clock after the move, stored as a `[%clk h:mm:ss]` comment.
+    pub clock: Option<Duration>,
+}
+
+impl MoveNode {
+    pub fn new(chess_move: ChessMove) -> MoveNode {
+        MoveNode {
+            chess_move,
+            nags: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+            clock: None,
+        }
+    }
+}
+
+/// A line of moves, the mainline of a game or one of its variations.
+#[derive(Debug, Clone, Default)]
+pub struct Variation {
+    /// Comments before the first move.
+    pub comments: Vec<String>,
+    pub moves: Vec<MoveNode>,
+}
+
+/// A game read from or written to PGN.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    /// Every tag in file order, including the seven tag roster.
+    pub tags: Vec<(String, String)>,
+    /// The position before the first move, set by the `FEN` tag or the standard start.
+    pub starting_position: Board,
+    pub mainline: Variation,
+    pub result: GameResult,
+}
+
+impl GameRecord {
+    pub fn new(starting_position: Board) -> GameRecord {
+        GameRecord {
+            tags: Vec::new(),
+            starting_position,
+            mainline: Variation::default(),
+            result: GameResult::Unfinished,
+        }
+    }
+
+    /// A record of `moves` played from `starting_position`, without tags or annotations.
+    pub fn from_moves(starting_position: Board, moves: &[ChessMove]) -> GameRecord {
+        let mut record = GameRecord::new(starting_position);
+        record.mainline.moves = moves.iter().map(|&chess_move| MoveNode::new(chess_move)).collect();
+        record
+    }
+
+    /// Returns the value of the tag `name`, if present.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the tag `name`, replacing its value if already present.
+    pub fn set_tag(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old_value)) => *old_value = value,
+            None => self.tags.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn mainline_moves(&self) -> Vec<ChessMove> {
+        self.mainline.moves.iter().map(|node| node.chess_move).collect()
+    }
+
+    /// Plays the mainline from the starting position.
+    pub fn final_position(&self) -> Board {
+        let mut board = self.starting_position.clone();
+        for node in &self.mainline.moves {
+            node.chess_move.make_move(&mut board);
+        }
+        board
+    }
+
+    /// The outcome recorded by the result and `Termination` tag, if the game is finished and
+    /// its termination is known.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        let termination = Termination::from_pgn_tag(self.tag("Termination")?)?;
+        match self.result {
+            GameResult::WhiteWins => Some(GameOutcome::win(Color::White, termination)),
+            GameResult::BlackWins => Some(GameOutcome::win(Color::Black, termination)),
+            GameResult::Draw => Some(GameOutcome::draw(termination)),
+            GameResult::Unfinished => None,
+        }
+    }
+}
+
+/// What went wrong while reading PGN.
+#[derive(Debug)]
+pub enum PgnErrorKind {
+    /// The input holds no game.
+    NoGame,
+    /// A `{` comment without its closing `}`.
+    UnterminatedComment,
+    /// A tag that isn't `[Name "value"]`.
+    MalformedTag,
+    /// A character that can't start any PGN token.
+    UnexpectedCharacter(char),
+    /// A token that is out of place, e.g. a tag inside movetext.
+    UnexpectedToken(String),
+    /// A NAG that isn't `$` followed by a number from 0 to 255.
+    InvalidNag(String),
+    /// A SAN move that doesn't name exactly one legal move.
+    IllegalMove { san: String, error: ParseError },
+    /// The `FEN` tag doesn't hold a valid position.
+    InvalidFen(FenError),
+    /// The `FEN` tag holds a position that can't occur in a game, see [`Board::validate`].
+    IllegalPosition(Vec<BoardError>),
+    /// A variation opened before any move it could be an alternative to.
+    VariationWithoutMove,
+    /// A `)` without a matching `(`.
+    UnmatchedVariationEnd,
+    /// The input ended inside a variation.
+    UnterminatedVariation,
+}
+
+/// A PGN error with the 1-based line and column it was found at.
+#[derive(Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnErrorKind::NoGame => write!(f, "No game found"),
+            PgnErrorKind::UnterminatedComment => write!(f, "Comment is missing its closing '}}'"),
+            PgnErrorKind::MalformedTag => write!(f, "Tag is not of the form [Name \"value\"]"),
+            PgnErrorKind::UnexpectedCharacter(character) => write!(f, "Unexpected character '{}'", character),
+            PgnErrorKind::UnexpectedToken(token) => write!(f, "Unexpected \"{}\"", token),
+            PgnErrorKind::InvalidNag(nag) => write!(f, "Invalid annotation glyph \"{}\"", nag),
+            PgnErrorKind::IllegalMove { san, error } => write!(f, "Illegal move \"{}\" ({:?})", san, error),
+            PgnErrorKind::InvalidFen(error) => write!(f, "Invalid FEN tag: {}", error),
+            PgnErrorKind::IllegalPosition(errors) => {
+                let reasons: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "Illegal position in FEN tag: {}", reasons.join(", "))
+            }
+            PgnErrorKind::VariationWithoutMove => write!(f, "Variation has no move to be an alternative to"),
+            PgnErrorKind::UnmatchedVariationEnd => write!(f, "')' without a matching '('"),
+            PgnErrorKind::UnterminatedVariation => write!(f, "Variation is missing its closing ')'"),
+        }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)
+    }
+}
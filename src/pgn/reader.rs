@@ -0,0 +1,573 @@
+//! PGN import.
+//!
+//! The input is split into tokens by a small lexer that tracks line and column, then each game's
+//! movetext is played out on a running board so every SAN move is resolved with
+//! `ChessMove::new_from_proto` against the position it is played from. Variations are read
+//! recursively from the position before the move they replace.
+//!
+//! Whitespace inside comments is collapsed to single spaces, since line breaks in a comment are
+//! only ever there to wrap it. A `[%clk h:mm:ss]` command in a comment is taken out of it and
+//! stored as the clock of the move it follows.
+//!
+//! A malformed game doesn't stop the rest of a file from being read: [`PgnReader`] reports the
+//! error and carries on from the next tag section.
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::chess_moves::ChessMove;
+use crate::move_parser::chess_notation_parser::from_simplified_algebraic_notation;
+use crate::pgn::{GameRecord, GameResult, MoveNode, PgnError, PgnErrorKind, Variation};
+
+/// Characters that end a SAN symbol besides whitespace.
+const DELIMITERS: &str = "{}()[];$\"";
+
+/// Suffix annotations and the NAG each one stands for.
+const SUFFIX_ANNOTATIONS: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    MoveNumber,
+    Symbol(String),
+    Result(GameResult),
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    peeked: Option<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: text.trim_start_matches('\u{feff}').chars().peekable(),
+            line: 1,
+            column: 1,
+            peeked: None,
+        }
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn advance_while(&mut self, mut condition: impl FnMut(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !condition(c) {
+                break;
+            }
+            taken.push(c);
+            self.advance();
+        }
+        taken
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+
+    fn peek(&mut self) -> Result<&Token, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_token()?);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    fn next(&mut self) -> Result<Token, PgnError> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.read_token(),
+        }
+    }
+
+    /// Drops the rest of a broken game, up to the next line that starts with a tag.
+    fn skip_to_next_game(&mut self) {
+        self.peeked = None;
+        while let Some(&c) = self.chars.peek() {
+            if c == '[' && self.column == 1 {
+                break;
+            }
+            self.advance();
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Token, PgnError> {
+        loop {
+            self.advance_while(char::is_whitespace);
+            // A % in the first column escapes the rest of the line
+            if self.column == 1 && self.chars.peek() == Some(&'%') {
+                self.advance_while(|c| c != '\n');
+                continue;
+            }
+            break;
+        }
+
+        let (line, column) = (self.line, self.column);
+        let kind = match self.chars.peek().copied() {
+            None => TokenKind::End,
+            Some('[') => self.read_tag()?,
+            Some('{') => {
+                self.advance();
+                let comment = self.advance_while(|c| c != '}');
+                if self.advance().is_none() {
+                    return Err(PgnError {
+                        line,
+                        column,
+                        kind: PgnErrorKind::UnterminatedComment,
+                    });
+                }
+                TokenKind::Comment(collapse_whitespace(&comment))
+            }
+            Some(';') => {
+                self.advance();
+                TokenKind::Comment(collapse_whitespace(&self.advance_while(|c| c != '\n')))
+            }
+            Some('$') => {
+                self.advance();
+                let digits = self.advance_while(|c| c.is_ascii_digit());
+                match digits.parse::<u8>() {
+                    Ok(nag) => TokenKind::Nag(nag),
+                    Err(_) => {
+                        return Err(PgnError {
+                            line,
+                            column,
+                            kind: PgnErrorKind::InvalidNag(format!("${}", digits)),
+                        })
+                    }
+                }
+            }
+            Some('(') => {
+                self.advance();
+                TokenKind::VariationStart
+            }
+            Some(')') => {
+                self.advance();
+                TokenKind::VariationEnd
+            }
+            Some(c) if DELIMITERS.contains(c) => return Err(self.error(PgnErrorKind::UnexpectedCharacter(c))),
+            Some(_) => self.read_symbol(),
+        };
+        Ok(Token { kind, line, column })
+    }
+
+    /// Reads `[Name "value"]`, where the value may escape `"` and `\` with a backslash.
+    fn read_tag(&mut self) -> Result<TokenKind, PgnError> {
+        let malformed = self.error(PgnErrorKind::MalformedTag);
+        self.advance();
+        self.advance_while(|c| c == ' ' || c == '\t');
+        let name = self.advance_while(|c| c.is_alphanumeric() || c == '_');
+        self.advance_while(|c| c == ' ' || c == '\t');
+        if name.is_empty() || self.advance() != Some('"') {
+            return Err(malformed);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => break,
+                Some('\\') => match self.advance() {
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err(malformed),
+                },
+                Some('\n') | None => return Err(malformed),
+                Some(c) => value.push(c),
+            }
+        }
+        self.advance_while(|c| c == ' ' || c == '\t');
+        if self.advance() != Some(']') {
+            return Err(malformed);
+        }
+        Ok(TokenKind::Tag(name, value))
+    }
+
+    /// Reads a move number, a result or a SAN move. Move numbers may run straight into the
+    /// move, as in `1.e4`.
+    fn read_symbol(&mut self) -> TokenKind {
+        let mut symbol = self.advance_while(|c| c.is_ascii_digit());
+        if !symbol.is_empty() && self.chars.peek() == Some(&'.') {
+            self.advance_while(|c| c == '.');
+            return TokenKind::MoveNumber;
+        }
+        symbol.push_str(&self.advance_while(|c| !c.is_whitespace() && !DELIMITERS.contains(c)));
+        match GameResult::from_pgn(&symbol) {
+            Some(result) => TokenKind::Result(result),
+            None => TokenKind::Symbol(symbol),
+        }
+    }
+}
+
+/// How a line of movetext ended.
+enum LineEnd {
+    Result(GameResult),
+    VariationEnd,
+    /// The end of the input, or the tags of the next game.
+    GameEnd,
+}
+
+/// Reads the games of a PGN file one at a time.
+pub struct PgnReader<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> PgnReader<'a> {
+    pub fn new(text: &'a str) -> PgnReader<'a> {
+        PgnReader { lexer: Lexer::new(text) }
+    }
+
+    fn read_game(&mut self) -> Result<Option<GameRecord>, PgnError> {
+        let mut tags = Vec::new();
+        let mut fen_tag = None;
+        loop {
+            let token = self.lexer.peek()?;
+            match &token.kind {
+                TokenKind::Tag(name, value) => {
+                    if name == "FEN" {
+                        fen_tag = Some((token.clone(), value.clone()));
+                    }
+                    tags.push((name.clone(), value.clone()));
+                }
+                // Comments between games belong to neither
+                TokenKind::Comment(_) if tags.is_empty() => {}
+                TokenKind::End if tags.is_empty() => return Ok(None),
+                _ => break,
+            }
+            self.lexer.next()?;
+        }
+
+        let starting_position = match fen_tag {
+            Some((token, fen)) => {
+                let board = Board::from_fen(&fen).map_err(|error| token.error(PgnErrorKind::InvalidFen(error)))?;
+                // Replaying moves, the book builder and the search all assume a reachable position
+                let errors = board.validate();
+                if !errors.is_empty() {
+                    return Err(token.error(PgnErrorKind::IllegalPosition(errors)));
+                }
+                board
+            }
+            None => Board::std_new(),
+        };
+        let mut record = GameRecord::new(starting_position.clone());
+        record.tags = tags;
+
+        let (mainline, line_end) = self.read_line(starting_position, None)?;
+        record.mainline = mainline;
+        record.result = match line_end {
+            LineEnd::Result(result) => result,
+            // No result token, fall back to the tag
+            _ => record.tag("Result").and_then(GameResult::from_pgn).unwrap_or(GameResult::Unfinished),
+        };
+        Ok(Some(record))
+    }
+
+    /// Reads moves from `board` until the line ends.
+    ///
+    /// # Arguments
+    /// * `board` - The position the line starts from.
+    /// * `opening` - The `(` token of a variation, `None` for the mainline.
+    fn read_line(&mut self, mut board: Board, opening: Option<&Token>) -> Result<(Variation, LineEnd), PgnError> {
+        let mut variation = Variation::default();
+        let mut before_last_move: Option<Board> = None;
+        loop {
+            if let TokenKind::Tag(..) = self.lexer.peek()?.kind {
+                return match opening {
+                    Some(opening) => Err(opening.error(PgnErrorKind::UnterminatedVariation)),
+                    None => Ok((variation, LineEnd::GameEnd)),
+                };
+            }
+            let token = self.lexer.next()?;
+            match token.kind {
+                TokenKind::MoveNumber => {}
+                TokenKind::Comment(comment) => match variation.moves.last_mut() {
+                    Some(node) => {
+                        let comment = match split_clock(&comment) {
+                            Some((clock, rest)) => {
+                                node.clock = Some(clock);
+                                rest
+                            }
+                            None => comment,
+                        };
+                        if !comment.is_empty() {
+                            node.comments.push(comment);
+                        }
+                    }
+                    None => variation.comments.push(comment),
+                },
+                TokenKind::Nag(nag) => match variation.moves.last_mut() {
+                    Some(node) => node.nags.push(nag),
+                    None => return Err(token.error(PgnErrorKind::UnexpectedToken(format!("${}", nag)))),
+                },
+                TokenKind::Symbol(ref symbol) => {
+                    let (san, nag) = split_suffix_annotation(symbol);
+                    let chess_move = resolve_san(&mut board, san)
+                        .map_err(|kind| token.error(kind))?;
+                    before_last_move = Some(board.clone());
+                    chess_move.make_move(&mut board);
+
+                    let mut node = MoveNode::new(chess_move);
+                    node.nags.extend(nag);
+                    variation.moves.push(node);
+                }
+                TokenKind::VariationStart => {
+                    let start = match &before_last_move {
+                        Some(start) => start.clone(),
+                        None => return Err(token.error(PgnErrorKind::VariationWithoutMove)),
+                    };
+                    let (alternative, line_end) = self.read_line(start, Some(&token))?;
+                    match line_end {
+                        LineEnd::VariationEnd => {
+                            if let Some(node) = variation.moves.last_mut() {
+                                node.variations.push(alternative);
+                            }
+                        }
+                        LineEnd::Result(_) | LineEnd::GameEnd => {
+                            return Err(token.error(PgnErrorKind::UnterminatedVariation))
+                        }
+                    }
+                }
+                TokenKind::VariationEnd => {
+                    return match opening {
+                        Some(_) => Ok((variation, LineEnd::VariationEnd)),
+                        None => Err(token.error(PgnErrorKind::UnmatchedVariationEnd)),
+                    };
+                }
+                TokenKind::Result(result) => return Ok((variation, LineEnd::Result(result))),
+                TokenKind::End => return Ok((variation, LineEnd::GameEnd)),
+                TokenKind::Tag(..) => unreachable!("tags end the line before they are read"),
+            }
+        }
+    }
+}
+
+impl Iterator for PgnReader<'_> {
+    type Item = Result<GameRecord, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_game() {
+            Ok(record) => record.map(Ok),
+            Err(error) => {
+                self.lexer.skip_to_next_game();
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Splits a trailing `!`, `?`, `!!`, `??`, `!?` or `?!` off a move, with the NAG it stands for.
+fn split_suffix_annotation(symbol: &str) -> (&str, Option<u8>) {
+    for (suffix, nag) in SUFFIX_ANNOTATIONS {
+        if let Some(san) = symbol.strip_suffix(suffix) {
+            return (san, Some(nag));
+        }
+    }
+    (symbol, None)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Takes a `[%clk h:mm:ss]` command, with optional fractions of a second, out of a comment.
+///
+/// # Returns
+/// The clock time and the rest of the comment, or `None` if the comment holds no valid command.
+fn split_clock(comment: &str) -> Option<(Duration, String)> {
+    let start = comment.find("[%clk ")?;
+    let end = start + comment[start..].find(']')?;
+    let mut fields = comment[start + "[%clk ".len()..end].trim().split(':');
+    let hours: u64 = fields.next()?.parse().ok()?;
+    let minutes: u64 = fields.next()?.parse().ok()?;
+    let seconds: f64 = fields.next()?.parse().ok()?;
+    if fields.next().is_some() || minutes >= 60 || !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    // Times too long for a Duration can't be stored exactly, keep them as plain comments
+    let whole_seconds = hours.checked_mul(3600)?.checked_add(minutes * 60)?;
+    let clock = Duration::from_secs(whole_seconds).checked_add(Duration::from_secs_f64(seconds))?;
+    let rest = format!("{} {}", &comment[..start], &comment[end + 1..]);
+    Some((clock, collapse_whitespace(&rest)))
+}
+
+fn resolve_san(board: &mut Board, san: &str) -> Result<ChessMove, PgnErrorKind> {
+    let illegal_move = |error| PgnErrorKind::IllegalMove {
+        san: san.to_string(),
+        error,
+    };
+    let proto_move = from_simplified_algebraic_notation(san, board.active_player).map_err(illegal_move)?;
+    ChessMove::new_from_proto(board, proto_move).map_err(illegal_move)
+}
+
+/// Reads every game of a PGN file. A game with an error doesn't stop the games after it from
+/// being read.
+pub fn read_games(text: &str) -> Vec<Result<GameRecord, PgnError>> {
+    PgnReader::new(text).collect()
+}
+
+impl GameRecord {
+    /// Reads the first game of `text`.
+    #[cfg(test)]
+    pub fn from_pgn(text: &str) -> Result<GameRecord, PgnError> {
+        PgnReader::new(text).next().unwrap_or(Err(PgnError {
+            line: 1,
+            column: 1,
+            kind: PgnErrorKind::NoGame,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{GameOutcome, Termination};
+    use crate::board::pieces::Color;
+    use crate::board::square::{Square, SquareExt};
+    use crate::board::BoardError;
+
+    const GAME: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "Someone \"quoted\""]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+"#;
+
+    #[test]
+    fn reads_tags_and_mainline() {
+        let record = GameRecord::from_pgn(GAME).unwrap();
+        assert_eq!(record.tags.len(), 8);
+        assert_eq!(record.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(record.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(record.result, GameResult::Draw);
+        assert_eq!(record.mainline.moves.len(), 85);
+        assert_eq!(record.mainline.moves[4].comments, vec!["This opening is called the Ruy Lopez."]);
+        assert_eq!(record.final_position().to_fen(), "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+    }
+
+    #[test]
+    fn reads_variations_nags_and_comments() {
+        let pgn = "[Event \"?\"]\n\n% escaped line\n{Before} 1.e4 $1 e5!? (1...c5 2.Nf3 (2.c3) d6; line comment\n) 2.Nf3 *";
+        let record = GameRecord::from_pgn(pgn).unwrap();
+        assert_eq!(record.mainline.comments, vec!["Before"]);
+        let moves = &record.mainline.moves;
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[0].nags, vec![1]);
+        assert_eq!(moves[1].nags, vec![5]);
+
+        let sicilian = &moves[1].variations[0];
+        assert_eq!(sicilian.moves.len(), 3);
+        assert_eq!(sicilian.moves[0].chess_move.to_coordinate_notation(), "c7c5");
+        assert_eq!(sicilian.moves[1].variations[0].moves[0].chess_move.to_coordinate_notation(), "c2c3");
+        assert_eq!(sicilian.moves[2].comments, vec!["line comment"]);
+        assert_eq!(record.result, GameResult::Unfinished);
+    }
+
+    #[test]
+    fn reads_clocks_that_fit_a_duration() {
+        let record = GameRecord::from_pgn("1. e4 {[%clk 1:02:03.5] Fast} e5 {[%clk 0:61:00]} *").unwrap();
+        let moves = &record.mainline.moves;
+        assert_eq!(moves[0].clock, Some(Duration::from_secs_f64(3723.5)));
+        assert_eq!(moves[0].comments, vec!["Fast"]);
+        assert_eq!(moves[1].clock, None);
+
+        for clock in ["5124095576030431:00:59", "99999999999999999:00:00"] {
+            let pgn = format!("1. e4 {{[%clk {}]}} *", clock);
+            let record = GameRecord::from_pgn(&pgn).unwrap();
+            assert_eq!(record.mainline.moves[0].clock, None);
+            assert_eq!(record.mainline.moves[0].comments, vec![format!("[%clk {}]", clock)]);
+        }
+    }
+
+    #[test]
+    fn reads_several_games_and_setups() {
+        let pgn = "[Result \"1-0\"]\n[Termination \"time forfeit\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n\
+            [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K2R w K - 0 1\"]\n\n1. O-O Kd7 *\n";
+        let games = read_games(pgn);
+        assert_eq!(games.len(), 2);
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.result, GameResult::BlackWins);
+        assert_eq!(first.outcome(), Some(GameOutcome::win(Color::Black, Termination::Timeout)));
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(second.mainline.moves.len(), 2);
+        assert_eq!(second.final_position().to_fen(), "8/3k4/8/8/8/8/8/5RK1 w - - 2 2");
+    }
+
+    #[test]
+    fn reports_error_positions_and_recovers() {
+        let pgn = "[Event \"Broken\"]\n\n1. e4 e5\n2. Ke3 Nc6 *\n\n[Event \"Fine\"]\n\n1. d4 *\n";
+        let games = read_games(pgn);
+        assert_eq!(games.len(), 2);
+        let error = games[0].as_ref().unwrap_err();
+        assert_eq!((error.line, error.column), (4, 4));
+        assert!(matches!(error.kind, PgnErrorKind::IllegalMove { ref san, .. } if san == "Ke3"));
+        assert_eq!(error.to_string().split(':').next(), Some("line 4, column 4"));
+        assert_eq!(games[1].as_ref().unwrap().tag("Event"), Some("Fine"));
+
+        let error = GameRecord::from_pgn("1. e4 {unterminated\ncomment").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::UnterminatedComment));
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = GameRecord::from_pgn("1. e4 (1. d4 *").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::UnterminatedVariation));
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = GameRecord::from_pgn("[Event \"x]\n1. e4 *").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::MalformedTag));
+        assert!(matches!(GameRecord::from_pgn("  ").unwrap_err().kind, PgnErrorKind::NoGame));
+
+        let error = GameRecord::from_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::IllegalPosition(ref errors)
+            if errors.contains(&BoardError::MissingKing(Color::White))));
+        assert_eq!((error.line, error.column), (1, 1));
+        let error = GameRecord::from_pgn("[FEN \"P3k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n*").unwrap_err();
+        assert!(matches!(error.kind, PgnErrorKind::IllegalPosition(ref errors)
+            if errors == &[BoardError::PawnOnBackRank(Square::A8)]));
+    }
+}
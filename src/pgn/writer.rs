@@ -0,0 +1,274 @@
+//! PGN export.
+//!
+//! Games are written in the PGN export format: the seven tag roster first and in order, then
+//! `SetUp` and `FEN` for games that don't start from the standard position, then every other
+//! tag. Movetext follows after a blank line, wrapped at [`LINE_LENGTH`] columns, with a move
+//! number before every white move and before a black move that follows a comment or variation.
+//!
+//! Moves are written in SAN computed from the position they are played in, clock times as
+//! `[%clk h:mm:ss]` comments, and the `Result` tag always agrees with the closing result token.
+use std::time::Duration;
+
+use crate::board::pieces::Color;
+use crate::board::Board;
+use crate::pgn::{GameRecord, Variation, SEVEN_TAG_ROSTER};
+
+/// Maximum length of a movetext line, as the export format requires.
+pub const LINE_LENGTH: usize = 80;
+
+/// Collects movetext tokens, to be wrapped into lines of at most [`LINE_LENGTH`] characters.
+///
+/// Lines only break between tokens, so a move number stays with its move and parentheses stay
+/// with the moves they enclose.
+#[derive(Default)]
+struct Movetext {
+    tokens: Vec<String>,
+    /// Text to put in front of the next token, the `(` opening a variation.
+    prefix: String,
+}
+
+impl Movetext {
+    fn push(&mut self, token: &str) {
+        self.tokens.push(std::mem::take(&mut self.prefix) + token);
+    }
+
+    fn open_variation(&mut self) {
+        self.prefix.push('(');
+    }
+
+    fn close_variation(&mut self) {
+        match self.tokens.last_mut() {
+            Some(token) if self.prefix.is_empty() => token.push(')'),
+            _ => self.push(")"),
+        }
+    }
+
+    fn wrap(&self) -> String {
+        let mut text = String::new();
+        let mut line_length = 0;
+        for token in &self.tokens {
+            if line_length > 0 {
+                // A line starting with % would be read back as an escaped line, keep it on this one
+                if line_length + 1 + token.len() > LINE_LENGTH && !token.starts_with('%') {
+                    text.push('\n');
+                    line_length = 0;
+                } else {
+                    text.push(' ');
+                    line_length += 1;
+                }
+            }
+            text.push_str(token);
+            line_length += token.len();
+        }
+        text
+    }
+
+    /// Pushes a brace comment a word at a time so long comments wrap with the movetext.
+    fn push_comment(&mut self, comment: &str) {
+        // A comment can't hold its own closing brace
+        let comment = comment.replace('}', "");
+        let words: Vec<&str> = comment.split_whitespace().collect();
+        match words.as_slice() {
+            [] => self.push("{}"),
+            [word] => self.push(&format!("{{{}}}", word)),
+            [first, middle @ .., last] => {
+                self.push(&format!("{{{}", first));
+                for word in middle {
+                    self.push(word);
+                }
+                self.push(&format!("{}}}", last));
+            }
+        }
+    }
+
+    /// Writes `variation` played from `board`.
+    fn push_variation(&mut self, variation: &Variation, mut board: Board) {
+        for comment in &variation.comments {
+            self.push_comment(comment);
+        }
+
+        let mut needs_move_number = true;
+        for node in &variation.moves {
+            let move_number = board.full_move_number;
+            let san = node.chess_move.to_san(&board);
+            if board.active_player == Color::White {
+                self.push(&format!("{}. {}", move_number, san));
+            } else if needs_move_number {
+                self.push(&format!("{}... {}", move_number, san));
+            } else {
+                self.push(&san);
+            }
+            for nag in &node.nags {
+                self.push(&format!("${}", nag));
+            }
+
+            needs_move_number = false;
+            if let Some(clock) = node.clock {
+                self.push(&format!("{{[%clk {}]}}", format_clock(clock)));
+                needs_move_number = true;
+            }
+            for comment in &node.comments {
+                self.push_comment(comment);
+                needs_move_number = true;
+            }
+            for alternative in &node.variations {
+                self.open_variation();
+                self.push_variation(alternative, board.clone());
+                self.close_variation();
+                needs_move_number = true;
+            }
+
+            node.chess_move.make_move(&mut board);
+        }
+    }
+}
+
+/// Formats a clock time as `h:mm:ss`, dropping fractions of a second.
+fn format_clock(clock: Duration) -> String {
+    let seconds = clock.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl GameRecord {
+    /// Writes the record as a single PGN game, ending in a newline.
+    pub fn to_pgn(&self) -> String {
+        let mut tags: Vec<(&str, String)> = Vec::with_capacity(self.tags.len() + SEVEN_TAG_ROSTER.len() + 2);
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.to_pgn().to_string(),
+                "Date" => self.tag(name).unwrap_or("????.??.??").to_string(),
+                _ => self.tag(name).unwrap_or("?").to_string(),
+            };
+            tags.push((name, value));
+        }
+        let fen = self.starting_position.to_fen();
+        if fen != Board::std_new().to_fen() {
+            tags.push(("SetUp", String::from("1")));
+            tags.push(("FEN", fen));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                tags.push((name, value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(&value)));
+        }
+        pgn.push('\n');
+
+        let mut movetext = Movetext::default();
+        movetext.push_variation(&self.mainline, self.starting_position.clone());
+        movetext.push(self.result.to_pgn());
+        pgn.push_str(&movetext.wrap());
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Writes several games, separated by blank lines.
+pub fn write_games(records: &[GameRecord]) -> String {
+    records.iter().map(GameRecord::to_pgn).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::reader::read_games;
+    use crate::pgn::{GameResult, MoveNode};
+
+    /// Compares everything a record holds, moves by their coordinates.
+    fn assert_same_variation(left: &Variation, right: &Variation) {
+        assert_eq!(left.comments, right.comments);
+        assert_eq!(left.moves.len(), right.moves.len());
+        for (left, right) in left.moves.iter().zip(&right.moves) {
+            assert_eq!(left.chess_move.to_coordinate_notation(), right.chess_move.to_coordinate_notation());
+            assert_eq!(left.nags, right.nags);
+            assert_eq!(left.comments, right.comments);
+            assert_eq!(left.clock, right.clock);
+            assert_eq!(left.variations.len(), right.variations.len());
+            for (left, right) in left.variations.iter().zip(&right.variations) {
+                assert_same_variation(left, right);
+            }
+        }
+    }
+
+    fn assert_round_trips(record: &GameRecord) -> String {
+        let pgn = record.to_pgn();
+        let games = read_games(&pgn);
+        assert_eq!(games.len(), 1, "{}", pgn);
+        let read = games[0].as_ref().unwrap_or_else(|error| panic!("{}\n{}", error, pgn));
+        assert_eq!(read.starting_position.to_fen(), record.starting_position.to_fen());
+        assert_eq!(read.result, record.result);
+        assert_same_variation(&read.mainline, &record.mainline);
+        assert_eq!(read.to_pgn(), pgn);
+        pgn
+    }
+
+    #[test]
+    fn writes_export_format() {
+        let mut record = GameRecord::from_pgn(
+            "[White \"Some \\\"One\\\"\"]\n[Annotator \"x\"]\n[Event \"Test\"]\n\n\
+             {Start} 1. e4 $1 {[%clk 0:05:00] Best by test} e5 (1... c5 2. Nf3) 2. Nf3 {[%clk 1:02:03]} 0-1",
+        )
+        .unwrap();
+        record.result = GameResult::Draw;
+        let pgn = assert_round_trips(&record);
+        assert_eq!(
+            pgn,
+            "[Event \"Test\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"Some \\\"One\\\"\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n[Annotator \"x\"]\n\n\
+             {Start} 1. e4 $1 {[%clk 0:05:00]} {Best by test} 1... e5 (1... c5 2. Nf3) 2. Nf3\n\
+             {[%clk 1:02:03]} 1/2-1/2\n"
+        );
+    }
+
+    #[test]
+    fn round_trips_set_up_positions() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20").unwrap();
+        let start = board.clone();
+        let mut moves = Vec::new();
+        for notation in ["e8c8", "e1g1", "h8h1", "g1h1"] {
+            let chess_move = crate::chess_moves::ChessMove::get_valid_moves(&mut board)
+                .into_iter()
+                .find(|chess_move| chess_move.to_coordinate_notation() == notation)
+                .unwrap();
+            chess_move.make_move(&mut board);
+            moves.push(chess_move);
+        }
+        let mut record = GameRecord::from_moves(start, &moves);
+        record.mainline.moves[0].clock = Some(Duration::from_secs(59));
+        let pgn = assert_round_trips(&record);
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20\"]\n"));
+        assert!(pgn.contains("20... O-O-O {[%clk 0:00:59]} 21. O-O Rh1+ 22. Kxh1 *"), "{}", pgn);
+    }
+
+    #[test]
+    fn wraps_long_movetext() {
+        let mut record = GameRecord::from_pgn(
+            "1. d4 Nf6 2. c4 e6 3. Nc3 Bb4 4. e3 O-O 5. Bd3 d5 6. Nf3 c5 7. O-O Nc6 8. a3 Bxc3 \
+             9. bxc3 dxc4 10. Bxc4 Qc7 11. Bd3 e5 12. Qc2 Re8 13. Nxe5 Nxe5 14. dxe5 Qxe5 *",
+        )
+        .unwrap();
+        let long_comment = "word ".repeat(40);
+        record.mainline.moves[5].comments.push(long_comment.trim().to_string());
+        // The same move again as its own variation, behind a comment that must not start a line
+        let mut variation = Variation::default();
+        variation.comments.push(String::from("%not an escape"));
+        variation.moves.push(MoveNode::new(record.mainline.moves[7].chess_move));
+        record.mainline.moves[7].variations.push(variation);
+        let pgn = assert_round_trips(&record);
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 3);
+        for line in movetext.lines() {
+            assert!(line.len() <= LINE_LENGTH, "{}", line);
+            assert!(!line.starts_with('%'));
+        }
+        assert_eq!(write_games(&[record.clone(), record]).matches("[Event").count(), 2);
+    }
+}
@@ -1,5 +1,8 @@
+pub mod draw;
+
 use std::time::{Duration, Instant};
 use crate::board::Color;
+use crate::rules::draw::DrawReason;
 
 /// # System Types
 ///
@@ -24,10 +27,8 @@ pub enum GameState {
     Start,
     /// The game is in progress
     Running,
-    /// A player has been checkmated.
-    Checkmate,
-    /// The game has ended in a draw
-    Draw,
+    /// The game has ended with the given outcome
+    Finished(GameOutcome),
 }
 
 impl PartialEq for GameState {
@@ -35,13 +36,123 @@ impl PartialEq for GameState {
         match (self, other) {
             (GameState::Start, GameState::Start) => true,
             (GameState::Running, GameState::Running) => true,
-            (GameState::Checkmate, GameState::Checkmate) => true,
-            (GameState::Draw, GameState::Draw) => true,
+            (GameState::Finished(outcome), GameState::Finished(other_outcome)) => outcome == other_outcome,
             _ => false,
         }
     }
 }
 
+impl GameState {
+    /// The PGN `Result` tag value, `*` while the game is unfinished.
+    pub fn to_pgn_result(&self) -> &'static str {
+        match self {
+            GameState::Finished(outcome) => outcome.to_pgn_result(),
+            GameState::Start | GameState::Running => "*",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why a game ended
+pub enum Termination {
+    /// The loser was checkmated
+    Checkmate,
+    /// The loser resigned
+    Resignation,
+    /// A player ran out of time. Drawn if the opponent can't possibly checkmate
+    Timeout,
+    /// The player to move has no legal moves and isn't in check
+    Stalemate,
+    /// Both players agreed to a draw
+    Agreement,
+    /// Threefold repetition claimed, or fivefold repetition
+    Repetition,
+    /// Fifty-move rule claimed, or seventy-five-move rule
+    FiftyMoveRule,
+    /// Neither side can checkmate
+    InsufficientMaterial,
+    /// A player left the game
+    Abandonment,
+    /// The result was decided by an arbiter or engine adjudication
+    Adjudication,
+}
+
+impl Termination {
+    const ALL: [Termination; 10] = [
+        Termination::Checkmate,
+        Termination::Resignation,
+        Termination::Timeout,
+        Termination::Stalemate,
+        Termination::Agreement,
+        Termination::Repetition,
+        Termination::FiftyMoveRule,
+        Termination::InsufficientMaterial,
+        Termination::Abandonment,
+        Termination::Adjudication,
+    ];
+
+    /// The PGN `Termination` tag value. `time forfeit`, `abandoned` and `adjudication` are the
+    /// standard's own values, the rest spell out what the standard lumps together as `normal`.
+    pub fn to_pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Resignation => "resignation",
+            Termination::Timeout => "time forfeit",
+            Termination::Stalemate => "stalemate",
+            Termination::Agreement => "agreement",
+            Termination::Repetition => "repetition",
+            Termination::FiftyMoveRule => "fifty-move rule",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Abandonment => "abandoned",
+            Termination::Adjudication => "adjudication",
+        }
+    }
+    /// Parses a PGN `Termination` tag value written by `to_pgn_tag`, ignoring case.
+    pub fn from_pgn_tag(tag: &str) -> Option<Termination> {
+        Termination::ALL
+            .into_iter()
+            .find(|termination| termination.to_pgn_tag().eq_ignore_ascii_case(tag.trim()))
+    }
+}
+
+impl From<DrawReason> for Termination {
+    fn from(draw_reason: DrawReason) -> Termination {
+        match draw_reason {
+            DrawReason::FiftyMoveRule | DrawReason::SeventyFiveMoveRule => Termination::FiftyMoveRule,
+            DrawReason::ThreefoldRepetition | DrawReason::FivefoldRepetition => Termination::Repetition,
+            DrawReason::InsufficientMaterial => Termination::InsufficientMaterial,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The result of a finished game
+pub struct GameOutcome {
+    /// The winning player, `None` for a draw
+    pub winner: Option<Color>,
+    pub termination: Termination,
+}
+
+impl GameOutcome {
+    pub fn win(winner: Color, termination: Termination) -> GameOutcome {
+        GameOutcome { winner: Some(winner), termination }
+    }
+    pub fn draw(termination: Termination) -> GameOutcome {
+        GameOutcome { winner: None, termination }
+    }
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+    /// The PGN `Result` tag value, `1-0`, `0-1` or `1/2-1/2`.
+    pub fn to_pgn_result(&self) -> &'static str {
+        match self.winner {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
 #[derive(Debug)]
 /// The outcome of a [crate::chess_moves:ChessMove] move
 pub enum MoveResult {
@@ -99,6 +210,7 @@ impl CastleType {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct TimeControls {
     pub initial_time_white: Duration,
     pub initial_time_black: Duration,
@@ -106,6 +218,40 @@ pub struct TimeControls {
     pub time_per_move_black: Duration,
 }
 
+impl TimeControls {
+    /// Formats the controls as a PGN `TimeControl` tag, seconds per game plus the increment per
+    /// move, e.g. `300+2`. PGN has no form for uneven controls, so white's are used.
+    pub fn to_pgn_tag(self) -> String {
+        let initial = self.initial_time_white.as_secs();
+        let increment = self.time_per_move_white.as_secs();
+        if increment == 0 {
+            initial.to_string()
+        } else {
+            format!("{}+{}", initial, increment)
+        }
+    }
+}
+
+impl std::str::FromStr for TimeControls {
+    type Err = String;
+
+    /// Reads even controls written like a PGN `TimeControl` tag, `300` or `300+2` seconds.
+    fn from_str(text: &str) -> Result<TimeControls, String> {
+        let text = text.trim();
+        let (initial, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let seconds = |part: &str| part.trim().parse().map(Duration::from_secs);
+        match (seconds(initial), seconds(increment)) {
+            (Ok(initial), Ok(increment)) if !initial.is_zero() => Ok(TimeControls {
+                initial_time_white: initial,
+                initial_time_black: initial,
+                time_per_move_white: increment,
+                time_per_move_black: increment,
+            }),
+            _ => Err(format!("Invalid time control '{}', expected seconds like 300 or 300+2", text)),
+        }
+    }
+}
+
 /// A trait representing the basic functionalities of a chess clock.
 /// A chess clock is used to manage and track the time for two players in a game.
 ///
@@ -146,3 +292,35 @@ pub trait Timer {
     /// Sets the remaining time for a specific player.
     fn set_player_time_left(&mut self, color: Color, time: Duration);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgn_result_and_termination_tags() {
+        assert_eq!(GameState::Running.to_pgn_result(), "*");
+        assert_eq!(GameOutcome::win(Color::White, Termination::Timeout).to_pgn_result(), "1-0");
+        assert_eq!(GameOutcome::draw(Termination::Agreement).to_pgn_result(), "1/2-1/2");
+        for termination in Termination::ALL {
+            assert_eq!(Termination::from_pgn_tag(termination.to_pgn_tag()), Some(termination));
+        }
+        // Flag losses and resignations stay distinguishable
+        assert_ne!(Termination::Timeout.to_pgn_tag(), Termination::Resignation.to_pgn_tag());
+        assert_eq!(Termination::from_pgn_tag("Time forfeit"), Some(Termination::Timeout));
+        assert_eq!(Termination::from_pgn_tag("normal"), None);
+    }
+
+    #[test]
+    fn time_controls_read_their_pgn_tag() {
+        for tag in ["300", "300+2", "5400+30"] {
+            assert_eq!(tag.parse::<TimeControls>().unwrap().to_pgn_tag(), tag);
+        }
+        let time_controls: TimeControls = " 180+2 ".parse().unwrap();
+        assert_eq!(time_controls.initial_time_black, Duration::from_secs(180));
+        assert_eq!(time_controls.time_per_move_black, Duration::from_secs(2));
+        for invalid in ["", "0", "5 minutes", "300+", "-60"] {
+            assert!(invalid.parse::<TimeControls>().is_err(), "{}", invalid);
+        }
+    }
+}
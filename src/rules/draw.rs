@@ -0,0 +1,298 @@
+//! Draw rules.
+//!
+//! Follows the FIDE Laws of Chess:
+//! - Fifty moves by each player without a capture or pawn move may be claimed as a draw, after
+//!   seventy-five the game is drawn automatically.
+//! - The third occurrence of a position may be claimed as a draw, the fifth draws automatically.
+//! - A position where neither side can checkmate by any series of legal moves is drawn
+//!   immediately. Only the material combinations that can never mate are detected: king against
+//!   king, a single minor piece against a bare king, and bishops that all stand on one colour.
+//!
+//! Positions repeat when the same side is to move with the same pieces, castling rights and en
+//! passant captures available, which is exactly what the board's Zobrist key covers.
+//!
+//! A checkmate delivered by the move reaching a seventy-five move or fivefold repetition draw
+//! still wins, so callers should check for mate first.
+use crate::board::pieces::Color;
+use crate::board::zobrist::ZobristKey;
+use crate::board::{Bitboard, Board};
+use crate::chess_moves::ChessMove;
+
+/// Half moves without a capture or pawn move before a draw can be claimed.
+pub const FIFTY_MOVE_RULE_PLIES: u8 = 100;
+/// Half moves without a capture or pawn move before the game is drawn automatically.
+pub const SEVENTY_FIVE_MOVE_RULE_PLIES: u8 = 150;
+
+/// Squares of the same colour as a8.
+const LIGHT_SQUARES: Bitboard = 0xAA55_AA55_AA55_AA55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    /// Claimable, fifty moves by each side without a capture or pawn move.
+    FiftyMoveRule,
+    /// Automatic, seventy-five moves by each side without a capture or pawn move.
+    SeventyFiveMoveRule,
+    /// Claimable, the position has occurred three times.
+    ThreefoldRepetition,
+    /// Automatic, the position has occurred five times.
+    FivefoldRepetition,
+    /// Automatic, neither side has the material to checkmate.
+    InsufficientMaterial,
+}
+
+impl DrawReason {
+    /// Returns true if the draw ends the game without either player claiming it.
+    pub fn is_automatic(&self) -> bool {
+        match self {
+            DrawReason::SeventyFiveMoveRule
+            | DrawReason::FivefoldRepetition
+            | DrawReason::InsufficientMaterial => true,
+            DrawReason::FiftyMoveRule | DrawReason::ThreefoldRepetition => false,
+        }
+    }
+}
+
+/// The draws that apply to a position.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DrawStatus {
+    /// The draw that ends the game, if any.
+    pub automatic: Option<DrawReason>,
+    /// The draws the player to move may claim.
+    pub claimable: Vec<DrawReason>,
+}
+
+impl DrawStatus {
+    pub fn is_draw(&self) -> bool {
+        self.automatic.is_some()
+    }
+    pub fn can_claim(&self) -> bool {
+        !self.claimable.is_empty()
+    }
+}
+
+/// Zobrist keys of every position of a game, oldest first, including the current one.
+#[derive(Debug, Clone, Default)]
+pub struct PositionHistory {
+    keys: Vec<ZobristKey>,
+}
+
+impl PositionHistory {
+    /// Starts a history at `board`.
+    pub fn new(board: &Board) -> PositionHistory {
+        PositionHistory {
+            keys: vec![board.zobrist_key()],
+        }
+    }
+
+    /// Builds the history of a game by playing `moves` from `start`.
+    ///
+    /// # Returns
+    ///
+    /// The history, and the board after the last move.
+    pub fn replay(start: &Board, moves: &[ChessMove]) -> (PositionHistory, Board) {
+        let mut board = start.clone();
+        let mut history = PositionHistory::new(&board);
+        for chess_move in moves {
+            chess_move.make_move(&mut board);
+            history.push(&board);
+        }
+        (history, board)
+    }
+
+    /// Records the position reached by the latest move.
+    pub fn push(&mut self, board: &Board) {
+        self.keys.push(board.zobrist_key());
+    }
+
+    /// Forgets the latest position, when a move is taken back.
+    pub fn pop(&mut self) -> Option<ZobristKey> {
+        self.keys.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Counts the occurrences of `board`'s position, including the current one.
+    ///
+    /// Positions before the last capture or pawn move can't recur, so only the last
+    /// `half_move_clock` plies are searched.
+    pub fn repetitions(&self, board: &Board) -> usize {
+        let key = board.zobrist_key();
+        let reversible_plies = board.half_move_clock as usize + 1;
+        let occurrences = self
+            .keys
+            .iter()
+            .rev()
+            .take(reversible_plies)
+            .filter(|&&previous| previous == key)
+            .count();
+        // The current position counts even when it hasn't been pushed yet
+        if self.keys.last() == Some(&key) {
+            occurrences
+        } else {
+            occurrences + 1
+        }
+    }
+}
+
+/// Reports which draws apply to `board`.
+///
+/// # Arguments
+///
+/// * `board` - The current position.
+/// * `history` - Every position of the game up to `board`.
+pub fn draw_status(board: &Board, history: &PositionHistory) -> DrawStatus {
+    let mut status = DrawStatus::default();
+
+    if has_insufficient_material(board) {
+        status.automatic = Some(DrawReason::InsufficientMaterial);
+    }
+
+    let repetitions = history.repetitions(board);
+    if repetitions >= 5 {
+        status.automatic.get_or_insert(DrawReason::FivefoldRepetition);
+    } else if repetitions >= 3 {
+        status.claimable.push(DrawReason::ThreefoldRepetition);
+    }
+
+    if board.half_move_clock >= SEVENTY_FIVE_MOVE_RULE_PLIES {
+        status.automatic.get_or_insert(DrawReason::SeventyFiveMoveRule);
+    } else if board.half_move_clock >= FIFTY_MOVE_RULE_PLIES {
+        status.claimable.push(DrawReason::FiftyMoveRule);
+    }
+    status
+}
+
+/// Returns true if neither side has the material to ever deliver checkmate.
+pub fn has_insufficient_material(board: &Board) -> bool {
+    let mut knights = 0;
+    let mut bishops = 0;
+    for color in [Color::White, Color::Black] {
+        let mating_material = board.get_bitboard(color.get_pawn())
+            | board.get_bitboard(color.get_rook())
+            | board.get_bitboard(color.get_queen());
+        if mating_material != 0 {
+            return false;
+        }
+        knights |= board.get_bitboard(color.get_knight());
+        bishops |= board.get_bitboard(color.get_bishop());
+    }
+
+    let minor_pieces = (knights | bishops).count_ones();
+    if minor_pieces <= 1 {
+        return true;
+    }
+    // Any number of bishops on one colour can't cover the squares around a king
+    knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+}
+
+/// Returns true if `color` has nothing but its king left.
+///
+/// A player who runs out of time loses unless the opponent can't possibly checkmate, which a
+/// bare king never can.
+pub fn has_bare_king(board: &Board, color: Color) -> bool {
+    board.get_color_bitboard(color) == board.get_bitboard(color.get_king())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_moves::MoveList;
+
+    fn status(fen: &str) -> DrawStatus {
+        let board = Board::from_fen(fen).unwrap();
+        draw_status(&board, &PositionHistory::new(&board))
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KB2 b - - 0 1",
+            // Light squared bishops only
+            "4k3/8/8/8/2B5/8/8/4KB2 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        ] {
+            assert_eq!(status(fen).automatic, Some(DrawReason::InsufficientMaterial), "{}", fen);
+        }
+        for fen in [
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KNN1 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1",
+        ] {
+            assert_eq!(status(fen).automatic, None, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn applies_move_counting_rules() {
+        let status_49 = status("4k3/8/8/8/8/8/8/R3K3 w - - 99 80");
+        assert!(!status_49.can_claim() && !status_49.is_draw());
+
+        let status_50 = status("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert_eq!(status_50.claimable, vec![DrawReason::FiftyMoveRule]);
+        assert!(!status_50.is_draw());
+
+        let status_75 = status("4k3/8/8/8/8/8/8/R3K3 w - - 150 105");
+        assert_eq!(status_75.automatic, Some(DrawReason::SeventyFiveMoveRule));
+    }
+
+    #[test]
+    fn counts_repetitions() {
+        let mut board = Board::from_fen("4k1n1/8/8/8/8/8/8/4K1N1 w - - 0 1").unwrap();
+        let mut history = PositionHistory::new(&board);
+        // Shuffle both knights out and back
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let play = |board: &mut Board, history: &mut PositionHistory| {
+            for notation in shuffle {
+                let mut moves = MoveList::new();
+                ChessMove::generate_legal_moves(board, &mut moves);
+                let chess_move = *moves.iter().find(|chess_move| chess_move.to_coordinate_notation() == notation).unwrap();
+                chess_move.make_move(board);
+                history.push(board);
+            }
+        };
+
+        play(&mut board, &mut history);
+        assert_eq!(board.half_move_clock, 4);
+        assert_eq!(history.repetitions(&board), 2);
+        assert!(!draw_status(&board, &history).can_claim());
+
+        play(&mut board, &mut history);
+        assert_eq!(draw_status(&board, &history).claimable, vec![DrawReason::ThreefoldRepetition]);
+
+        play(&mut board, &mut history);
+        play(&mut board, &mut history);
+        assert_eq!(history.repetitions(&board), 5);
+        assert_eq!(draw_status(&board, &history).automatic, Some(DrawReason::FivefoldRepetition));
+    }
+
+    #[test]
+    fn replays_move_history() {
+        let start = Board::std_new();
+        let mut moves = Vec::new();
+        let mut board = start.clone();
+        for _ in 0..2 {
+            for notation in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let chess_move = ChessMove::get_valid_moves(&mut board)
+                    .into_iter()
+                    .find(|chess_move| chess_move.to_coordinate_notation() == notation)
+                    .unwrap();
+                chess_move.make_move(&mut board);
+                moves.push(chess_move);
+            }
+        }
+        let (history, replayed) = PositionHistory::replay(&start, &moves);
+        assert_eq!(history.len(), 9);
+        assert_eq!(replayed.to_fen(), board.to_fen());
+        assert_eq!(draw_status(&replayed, &history).claimable, vec![DrawReason::ThreefoldRepetition]);
+    }
+}
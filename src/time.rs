@@ -46,6 +46,7 @@ pub mod time_format {
         (year % 4 == 0) && ((year % 100 != 0) || (year % 400 == 0))
     }
 
+    #[derive(Clone)]
     pub struct DateMillis{
         start: Instant,
         display_mode: DisplayMode,
@@ -63,6 +64,8 @@ pub mod time_format {
         ISO8601,
         Simple,
         Debug,
+        /// The date alone, as the PGN `Date` tag writes it: `YYYY.MM.DD`
+        Pgn,
     }
 
     impl DateMillis{
@@ -139,16 +142,18 @@ pub mod time_format {
 
         pub fn new() -> Result<DateMillis, SystemTimeError> {        
             //println!("DateMillis::new()");
-            let now = Instant::now();
-
-            let mut time_millis: u128 = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            let time_millis: u128 = match SystemTime::now().duration_since(UNIX_EPOCH) {
                 Err(sys_time_err) => return Err(sys_time_err),
                 Ok(millis) => millis.as_millis(),
             };
+            Ok(DateMillis::from_unix_millis(time_millis))
+        }
+        /// The UTC date `time_millis` milliseconds after `UNIX_EPOCH`.
+        pub fn from_unix_millis(mut time_millis: u128) -> DateMillis {
             //println!("DateMillis\n\ttime_millis={}", time_millis);
             
             let mut date = DateMillis{
-                start: now,
+                start: Instant::now(),
                 display_mode: DisplayMode::ISO8601,
                 milliseconds : 0,
                 seconds : 0,
@@ -199,27 +204,30 @@ pub mod time_format {
             //println!("DateMillis::new Year Calc");
             let is_leap_year: bool;
             if date.year < 2000 {
+                // Four years from 1970 run regular, regular, leap, regular
                 if time_millis >= 2 * DateMillis::REGULAR_YEAR + DateMillis::LEAP_YEAR {
                     is_leap_year = false;
                     date.year += 3;
+                    time_millis -= 2 * DateMillis::REGULAR_YEAR + DateMillis::LEAP_YEAR;
                 } else if time_millis >= 2 * DateMillis::REGULAR_YEAR {
                     is_leap_year = true;
                     date.year += 2;
+                    time_millis -= 2 * DateMillis::REGULAR_YEAR;
                 } else {
                     is_leap_year = false;
-                    let year_count: Year = (time_millis / (2*DateMillis::REGULAR_YEAR)) as Year;
+                    let year_count: Year = (time_millis / DateMillis::REGULAR_YEAR) as Year;
                     date.year += year_count;
                     time_millis -= year_count as u128 * DateMillis::REGULAR_YEAR;
                 }
-            } else if time_millis >= 3 * DateMillis::REGULAR_YEAR {
-                is_leap_year = true;
-                date.year += 3;
-                time_millis -= 3 * DateMillis::REGULAR_YEAR;
-            } else {
+            } else if time_millis >= DateMillis::LEAP_YEAR {
+                // Four years from 2000 start with the leap year
                 is_leap_year = false;
+                time_millis -= DateMillis::LEAP_YEAR;
                 let year_count: Year = (time_millis / DateMillis::REGULAR_YEAR) as Year;
-                date.year += year_count;
+                date.year += 1 + year_count;
                 time_millis -= year_count as u128 * DateMillis::REGULAR_YEAR;
+            } else {
+                is_leap_year = true;
             }
             //println!("DateMillis\n\ttime_millis={}", time_millis);
             //println!("DateMillis:{{\n\t{}\n}}", date);
@@ -237,7 +245,8 @@ pub mod time_format {
             (month, days_before_month) = DateMillis::day_to_day_before_month_pair(day_count as u16, is_leap_year);
             date.month = month;
             //println!("DateMillis::new\n\tmonth={}\n\tdays_before_month={}", month, days_before_month);
-            date.day = (day_count as u16 - days_before_month) as u8;
+            // Days of the month count from 1
+            date.day = (day_count as u16 - days_before_month + 1) as u8;
             //println!("DateMillis\n\ttime_millis={}", time_millis);
             //println!("DateMillis:{{\n\t{}\n}}", date);
             
@@ -274,7 +283,7 @@ pub mod time_format {
 
             date.milliseconds = time_millis as u16;
 
-            Ok(date)
+            date
         }
         pub fn update(&mut self) {
             let mut elapsed = self.start.elapsed().as_millis();
@@ -325,7 +334,7 @@ pub mod time_format {
                 let days_in_month: u128 = DateMillis::days_in_month(self.month, leap_year) as u128;
                 
                 // Early finish
-                if self.day as u128 + days_elapsed < days_in_month {
+                if self.day as u128 + days_elapsed <= days_in_month {
                     self.day += days_elapsed as Day;
                     return;
                 }
@@ -395,7 +404,7 @@ pub mod time_format {
                 let days_in_month: u128 = DateMillis::days_in_month(self.month, leap_year) as u128;
                 
                 // Loop exit
-                if self.day as u128 + days_elapsed < days_in_month {
+                if self.day as u128 + days_elapsed <= days_in_month {
                     self.day += days_elapsed as Day;
                     return;
                 }
@@ -449,6 +458,9 @@ pub mod time_format {
                         self.hours, self.minutes, self.seconds,
                     )
                 }
+                DisplayMode::Pgn => {
+                    write!(f, "{:04}.{:02}.{:02}", self.year, self.month as u8, self.day)
+                }
                 DisplayMode::Debug => {
                     write!(f,
                         "{}/{}/{} {}:{}:{}.{}",
@@ -4,6 +4,7 @@ mod backend_x11;
 use std::sync::mpsc;
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 use crate::board::Square;
 use crate::common::ThreadIdentifier;
@@ -11,6 +12,8 @@ use crate::Board;
 use crate::LogLevel;
 use crate::LogMessage;
 use crate::common::common_lib::Log;
+use crate::game::GameController;
+use crate::rules::GameOutcome;
 
 #[cfg(target_os = "linux")]
 type CurrentBackend = backend_x11::X11Backend;
@@ -54,6 +57,13 @@ pub enum UiUpdate {
 }
 pub enum UiMessage {
     BoardUpdate(Board),
+    /// A move just played, in SAN.
+    MovePlayed(String),
+    /// A move the human entered that couldn't be played, and why.
+    IllegalMove(String),
+    /// Time left on the white and black clocks after a move in a timed game.
+    TimeLeft(Duration, Duration),
+    GameOver(GameOutcome),
 }
 
 impl Log for UIManager {
@@ -111,3 +121,42 @@ impl UIManager {
     ////    }
     //}
 }
+
+/// Plays a game in the terminal, sending the moves typed on stdin to the game thread and
+/// printing what it reports, until the game ends or `quit` is typed.
+pub fn play_in_terminal(game_controller: mpsc::Sender<GameController>, ui_in: mpsc::Receiver<UiMessage>) {
+    println!("Enter moves like e2e4 or Nf3, or quit to stop.");
+    // Reading stdin blocks, so it gets its own thread and the game carries on meanwhile
+    thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            let message = match line.trim() {
+                "" => continue,
+                "quit" => GameController::StopGame,
+                notation => GameController::MakeMove(notation.to_string()),
+            };
+            if game_controller.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    for message in ui_in {
+        match message {
+            UiMessage::BoardUpdate(board) => println!("{}", board.to_string()),
+            UiMessage::MovePlayed(san) => println!("{}", san),
+            UiMessage::IllegalMove(reason) => println!("{}", reason),
+            UiMessage::TimeLeft(white, black) => println!("White {}  Black {}", clock_time(white), clock_time(black)),
+            UiMessage::GameOver(outcome) => {
+                println!("{} by {}", outcome.to_pgn_result(), outcome.termination.to_pgn_tag());
+                break;
+            }
+        }
+    }
+}
+
+/// Formats a clock's time left as minutes and seconds, like `4:05`.
+fn clock_time(time_left: Duration) -> String {
+    let seconds = time_left.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}